@@ -0,0 +1,1079 @@
+// A TOML subset: `[table]`, `[[array]]`, `[array.headers]`, `key = value` with
+// strings, integers, booleans and single-line arrays, `#` comments
+
+use std::{
+    collections::HashMap,
+    error::Error,
+    fmt::Display,
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::Duration,
+};
+
+use crate::{
+    http::{
+        account::AuthLevel,
+        error_pages::{ErrorFormat, ErrorPage, ErrorPages},
+        extract::Handler,
+        file_cache::Caching,
+        method::Method,
+        request::HTTPRequest,
+        response::{HTTPResponse, StatusCode},
+        router::{Methods, RouteError, Router},
+        server::Client,
+        site::{Api, ApiFn, DataFn, Endpoint, SSRSite, SSite, Site, SiteType, TemplateSite},
+        static_dir::{Listing, StaticDir},
+        template,
+        uri::Host,
+        utils::{ContType, HTML, PLAIN},
+    },
+    traits::New,
+};
+
+/// Sites, APIs and directory mounts read from a config file
+///
+/// ```toml
+/// [auth]
+/// admin = 200
+///
+/// [[site]]
+/// path = "/"
+/// file = "www/index.html"
+/// cache = true            # or seconds between revalidations
+///
+/// [site.headers]
+/// "Cache-Control" = "max-age=60"
+///
+/// [[site]]
+/// path = "/time"
+/// render = "time"         # registered in `Handlers`
+/// type = "txt"
+///
+/// [[site]]
+/// path = "/blog/:post"
+/// template = "templates/post.html"
+/// data = "post"           # registered in `Handlers`, optional
+///
+/// [[api]]
+/// path = "/users/:id"
+/// method = "DELETE"
+/// handler = "delete_user"
+/// auth = "admin"
+///
+/// [[dir]]
+/// prefix = "/assets"
+/// root = "public"
+/// listing = "html"        # "off", "html" or "json"
+///
+/// [[error]]
+/// status = 404
+/// file = "www/404.html"   # or handler = "not_found"
+/// host = "example.com"    # optional, for this host only
+/// ```
+pub struct Config {
+    pub routes: Router<Methods<Endpoint>>,
+    /// The `[auth]` table
+    pub auth: HashMap<String, AuthLevel>,
+    pub errors: ErrorPages,
+    /// Files of static sites and error pages, template directories and roots
+    /// of mounts, for `reload::Reloader`
+    pub watch: Vec<PathBuf>,
+}
+
+/// Named functions a config binds to, `render` takes pages, `data` template
+/// data and `handler` pages or responses, of errors also error pages
+pub struct Handlers {
+    entries: HashMap<Box<str>, Registered>,
+}
+
+type SharedPage = Arc<dyn Fn(&HTTPRequest, &Client) -> Vec<u8> + Send + Sync>;
+type SharedResponse = Arc<dyn Fn(&HTTPRequest, &Client) -> HTTPResponse + Send + Sync>;
+type SharedData = Arc<dyn Fn(&HTTPRequest, &Client) -> template::Value + Send + Sync>;
+type SharedError = Arc<dyn Fn(&HTTPRequest, &Client, StatusCode, ErrorFormat) -> HTTPResponse + Send + Sync>;
+
+enum Registered {
+    Page(SharedPage),
+    Response(SharedResponse),
+    Data(SharedData),
+    Error(SharedError),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ConfigError {
+    kind: ConfigErrorKind,
+    line: usize,
+    detail: String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConfigErrorKind {
+    Io,
+    Syntax,
+    UnknownSection,
+    UnknownKey,
+    DuplicateKey,
+    MissingKey,
+    InvalidValue,
+    UnknownHandler,
+    Route,
+}
+
+impl ConfigError {
+    fn new(kind: ConfigErrorKind, line: usize, detail: impl Display) -> ConfigError {
+        ConfigError {
+            kind,
+            line,
+            detail: detail.to_string(),
+        }
+    }
+    pub fn kind(&self) -> ConfigErrorKind {
+        self.kind
+    }
+    /// 1-based, 0 if the file could not be read
+    pub fn line(&self) -> usize {
+        self.line
+    }
+    pub fn detail(&self) -> &str {
+        &self.detail
+    }
+}
+
+impl ConfigErrorKind {
+    pub fn reason(&self) -> &str {
+        match self {
+            ConfigErrorKind::Io => "Could not read Config",
+            ConfigErrorKind::Syntax => "Syntax Error",
+            ConfigErrorKind::UnknownSection => "Unknown Section",
+            ConfigErrorKind::UnknownKey => "Unknown Key",
+            ConfigErrorKind::DuplicateKey => "Duplicate Key",
+            ConfigErrorKind::MissingKey => "Missing Key",
+            ConfigErrorKind::InvalidValue => "Invalid Value",
+            ConfigErrorKind::UnknownHandler => "Unknown Handler",
+            ConfigErrorKind::Route => "Invalid Route",
+        }
+    }
+}
+
+impl Display for ConfigError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} in Line {}: {}", self.kind.reason(), self.line, self.detail)
+    }
+}
+
+impl Error for ConfigError {}
+
+impl Handlers {
+    /// For `render` of sites and `handler` of APIs, sent with the `type` of the entry
+    pub fn page(
+        mut self,
+        name: &str,
+        page: impl Fn(&HTTPRequest, &Client) -> Vec<u8> + Send + Sync + 'static,
+    ) -> Handlers {
+        self.entries.insert(name.into(), Registered::Page(Arc::new(page)));
+        self
+    }
+    /// For `handler` of APIs
+    pub fn response(
+        mut self,
+        name: &str,
+        response: impl Fn(&HTTPRequest, &Client) -> HTTPResponse + Send + Sync + 'static,
+    ) -> Handlers {
+        self.entries
+            .insert(name.into(), Registered::Response(Arc::new(response)));
+        self
+    }
+    /// For `data` of template sites
+    pub fn data(
+        mut self,
+        name: &str,
+        data: impl Fn(&HTTPRequest, &Client) -> template::Value + Send + Sync + 'static,
+    ) -> Handlers {
+        self.entries.insert(name.into(), Registered::Data(Arc::new(data)));
+        self
+    }
+    /// For `handler` of error pages
+    pub fn error(
+        mut self,
+        name: &str,
+        page: impl Fn(&HTTPRequest, &Client, StatusCode, ErrorFormat) -> HTTPResponse + Send + Sync + 'static,
+    ) -> Handlers {
+        self.entries.insert(name.into(), Registered::Error(Arc::new(page)));
+        self
+    }
+    /// For `handler` of APIs, with extractor arguments like `ApiFn::handler`
+    pub fn handler<Args: 'static>(mut self, name: &str, handler: impl Handler<Args>) -> Handlers {
+        let handler = Arc::new(handler);
+        self.entries.insert(
+            name.into(),
+            Registered::Response(Arc::new(move |req, res| handler.call(req, res))),
+        );
+        self
+    }
+}
+
+impl Default for Handlers {
+    fn default() -> Self {
+        Self {
+            entries: HashMap::new(),
+        }
+    }
+}
+
+impl New for Handlers {}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Value {
+    Str(String),
+    Int(i64),
+    Bool(bool),
+    Array(Vec<Value>),
+}
+
+struct Entry {
+    key: String,
+    value: Value,
+    line: usize,
+}
+
+struct Section {
+    name: String,
+    line: usize,
+    entries: Vec<Entry>,
+    headers: Vec<Entry>,
+}
+
+impl Value {
+    fn kind(&self) -> &str {
+        match self {
+            Value::Str(_) => "string",
+            Value::Int(_) => "integer",
+            Value::Bool(_) => "boolean",
+            Value::Array(_) => "array",
+        }
+    }
+}
+
+fn syntax(line: usize, detail: impl Display) -> ConfigError {
+    ConfigError::new(ConfigErrorKind::Syntax, line, detail)
+}
+
+// A quoted string at the start of `src`, returns the rest after the closing quote
+fn parse_string(src: &str, line: usize) -> Result<(String, &str), ConfigError> {
+    let mut out = String::new();
+    let mut chars = src[1..].char_indices();
+    while let Some((i, c)) = chars.next() {
+        match c {
+            '"' => return Ok((out, &src[i + 2..])),
+            '\\' => match chars.next() {
+                Some((_, '"')) => out.push('"'),
+                Some((_, '\\')) => out.push('\\'),
+                Some((_, 'n')) => out.push('\n'),
+                Some((_, 't')) => out.push('\t'),
+                _ => return Err(syntax(line, "Invalid escape")),
+            },
+            c => out.push(c),
+        }
+    }
+    Err(syntax(line, "Unterminated string"))
+}
+
+// One value at the start of `src`, returns the unparsed rest
+fn parse_value(src: &str, line: usize) -> Result<(Value, &str), ConfigError> {
+    let src = src.trim_start();
+    if src.starts_with('"') {
+        let (s, rest) = parse_string(src, line)?;
+        return Ok((Value::Str(s), rest));
+    }
+    if let Some(mut rest) = src.strip_prefix('[') {
+        let mut items = Vec::new();
+        loop {
+            rest = rest.trim_start();
+            if let Some(after) = rest.strip_prefix(']') {
+                return Ok((Value::Array(items), after));
+            }
+            let (item, after) = parse_value(rest, line)?;
+            items.push(item);
+            rest = after.trim_start();
+            if let Some(after) = rest.strip_prefix(',') {
+                rest = after;
+            } else if !rest.starts_with(']') {
+                return Err(syntax(line, "Expected , or ] in array"));
+            }
+        }
+    }
+    let end = src
+        .find(|c: char| c == ',' || c == ']' || c == '#' || c.is_whitespace())
+        .unwrap_or(src.len());
+    let (word, rest) = src.split_at(end);
+    let value = match word {
+        "true" => Value::Bool(true),
+        "false" => Value::Bool(false),
+        word => match word.replace('_', "").parse() {
+            Ok(int) => Value::Int(int),
+            Err(_) => return Err(syntax(line, format!("Invalid value \"{}\"", word))),
+        },
+    };
+    Ok((value, rest))
+}
+
+fn parse_key(src: &str, line: usize) -> Result<(String, &str), ConfigError> {
+    if src.starts_with('"') {
+        return parse_string(src, line);
+    }
+    let end = src
+        .find(|c: char| !(c.is_ascii_alphanumeric() || c == '_' || c == '-'))
+        .unwrap_or(src.len());
+    if end == 0 {
+        return Err(syntax(line, "Expected key"));
+    }
+    Ok((src[..end].to_string(), &src[end..]))
+}
+
+fn parse_sections(src: &str) -> Result<Vec<Section>, ConfigError> {
+    let mut sections: Vec<Section> = Vec::new();
+    // Whether `key = value` lines go to the headers of the last section
+    let mut in_headers = false;
+    for (i, raw) in src.lines().enumerate() {
+        let line = i + 1;
+        let text = raw.trim();
+        if text.is_empty() || text.starts_with('#') {
+            continue;
+        }
+        if let Some(header) = text.strip_prefix('[') {
+            let (array, inner) = match header.strip_prefix('[') {
+                Some(rest) => (true, rest.split_once("]]")),
+                None => (false, header.split_once(']')),
+            };
+            let (name, rest) = inner.ok_or_else(|| syntax(line, "Unterminated section header"))?;
+            let rest = rest.trim_start();
+            if !rest.is_empty() && !rest.starts_with('#') {
+                return Err(syntax(line, "Trailing characters after section header"));
+            }
+            let name = name.trim();
+            match (array, name) {
+                (true, "site" | "api" | "dir" | "error") | (false, "auth") => {
+                    if !array && sections.iter().any(|s| s.name == name) {
+                        return Err(ConfigError::new(ConfigErrorKind::DuplicateKey, line, name));
+                    }
+                    sections.push(Section {
+                        name: name.to_string(),
+                        line,
+                        entries: Vec::new(),
+                        headers: Vec::new(),
+                    });
+                    in_headers = false;
+                }
+                (false, _) if name.strip_suffix(".headers").is_some_and(|parent| {
+                    sections.last().is_some_and(|last| last.name == parent && parent != "auth" && parent != "error")
+                }) =>
+                {
+                    in_headers = true;
+                }
+                _ => return Err(ConfigError::new(ConfigErrorKind::UnknownSection, line, name)),
+            }
+            continue;
+        }
+        let section = sections
+            .last_mut()
+            .ok_or_else(|| syntax(line, "Key outside of a section"))?;
+        let (key, rest) = parse_key(text, line)?;
+        let rest = rest
+            .trim_start()
+            .strip_prefix('=')
+            .ok_or_else(|| syntax(line, "Expected ="))?;
+        let (value, rest) = parse_value(rest, line)?;
+        let rest = rest.trim_start();
+        if !rest.is_empty() && !rest.starts_with('#') {
+            return Err(syntax(line, "Trailing characters after value"));
+        }
+        let target = if in_headers {
+            &mut section.headers
+        } else {
+            &mut section.entries
+        };
+        if target.iter().any(|entry| entry.key == key) {
+            return Err(ConfigError::new(ConfigErrorKind::DuplicateKey, line, key));
+        }
+        target.push(Entry { key, value, line });
+    }
+    Ok(sections)
+}
+
+// Takes the known keys out of a section, whatever is left is reported as unknown
+struct Fields<'s> {
+    section: &'s Section,
+    used: Vec<&'s str>,
+}
+
+impl<'s> Fields<'s> {
+    fn get(&mut self, key: &'s str) -> Option<&'s Entry> {
+        self.used.push(key);
+        self.section.entries.iter().find(|entry| entry.key == key)
+    }
+    fn require(&mut self, key: &'s str) -> Result<&'s Entry, ConfigError> {
+        let line = self.section.line;
+        let name = &self.section.name;
+        self.get(key).ok_or_else(|| {
+            ConfigError::new(
+                ConfigErrorKind::MissingKey,
+                line,
+                format!("\"{}\" in [[{}]]", key, name),
+            )
+        })
+    }
+    fn finish(self) -> Result<(), ConfigError> {
+        match self
+            .section
+            .entries
+            .iter()
+            .find(|entry| !self.used.contains(&entry.key.as_str()))
+        {
+            Some(entry) => Err(ConfigError::new(
+                ConfigErrorKind::UnknownKey,
+                entry.line,
+                format!("\"{}\" in [[{}]]", entry.key, self.section.name),
+            )),
+            None => Ok(()),
+        }
+    }
+}
+
+fn invalid(entry: &Entry, expected: &str) -> ConfigError {
+    ConfigError::new(
+        ConfigErrorKind::InvalidValue,
+        entry.line,
+        format!("\"{}\" has to be {}, found {}", entry.key, expected, entry.value.kind()),
+    )
+}
+
+impl Entry {
+    fn str(&self) -> Result<&str, ConfigError> {
+        match &self.value {
+            Value::Str(s) => Ok(s),
+            _ => Err(invalid(self, "a string")),
+        }
+    }
+    fn bool(&self) -> Result<bool, ConfigError> {
+        match self.value {
+            Value::Bool(b) => Ok(b),
+            _ => Err(invalid(self, "a boolean")),
+        }
+    }
+    fn strings(&self) -> Result<Vec<&str>, ConfigError> {
+        match &self.value {
+            Value::Array(items) => items
+                .iter()
+                .map(|item| match item {
+                    Value::Str(s) => Ok(s.as_str()),
+                    _ => Err(invalid(self, "an array of strings")),
+                })
+                .collect(),
+            _ => Err(invalid(self, "an array of strings")),
+        }
+    }
+    fn content_type(&self) -> Result<ContType, ConfigError> {
+        let name = self.str()?;
+        ContType::for_extension(name).ok_or_else(|| {
+            ConfigError::new(
+                ConfigErrorKind::InvalidValue,
+                self.line,
+                format!("Unknown type \"{}\"", name),
+            )
+        })
+    }
+}
+
+fn level(value: i64, line: usize) -> Result<AuthLevel, ConfigError> {
+    AuthLevel::try_from(value).map_err(|_| {
+        ConfigError::new(
+            ConfigErrorKind::InvalidValue,
+            line,
+            format!("Auth level {} is not between 0 and 255", value),
+        )
+    })
+}
+
+impl Config {
+    pub fn load(path: impl AsRef<Path>, handlers: &Handlers) -> Result<Config, ConfigError> {
+        let path = path.as_ref();
+        let src = std::fs::read_to_string(path).map_err(|e| {
+            ConfigError::new(ConfigErrorKind::Io, 0, format!("{}: {}", path.display(), e))
+        })?;
+        Config::parse(&src, handlers)
+    }
+
+    pub fn parse(src: &str, handlers: &Handlers) -> Result<Config, ConfigError> {
+        let sections = parse_sections(src)?;
+        let mut config = Config {
+            routes: Router::new(),
+            auth: HashMap::new(),
+            errors: ErrorPages::new(),
+            watch: Vec::new(),
+        };
+        if let Some(auth) = sections.iter().find(|section| section.name == "auth") {
+            for entry in auth.entries.iter() {
+                match entry.value {
+                    Value::Int(value) => {
+                        config.auth.insert(entry.key.clone(), level(value, entry.line)?);
+                    }
+                    _ => return Err(invalid(entry, "an integer")),
+                }
+            }
+        }
+        for section in sections.iter() {
+            let route = |e: RouteError| ConfigError::new(ConfigErrorKind::Route, section.line, e);
+            match section.name.as_str() {
+                "site" => {
+                    let (path, site) = config.site(section, handlers)?;
+                    match site {
+                        Endpoint::Site(Site {
+                            site: SiteType::StaticSite(ref ss),
+                            ..
+                        }) => config.watch.push(PathBuf::from(ss.file_path.as_ref())),
+                        // Includes and layouts usually sit next to the template
+                        Endpoint::Site(Site {
+                            site: SiteType::Template(ref ts),
+                            ..
+                        }) => {
+                            let dir = Path::new(ts.template.as_ref()).parent().unwrap_or(Path::new(""));
+                            let dir = if dir.as_os_str().is_empty() { Path::new(".") } else { dir };
+                            if !config.watch.iter().any(|path| path == dir) {
+                                config.watch.push(dir.to_path_buf());
+                            }
+                        }
+                        _ => {}
+                    }
+                    config.routes.route(Method::GET, &path, site).map_err(route)?;
+                }
+                "api" => {
+                    let (method, path, api) = config.api(section, handlers)?;
+                    config.routes.route(method, &path, api).map_err(route)?;
+                }
+                "dir" => {
+                    let dir = config.dir(section)?;
+                    config.watch.push(dir.root().to_path_buf());
+                    Endpoint::mount(&mut config.routes, dir).map_err(route)?;
+                }
+                "error" => {
+                    let (host, status, page) = config.error(section, handlers)?;
+                    if let ErrorPage::File(ref path) = page {
+                        config.watch.push(path.clone());
+                    }
+                    let errors = std::mem::take(&mut config.errors);
+                    config.errors = match host {
+                        // Already checked by `error`
+                        Some(host) => errors
+                            .host(&host, status, page)
+                            .map_err(|e| ConfigError::new(ConfigErrorKind::InvalidValue, section.line, e))?,
+                        None => errors.page(status, page),
+                    };
+                }
+                _ => {}
+            }
+        }
+        Ok(config)
+    }
+
+    fn auth_level(&self, fields: &mut Fields) -> Result<AuthLevel, ConfigError> {
+        let entry = match fields.get("auth") {
+            Some(entry) => entry,
+            None => return Ok(0),
+        };
+        match &entry.value {
+            Value::Int(value) => level(*value, entry.line),
+            Value::Str(name) => self.auth.get(name).copied().ok_or_else(|| {
+                ConfigError::new(
+                    ConfigErrorKind::InvalidValue,
+                    entry.line,
+                    format!("Auth level \"{}\" is not defined in [auth]", name),
+                )
+            }),
+            _ => Err(invalid(entry, "an integer or a name from [auth]")),
+        }
+    }
+
+    fn site(&self, section: &Section, handlers: &Handlers) -> Result<(String, Endpoint), ConfigError> {
+        let mut fields = Fields {
+            section,
+            used: Vec::new(),
+        };
+        let path = fields.require("path")?.str()?.to_string();
+        let auth = self.auth_level(&mut fields)?;
+        let file = fields.get("file");
+        let render = fields.get("render");
+        let template = fields.get("template");
+        let data = fields.get("data");
+        let cache = fields.get("cache");
+        let file_type = fields.get("type").map(Entry::content_type).transpose()?;
+        if let (Some(data), None) = (data, template) {
+            return Err(ConfigError::new(
+                ConfigErrorKind::UnknownKey,
+                data.line,
+                "\"data\" only applies to sites with \"template\"",
+            ));
+        }
+        if let (Some(cache), None) = (cache, file) {
+            return Err(ConfigError::new(
+                ConfigErrorKind::UnknownKey,
+                cache.line,
+                "\"cache\" only applies to sites with \"file\"",
+            ));
+        }
+        let (site, default_type) = match (file, render, template) {
+            (Some(file), None, None) => {
+                let file_path = file.str()?;
+                let cache = match cache {
+                    None => Caching::Off,
+                    Some(entry) => match entry.value {
+                        Value::Bool(true) => Caching::Shared,
+                        Value::Bool(false) => Caching::Off,
+                        Value::Int(secs) if secs >= 0 => Caching::Every(Duration::from_secs(secs as u64)),
+                        _ => return Err(invalid(entry, "a boolean or seconds")),
+                    },
+                };
+                let default_type = Path::new(file_path)
+                    .extension()
+                    .and_then(|ext| ext.to_str())
+                    .map(ContType::from_extension)
+                    .unwrap_or(HTML);
+                (
+                    SiteType::StaticSite(SSite {
+                        file_path: file_path.into(),
+                        cache,
+                    }),
+                    default_type,
+                )
+            }
+            (None, Some(render), None) => {
+                let page = match handlers.entries.get(render.str()?) {
+                    Some(Registered::Page(page)) => page.clone(),
+                    Some(_) => {
+                        return Err(ConfigError::new(
+                            ConfigErrorKind::UnknownHandler,
+                            render.line,
+                            format!("\"{}\" is not a page", render.str()?),
+                        ));
+                    }
+                    None => return Err(unknown_handler(render)),
+                };
+                (
+                    SiteType::ServerSideRenderedSite(SSRSite {
+                        genfunc: Box::new(move |req, res| page(req, res)),
+                    }),
+                    HTML,
+                )
+            }
+            (None, None, Some(template)) => {
+                let data: DataFn = match data {
+                    None => Box::new(|_, _| template::Value::Null),
+                    Some(data) => match handlers.entries.get(data.str()?) {
+                        Some(Registered::Data(data)) => {
+                            let data = data.clone();
+                            Box::new(move |req, res| data(req, res))
+                        }
+                        Some(_) => {
+                            return Err(ConfigError::new(
+                                ConfigErrorKind::UnknownHandler,
+                                data.line,
+                                format!("\"{}\" is not a data function", data.str()?),
+                            ));
+                        }
+                        None => return Err(unknown_handler(data)),
+                    },
+                };
+                (
+                    SiteType::Template(TemplateSite {
+                        template: template.str()?.into(),
+                        data,
+                    }),
+                    HTML,
+                )
+            }
+            _ => {
+                return Err(ConfigError::new(
+                    ConfigErrorKind::MissingKey,
+                    section.line,
+                    "[[site]] needs one of \"file\", \"render\" or \"template\"",
+                ));
+            }
+        };
+        fields.finish()?;
+        Ok((
+            path.clone(),
+            Endpoint::Site(Site {
+                file_type: file_type.unwrap_or(default_type),
+                path: path.into(),
+                auth,
+                site,
+                headers: headers(section)?,
+            }),
+        ))
+    }
+
+    fn api(&self, section: &Section, handlers: &Handlers) -> Result<(Method, String, Endpoint), ConfigError> {
+        let mut fields = Fields {
+            section,
+            used: Vec::new(),
+        };
+        let path = fields.require("path")?.str()?.to_string();
+        let handler = fields.require("handler")?;
+        let method = match fields.get("method") {
+            Some(entry) => {
+                let method = entry.str()?.to_ascii_uppercase();
+                if method.is_empty() || !method.bytes().all(|b| b.is_ascii_alphabetic()) {
+                    return Err(ConfigError::new(
+                        ConfigErrorKind::InvalidValue,
+                        entry.line,
+                        format!("Invalid method \"{}\"", method),
+                    ));
+                }
+                Method::from_str(&method)
+            }
+            None => Method::GET,
+        };
+        let filetype = fields
+            .get("type")
+            .map(Entry::content_type)
+            .transpose()?
+            .unwrap_or(PLAIN);
+        let auth = self.auth_level(&mut fields)?;
+        fields.finish()?;
+        let fnp = match handlers.entries.get(handler.str()?) {
+            Some(Registered::Page(page)) => {
+                let page = page.clone();
+                ApiFn::raw(move |req, res| page(req, res))
+            }
+            Some(Registered::Response(response)) => {
+                let response = response.clone();
+                ApiFn::response(move |req, res| response(req, res))
+            }
+            Some(Registered::Data(_) | Registered::Error(_)) => {
+                return Err(ConfigError::new(
+                    ConfigErrorKind::UnknownHandler,
+                    handler.line,
+                    format!("\"{}\" is not a page or response", handler.str()?),
+                ));
+            }
+            None => return Err(unknown_handler(handler)),
+        };
+        Ok((
+            method.clone(),
+            path.clone(),
+            Endpoint::Api(Api {
+                path: path.into(),
+                method,
+                fnp,
+                filetype,
+                auth,
+                headers: headers(section)?,
+            }),
+        ))
+    }
+
+    fn dir(&self, section: &Section) -> Result<StaticDir, ConfigError> {
+        let mut fields = Fields {
+            section,
+            used: Vec::new(),
+        };
+        let prefix = fields.require("prefix")?.str()?;
+        let root = fields.require("root")?.str()?;
+        let mut dir = StaticDir::mount(prefix, PathBuf::from(root));
+        if let Some(entry) = fields.get("index") {
+            dir = dir.index(&entry.strings()?);
+        }
+        if let Some(entry) = fields.get("listing") {
+            dir = dir.listing(match entry.str()? {
+                "off" => Listing::Off,
+                "html" => Listing::Html,
+                "json" => Listing::Json,
+                other => {
+                    return Err(ConfigError::new(
+                        ConfigErrorKind::InvalidValue,
+                        entry.line,
+                        format!("Listing has to be \"off\", \"html\" or \"json\", found \"{}\"", other),
+                    ));
+                }
+            });
+        }
+        if let Some(entry) = fields.get("hidden") {
+            dir = dir.show_hidden(entry.bool()?);
+        }
+        fields.finish()?;
+        for (key, value) in headers(section)? {
+            dir = dir.header(&key, &value);
+        }
+        Ok(dir)
+    }
+
+    fn error(&self, section: &Section, handlers: &Handlers) -> Result<(Option<String>, StatusCode, ErrorPage), ConfigError> {
+        let mut fields = Fields {
+            section,
+            used: Vec::new(),
+        };
+        let entry = fields.require("status")?;
+        let status = match entry.value {
+            Value::Int(code @ 400..=599) => StatusCode::from_code(code as u16).ok_or_else(|| {
+                ConfigError::new(ConfigErrorKind::InvalidValue, entry.line, format!("Unknown status {}", code))
+            })?,
+            Value::Int(code) => {
+                return Err(ConfigError::new(
+                    ConfigErrorKind::InvalidValue,
+                    entry.line,
+                    format!("Status {} is not an error", code),
+                ));
+            }
+            _ => return Err(invalid(entry, "an integer")),
+        };
+        let host = match fields.get("host") {
+            Some(entry) => {
+                let host = entry.str()?;
+                if Host::parse(host).is_err() {
+                    return Err(ConfigError::new(
+                        ConfigErrorKind::InvalidValue,
+                        entry.line,
+                        format!("Invalid host \"{}\"", host),
+                    ));
+                }
+                Some(host.to_string())
+            }
+            None => None,
+        };
+        let page = match (fields.get("file"), fields.get("handler")) {
+            (Some(file), None) => ErrorPage::file(file.str()?),
+            (None, Some(handler)) => match handlers.entries.get(handler.str()?) {
+                Some(Registered::Error(page)) => {
+                    let page = page.clone();
+                    ErrorPage::handler(move |req, res, status, format| page(req, res, status, format))
+                }
+                Some(Registered::Response(response)) => {
+                    let response = response.clone();
+                    ErrorPage::handler(move |req, res, _, _| response(req, res))
+                }
+                Some(_) => {
+                    return Err(ConfigError::new(
+                        ConfigErrorKind::UnknownHandler,
+                        handler.line,
+                        format!("\"{}\" is not an error page or response", handler.str()?),
+                    ));
+                }
+                None => return Err(unknown_handler(handler)),
+            },
+            _ => {
+                return Err(ConfigError::new(
+                    ConfigErrorKind::MissingKey,
+                    section.line,
+                    "[[error]] needs either \"file\" or \"handler\"",
+                ));
+            }
+        };
+        fields.finish()?;
+        Ok((host, status, page))
+    }
+}
+
+fn unknown_handler(entry: &Entry) -> ConfigError {
+    ConfigError::new(
+        ConfigErrorKind::UnknownHandler,
+        entry.line,
+        format!("No handler named \"{}\"", entry.str().unwrap_or_default()),
+    )
+}
+
+fn headers(section: &Section) -> Result<Vec<(String, String)>, ConfigError> {
+    section
+        .headers
+        .iter()
+        .map(|entry| {
+            let value = entry.str()?;
+            if entry.key.is_empty()
+                || !entry.key.bytes().all(|b| b.is_ascii_alphanumeric() || b"!#$%&'*+-.^_`|~".contains(&b))
+                || value.contains(['\r', '\n'])
+            {
+                return Err(ConfigError::new(
+                    ConfigErrorKind::InvalidValue,
+                    entry.line,
+                    format!("Invalid header \"{}\"", entry.key),
+                ));
+            }
+            Ok((entry.key.clone(), value.to_string()))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::http::{router::Dispatch, router::Routes};
+
+    fn handlers() -> Handlers {
+        Handlers::new()
+            .page("time", |_, _| b"12:00".to_vec())
+            .response("delete_user", |req, _| {
+                let id = req.parts.params.get("id").unwrap_or_default().to_string();
+                HTTPResponse::new().status_code(StatusCode::OK).body(id.into_bytes())
+            })
+            .data("post", |_, _| template::Value::Null)
+            .error("not_found", |_, _, status, _| HTTPResponse::new().status_code(status))
+    }
+
+    fn err(src: &str) -> (ConfigErrorKind, usize) {
+        match Config::parse(src, &handlers()) {
+            Ok(_) => panic!("accepted {:?}", src),
+            Err(e) => (e.kind(), e.line()),
+        }
+    }
+
+    #[test]
+    fn syntax() {
+        use ConfigErrorKind::Syntax;
+        assert_eq!(err("[[site]]\npath = \"/"), (Syntax, 2));
+        assert_eq!(err("[[site]]\npath = \"\\q\""), (Syntax, 2));
+        assert_eq!(err("[[site]]\n\npath = \"/\" x"), (Syntax, 3));
+        assert_eq!(err("[[site]] x"), (Syntax, 1));
+        assert_eq!(err("[[site]\npath = \"/\""), (Syntax, 1));
+        assert_eq!(err("path = \"/\""), (Syntax, 1));
+        assert_eq!(err("[[site]]\npath \"/\""), (Syntax, 2));
+        assert_eq!(err("[[dir]]\nindex = [\"a\" \"b\"]"), (Syntax, 2));
+        assert_eq!(err("[[site]]\ncache = maybe"), (Syntax, 2));
+        let e = Config::parse("[[site]]\npath = \"/", &handlers()).err().unwrap();
+        assert_eq!(e.to_string(), "Syntax Error in Line 2: Unterminated string");
+    }
+
+    #[test]
+    fn keys_and_sections() {
+        use ConfigErrorKind::*;
+        assert_eq!(err("[[site]]\npath = \"/\"\npath = \"/a\""), (DuplicateKey, 3));
+        assert_eq!(err("[auth]\na = 1\n[auth]"), (DuplicateKey, 3));
+        assert_eq!(err("[[site]]\npath = \"/\"\n[site.headers]\nA = \"1\"\nA = \"2\""), (DuplicateKey, 5));
+        assert_eq!(err("[[page]]"), (UnknownSection, 1));
+        assert_eq!(err("[site]"), (UnknownSection, 1));
+        assert_eq!(err("[auth]\n[auth.headers]"), (UnknownSection, 2));
+        assert_eq!(err("[[site]]\npath = \"/\"\n[api.headers]"), (UnknownSection, 3));
+        assert_eq!(err("[[site]]\npath = \"/\"\nfile = \"a.html\"\nfoo = 1"), (UnknownKey, 4));
+        assert_eq!(err("\n[[api]]\npath = \"/\""), (MissingKey, 2));
+        assert_eq!(err("[[site]]\npath = \"/\""), (MissingKey, 1));
+        assert_eq!(err("[[error]]\nstatus = 404"), (MissingKey, 1));
+        assert_eq!(err("[[site]]\npath = \"/\"\nrender = \"time\"\ndata = \"post\""), (UnknownKey, 4));
+        assert_eq!(err("[[site]]\npath = \"/\"\ntemplate = \"a.html\"\ncache = true"), (UnknownKey, 4));
+    }
+
+    #[test]
+    fn values() {
+        use ConfigErrorKind::*;
+        assert_eq!(err("[[site]]\npath = \"/\"\nrender = \"nope\""), (UnknownHandler, 3));
+        assert_eq!(err("[[site]]\npath = \"/\"\nrender = \"post\""), (UnknownHandler, 3));
+        assert_eq!(err("[[site]]\npath = \"/\"\ntemplate = \"a.html\"\ndata = \"time\""), (UnknownHandler, 4));
+        assert_eq!(err("[[api]]\npath = \"/\"\nhandler = \"not_found\""), (UnknownHandler, 3));
+        assert_eq!(err("[[error]]\nstatus = 404\nhandler = \"post\""), (UnknownHandler, 3));
+        assert_eq!(err("[[site]]\npath = 1\nfile = \"a.html\""), (InvalidValue, 2));
+        assert_eq!(err("[[site]]\npath = \"/\"\nfile = \"a.html\"\ncache = -1"), (InvalidValue, 4));
+        assert_eq!(err("[[site]]\npath = \"/\"\nfile = \"a.html\"\ntype = \"nope\""), (InvalidValue, 4));
+        assert_eq!(err("[[api]]\npath = \"/\"\nhandler = \"time\"\nmethod = \"GE T\""), (InvalidValue, 4));
+        assert_eq!(err("[[api]]\npath = \"/\"\nhandler = \"time\"\nauth = \"admin\""), (InvalidValue, 4));
+        assert_eq!(err("[auth]\nadmin = 256"), (InvalidValue, 2));
+        assert_eq!(err("[[dir]]\nprefix = \"/\"\nroot = \".\"\nlisting = \"xml\""), (InvalidValue, 4));
+        assert_eq!(err("[[error]]\nstatus = 200\nfile = \"a.html\""), (InvalidValue, 2));
+        assert_eq!(err("[[error]]\nstatus = 499\nfile = \"a.html\""), (InvalidValue, 2));
+        assert_eq!(err("[[error]]\nstatus = \"404\"\nfile = \"a.html\""), (InvalidValue, 2));
+        assert_eq!(err("[[error]]\nstatus = 404\nfile = \"a.html\"\nhost = \"a b\""), (InvalidValue, 4));
+        assert_eq!(err("[[site]]\npath = \"/\"\nfile = \"a.html\"\n[site.headers]\n\"Bad Header\" = \"1\""), (InvalidValue, 5));
+        assert_eq!(err("[[site]]\npath = \"/\"\nfile = \"a.html\"\n[site.headers]\nX = \"a\\nb\""), (InvalidValue, 5));
+    }
+
+    #[test]
+    fn routes() {
+        use ConfigErrorKind::Route;
+        let site = |path: &str| format!("[[site]]\npath = \"{}\"\nrender = \"time\"\n", path);
+        assert_eq!(err(&(site("/u/:id") + &site("/u/:uid"))), (Route, 4));
+        assert_eq!(err(&(site("/u") + &site("/u"))), (Route, 4));
+        assert_eq!(err(&site("/a/*rest/b")), (Route, 1));
+        assert_eq!(err(&(site("/assets/*file") + "[[dir]]\nprefix = \"/assets\"\nroot = \".\"")), (Route, 4));
+        // Another method on the same path is fine
+        assert!(Config::parse(&(site("/u") + "[[api]]\npath = \"/u\"\nmethod = \"post\"\nhandler = \"time\""), &handlers()).is_ok());
+    }
+
+    #[test]
+    fn doc_example() {
+        let src = r#"
+[auth]
+admin = 200
+
+[[site]]
+path = "/"
+file = "www/index.html"
+cache = true            # or seconds between revalidations
+
+[site.headers]
+"Cache-Control" = "max-age=60"
+
+[[site]]
+path = "/time"
+render = "time"         # registered in `Handlers`
+type = "txt"
+
+[[site]]
+path = "/blog/:post"
+template = "templates/post.html"
+data = "post"           # registered in `Handlers`, optional
+
+[[api]]
+path = "/users/:id"
+method = "DELETE"
+handler = "delete_user"
+auth = "admin"
+
+[[dir]]
+prefix = "/assets"
+root = "public"
+listing = "html"        # "off", "html" or "json"
+
+[[error]]
+status = 404
+file = "www/404.html"   # or handler = "not_found"
+host = "example.com"    # optional, for this host only
+"#;
+        let config = match Config::parse(src, &handlers()) {
+            Ok(config) => config,
+            Err(e) => panic!("{}", e),
+        };
+        assert_eq!(config.auth.get("admin"), Some(&200));
+        assert!(config.watch.contains(&PathBuf::from("www/index.html")));
+        assert!(config.watch.contains(&PathBuf::from("templates")));
+        assert!(config.watch.contains(&PathBuf::from("www/404.html")));
+        assert!(!config.errors.is_empty());
+        for (method, path) in [
+            (Method::GET, "/"),
+            (Method::GET, "/blog/hello"),
+            (Method::GET, "/assets"),
+            (Method::GET, "/assets/css/a.css"),
+        ] {
+            assert!(matches!(config.routes.dispatch(&method, path), Dispatch::Found(..)), "{}", path);
+        }
+        assert!(matches!(config.routes.dispatch(&Method::GET, "/users/7"), Dispatch::MethodNotAllowed(_)));
+
+        let res = Client {
+            routes: Routes {
+                router: config.routes,
+                errors: config.errors,
+            }
+            .into(),
+            ..Client::new()
+        };
+        let host = Host::parse("example.com").unwrap();
+        let run = |src: &str| {
+            let mut req = HTTPRequest::new();
+            assert!(req.from_string(&src.to_string(), &res).is_none(), "{}", src);
+            String::from_utf8(HTTPResponse::run(&mut req, &res, &host)).unwrap()
+        };
+        let out = run("GET /time HTTP/1.1\r\nHost: example.com\r\n\r\n");
+        assert!(out.starts_with("HTTP/1.1 200 OK\r\n") && out.ends_with("\r\n\r\n12:00"), "{}", out);
+        assert!(out.contains("\r\nContent-Type: text/plain"), "{}", out);
+        let out = run("DELETE /users/7 HTTP/1.1\r\nHost: example.com\r\n\r\n");
+        assert!(out.starts_with("HTTP/1.1 200 OK\r\n") && out.ends_with("\r\n\r\n7"), "{}", out);
+        let out = run("GET /users/7 HTTP/1.1\r\nHost: example.com\r\n\r\n");
+        assert!(out.contains("\r\nAllow: DELETE, OPTIONS\r\n"), "{}", out);
+        // The configured page does not exist, the builtin one answers
+        let out = run("GET /nope HTTP/1.1\r\nHost: example.com\r\n\r\n");
+        assert!(out.starts_with("HTTP/1.1 404 Not Found\r\n"), "{}", out);
+    }
+}
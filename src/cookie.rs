@@ -0,0 +1,409 @@
+use std::{
+    fmt::Display,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use crate::{
+    http::{
+        crypto::{self, KEY_LEN, NONCE_LEN, SHA256_LEN},
+        date::HttpDate,
+        form::percent_decode,
+        request::HTTPRequest,
+        utils::AllowedExtentions,
+    },
+    traits::New,
+};
+
+#[derive(Clone)]
+pub struct Cookie {
+    pub name: String,
+    pub value: String,
+    pub max_age: Option<Duration>,
+    pub expires: Option<HttpDate>,
+    pub path: Option<String>,
+    pub domain: Option<String>,
+    pub secure: bool,
+    pub http_only: bool,
+    pub same_site: Option<SameSite>,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum SameSite {
+    Strict,
+    Lax,
+    None,
+}
+
+/// Cookies of one request plus the changes which have to be sent back
+pub struct CookieJar {
+    original: Vec<(String, String)>,
+    delta: Vec<Cookie>,
+}
+
+/// Master secrets for signed and encrypted cookies.
+/// The first key is used for new cookies, the others are only accepted (rotation)
+#[derive(Clone)]
+pub struct CookieKeys {
+    keys: Vec<CookieKey>,
+}
+
+#[derive(Clone)]
+struct CookieKey {
+    signing: [u8; SHA256_LEN],
+    encryption: [u8; KEY_LEN],
+}
+
+pub struct SignedJar<'a> {
+    jar: &'a mut CookieJar,
+    keys: &'a CookieKeys,
+}
+
+pub struct PrivateJar<'a> {
+    jar: &'a mut CookieJar,
+    keys: &'a CookieKeys,
+}
+
+impl Cookie {
+    pub fn build(name: &str, value: &str) -> Cookie {
+        Cookie {
+            name: name.to_string(),
+            value: value.to_string(),
+            ..Cookie::default()
+        }
+    }
+    pub fn max_age(mut self, age: Duration) -> Cookie {
+        self.max_age = Some(age);
+        self
+    }
+    pub fn expires(mut self, at: HttpDate) -> Cookie {
+        self.expires = Some(at);
+        self
+    }
+    pub fn path(mut self, path: &str) -> Cookie {
+        self.path = Some(path.to_string());
+        self
+    }
+    pub fn domain(mut self, domain: &str) -> Cookie {
+        self.domain = Some(domain.to_string());
+        self
+    }
+    pub fn secure(mut self, secure: bool) -> Cookie {
+        self.secure = secure;
+        self
+    }
+    pub fn http_only(mut self, http_only: bool) -> Cookie {
+        self.http_only = http_only;
+        self
+    }
+    pub fn same_site(mut self, same_site: SameSite) -> Cookie {
+        self.same_site = Some(same_site);
+        self
+    }
+    /// A cookie which tells the client to delete `name`
+    pub fn removal(name: &str) -> Cookie {
+        Cookie::build(name, "").max_age(Duration::ZERO)
+    }
+    /// Value for a `Set-Cookie` header. Bytes the RFC 6265 grammar does not
+    /// allow in the name, value or path are percent-encoded, so `;`, CR and LF
+    /// can not add attributes or headers. An invalid domain is left out
+    pub fn to_header_value(&self) -> String {
+        let mut s = format!("{}={}", encode(&self.name, is_tchar), encode(&self.value, is_cookie_octet));
+        if let Some(age) = self.max_age {
+            s.push_str(&format!("; Max-Age={}", age.as_secs()));
+        }
+        if let Some(expires) = self.expires {
+            s.push_str(&format!("; Expires={}", expires));
+        }
+        if let Some(ref path) = self.path {
+            s.push_str(&format!("; Path={}", encode(path, |b| is_cookie_octet(b) || b == b' ' || b == b',')));
+        }
+        if let Some(ref domain) = self.domain {
+            if !domain.is_empty() && domain.bytes().all(|b| b.is_ascii_alphanumeric() || b == b'-' || b == b'.') {
+                s.push_str(&format!("; Domain={}", domain));
+            }
+        }
+        if self.secure {
+            s.push_str("; Secure");
+        }
+        if self.http_only {
+            s.push_str("; HttpOnly");
+        }
+        if let Some(same_site) = self.same_site {
+            s.push_str(&format!("; SameSite={}", same_site.as_str()));
+        }
+        s
+    }
+    fn expires_at(&self) -> u64 {
+        match (self.max_age, self.expires) {
+            (Some(age), _) => unix_now().saturating_add(age.as_secs()),
+            (None, Some(at)) => at.as_secs().max(1),
+            (None, None) => 0,
+        }
+    }
+}
+
+// RFC 9110 5.6.2, without `%` which starts an escape
+fn is_tchar(b: u8) -> bool {
+    b.is_ascii_alphanumeric() || b"!#$&'*+-.^_`|~".contains(&b)
+}
+
+// RFC 6265 4.1.1, without `%` which starts an escape
+fn is_cookie_octet(b: u8) -> bool {
+    matches!(b, 0x21 | 0x23..=0x24 | 0x26..=0x2B | 0x2D..=0x3A | 0x3C..=0x5B | 0x5D..=0x7E)
+}
+
+fn encode(src: &str, keep: impl Fn(u8) -> bool) -> String {
+    let mut out = String::with_capacity(src.len());
+    for b in src.bytes() {
+        if keep(b) {
+            out.push(b as char);
+        } else {
+            out.push_str(&format!("%{:02X}", b));
+        }
+    }
+    out
+}
+
+fn decode(src: &str) -> String {
+    if src.contains('%') {
+        String::from_utf8_lossy(&percent_decode(src.as_bytes(), false)).into_owned()
+    } else {
+        src.to_string()
+    }
+}
+
+impl Default for Cookie {
+    fn default() -> Self {
+        Self {
+            name: String::new(),
+            value: String::new(),
+            max_age: None,
+            expires: None,
+            path: Some("/".to_string()),
+            domain: None,
+            secure: false,
+            http_only: true,
+            same_site: Some(SameSite::Lax),
+        }
+    }
+}
+
+impl New for Cookie {}
+
+impl Display for Cookie {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.to_header_value())
+    }
+}
+
+impl SameSite {
+    pub fn as_str(&self) -> &str {
+        match self {
+            SameSite::Strict => "Strict",
+            SameSite::Lax => "Lax",
+            SameSite::None => "None",
+        }
+    }
+}
+
+impl CookieJar {
+    pub fn from_request(req: &HTTPRequest) -> CookieJar {
+        let mut jar = CookieJar::new();
+        for header in req.parts.headcont.iter() {
+            if header.key == AllowedExtentions::Cookie {
+                jar.original.extend(CookieJar::parse(&header.value));
+            }
+        }
+        jar
+    }
+    /// Names and values are percent-decoded, see `Cookie::to_header_value`
+    pub fn parse(src: &str) -> Vec<(String, String)> {
+        src.split(';')
+            .filter_map(|pair| {
+                let (key, value) = pair.split_once('=')?;
+                let value = value.trim();
+                let value = value
+                    .strip_prefix('"')
+                    .and_then(|v| v.strip_suffix('"'))
+                    .unwrap_or(value);
+                Some((decode(key.trim()), decode(value)))
+            })
+            .collect()
+    }
+    /// Returns the current value, including changes made on this jar
+    pub fn get(&self, name: &str) -> Option<&str> {
+        if let Some(cookie) = self.delta.iter().rev().find(|c| c.name == name) {
+            return match cookie.max_age {
+                Some(age) if age.is_zero() => None,
+                _ => Some(&cookie.value),
+            };
+        }
+        self.original
+            .iter()
+            .find(|(key, _)| key == name)
+            .map(|(_, value)| value.as_str())
+    }
+    pub fn add(&mut self, cookie: Cookie) {
+        self.delta.push(cookie);
+    }
+    pub fn remove(&mut self, name: &str) {
+        self.delta.push(Cookie::removal(name));
+    }
+    /// Every `Set-Cookie` value which has to be sent
+    pub fn delta(&self) -> impl Iterator<Item = &Cookie> {
+        self.delta.iter()
+    }
+    pub fn signed<'a>(&'a mut self, keys: &'a CookieKeys) -> SignedJar<'a> {
+        SignedJar { jar: self, keys }
+    }
+    pub fn private<'a>(&'a mut self, keys: &'a CookieKeys) -> PrivateJar<'a> {
+        PrivateJar { jar: self, keys }
+    }
+}
+
+impl Default for CookieJar {
+    fn default() -> Self {
+        Self {
+            original: Vec::new(),
+            delta: Vec::new(),
+        }
+    }
+}
+
+impl New for CookieJar {}
+
+impl CookieKeys {
+    /// Secrets shorter than 32 Bytes are refused
+    pub fn from_secrets(secrets: &[&[u8]]) -> Option<CookieKeys> {
+        if secrets.is_empty() || secrets.iter().any(|s| s.len() < 32) {
+            return None;
+        }
+        Some(CookieKeys {
+            keys: secrets
+                .iter()
+                .map(|s| CookieKey {
+                    signing: crypto::hmac_sha256(s, &[b"fiv-http cookie signing"]),
+                    encryption: crypto::hmac_sha256(s, &[b"fiv-http cookie encryption"]),
+                })
+                .collect(),
+        })
+    }
+    /// Adds a new current key, the old ones still verify
+    pub fn rotate(&mut self, secret: &[u8]) -> Option<()> {
+        let mut new = CookieKeys::from_secrets(&[secret])?;
+        new.keys.append(&mut self.keys);
+        self.keys = new.keys;
+        Some(())
+    }
+    /// Drops every key except the `n` newest
+    pub fn retire(&mut self, n: usize) {
+        self.keys.truncate(n.max(1));
+    }
+}
+
+// Both jars store `expiry (u64 BE, 0 = none) || value`, bound to the cookie name
+
+fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+fn pack(cookie: &Cookie) -> Vec<u8> {
+    let mut payload = cookie.expires_at().to_be_bytes().to_vec();
+    payload.extend_from_slice(cookie.value.as_bytes());
+    payload
+}
+
+fn unpack(payload: &[u8]) -> Option<String> {
+    if payload.len() < 8 {
+        return None;
+    }
+    let (exp, value) = payload.split_at(8);
+    let exp = u64::from_be_bytes(exp.try_into().unwrap());
+    if exp != 0 && exp <= unix_now() {
+        return None;
+    }
+    String::from_utf8(value.to_vec()).ok()
+}
+
+impl SignedJar<'_> {
+    /// `None` for missing, tampered or expired cookies
+    pub fn get(&self, name: &str) -> Option<String> {
+        let (payload, tag) = self.jar.get(name)?.split_once('.')?;
+        let payload = crypto::b64_decode(payload)?;
+        let tag = crypto::b64_decode(tag)?;
+        self.keys
+            .keys
+            .iter()
+            .find(|key| {
+                let mac = crypto::hmac_sha256(&key.signing, &[name.as_bytes(), &[0], &payload]);
+                crypto::ct_eq(&mac, &tag)
+            })
+            .and_then(|_| unpack(&payload))
+    }
+    pub fn add(&mut self, mut cookie: Cookie) {
+        let payload = pack(&cookie);
+        let key = &self.keys.keys[0];
+        let mac = crypto::hmac_sha256(&key.signing, &[cookie.name.as_bytes(), &[0], &payload]);
+        cookie.value = format!("{}.{}", crypto::b64_encode(&payload), crypto::b64_encode(&mac));
+        self.jar.add(cookie);
+    }
+    pub fn remove(&mut self, name: &str) {
+        self.jar.remove(name);
+    }
+}
+
+impl PrivateJar<'_> {
+    /// `None` for missing, tampered or expired cookies
+    pub fn get(&self, name: &str) -> Option<String> {
+        let sealed = crypto::b64_decode(self.jar.get(name)?)?;
+        if sealed.len() < NONCE_LEN {
+            return None;
+        }
+        let (nonce, sealed) = sealed.split_at(NONCE_LEN);
+        let nonce: [u8; NONCE_LEN] = nonce.try_into().unwrap();
+        self.keys
+            .keys
+            .iter()
+            .find_map(|key| crypto::open(&key.encryption, &nonce, name.as_bytes(), sealed))
+            .and_then(|payload| unpack(&payload))
+    }
+    pub fn add(&mut self, mut cookie: Cookie) {
+        let nonce = crypto::nonce();
+        let key = &self.keys.keys[0];
+        let mut value = nonce.to_vec();
+        value.extend(crypto::seal(&key.encryption, &nonce, cookie.name.as_bytes(), &pack(&cookie)));
+        cookie.value = crypto::b64_encode(&value);
+        self.jar.add(cookie);
+    }
+    pub fn remove(&mut self, name: &str) {
+        self.jar.remove(name);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn header_injection() {
+        let cookie = Cookie::build("id; Secure", "a;b\r\nSet-Cookie: evil=1")
+            .path("/x;Domain=evil")
+            .domain("evil.com; HttpOnly");
+        let header = cookie.to_header_value();
+        assert!(!header.contains(['\r', '\n']), "{header}");
+        assert_eq!(header.matches(';').count(), 3, "{header}");
+        assert!(header.starts_with("id%3B%20Secure=a%3Bb%0D%0ASet-Cookie:%20evil=1; Path=/x%3BDomain=evil;"), "{header}");
+        assert!(!header.contains("Domain=evil.com"), "{header}");
+        // Parsing undoes the encoding
+        let (name, value) = header.split(';').next().unwrap().split_once('=').unwrap();
+        assert_eq!(
+            CookieJar::parse(&format!("{}={}", name, value)),
+            vec![("id; Secure".to_string(), "a;b\r\nSet-Cookie: evil=1".to_string())]
+        );
+        assert_eq!(Cookie::build("a", "100%").to_header_value().split(';').next(), Some("a=100%25"));
+        assert!(Cookie::build("a", "b").domain("example.com").to_header_value().contains("; Domain=example.com"));
+    }
+}
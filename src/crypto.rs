@@ -0,0 +1,591 @@
+// Small self contained primitives for the cookie jars.
+// SHA-256 / HMAC-SHA256 (FIPS 180-4, RFC 2104) and ChaCha20-Poly1305 (RFC 8439).
+
+use std::{
+    collections::hash_map::RandomState,
+    hash::{BuildHasher, Hasher},
+    sync::atomic::{AtomicU64, Ordering},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+pub const SHA256_LEN: usize = 32;
+pub const KEY_LEN: usize = 32;
+pub const NONCE_LEN: usize = 12;
+pub const TAG_LEN: usize = 16;
+
+#[rustfmt::skip]
+const K: [u32; 64] = [
+    0x428a2f98, 0x71374491, 0xb5c0fbcf, 0xe9b5dba5, 0x3956c25b, 0x59f111f1, 0x923f82a4, 0xab1c5ed5,
+    0xd807aa98, 0x12835b01, 0x243185be, 0x550c7dc3, 0x72be5d74, 0x80deb1fe, 0x9bdc06a7, 0xc19bf174,
+    0xe49b69c1, 0xefbe4786, 0x0fc19dc6, 0x240ca1cc, 0x2de92c6f, 0x4a7484aa, 0x5cb0a9dc, 0x76f988da,
+    0x983e5152, 0xa831c66d, 0xb00327c8, 0xbf597fc7, 0xc6e00bf3, 0xd5a79147, 0x06ca6351, 0x14292967,
+    0x27b70a85, 0x2e1b2138, 0x4d2c6dfc, 0x53380d13, 0x650a7354, 0x766a0abb, 0x81c2c92e, 0x92722c85,
+    0xa2bfe8a1, 0xa81a664b, 0xc24b8b70, 0xc76c51a3, 0xd192e819, 0xd6990624, 0xf40e3585, 0x106aa070,
+    0x19a4c116, 0x1e376c08, 0x2748774c, 0x34b0bcb5, 0x391c0cb3, 0x4ed8aa4a, 0x5b9cca4f, 0x682e6ff3,
+    0x748f82ee, 0x78a5636f, 0x84c87814, 0x8cc70208, 0x90befffa, 0xa4506ceb, 0xbef9a3f7, 0xc67178f2,
+];
+
+#[derive(Clone)]
+pub struct Sha256 {
+    state: [u32; 8],
+    buf: [u8; 64],
+    buf_len: usize,
+    len: u64,
+}
+
+impl Sha256 {
+    pub fn update(&mut self, mut data: &[u8]) {
+        self.len += data.len() as u64;
+        if self.buf_len > 0 {
+            let take = (64 - self.buf_len).min(data.len());
+            self.buf[self.buf_len..self.buf_len + take].copy_from_slice(&data[..take]);
+            self.buf_len += take;
+            data = &data[take..];
+            if self.buf_len < 64 {
+                return;
+            }
+            let block = self.buf;
+            self.compress(&block);
+            self.buf_len = 0;
+        }
+        while data.len() >= 64 {
+            self.compress(data[..64].try_into().unwrap());
+            data = &data[64..];
+        }
+        self.buf[..data.len()].copy_from_slice(data);
+        self.buf_len = data.len();
+    }
+
+    pub fn finish(mut self) -> [u8; SHA256_LEN] {
+        let bits = self.len.wrapping_mul(8);
+        let mut pad = [0u8; 72];
+        pad[0] = 0x80;
+        let pad_len = if self.buf_len < 56 { 56 - self.buf_len } else { 120 - self.buf_len };
+        pad[pad_len..pad_len + 8].copy_from_slice(&bits.to_be_bytes());
+        let len = self.len;
+        self.update(&pad[..pad_len + 8]);
+        self.len = len;
+        let mut out = [0u8; SHA256_LEN];
+        for (chunk, word) in out.chunks_exact_mut(4).zip(self.state.iter()) {
+            chunk.copy_from_slice(&word.to_be_bytes());
+        }
+        out
+    }
+
+    pub fn digest(data: &[u8]) -> [u8; SHA256_LEN] {
+        let mut h = Sha256::default();
+        h.update(data);
+        h.finish()
+    }
+
+    fn compress(&mut self, block: &[u8; 64]) {
+        let mut w = [0u32; 64];
+        for i in 0..16 {
+            w[i] = u32::from_be_bytes(block[i * 4..i * 4 + 4].try_into().unwrap());
+        }
+        for i in 16..64 {
+            let s0 = w[i - 15].rotate_right(7) ^ w[i - 15].rotate_right(18) ^ (w[i - 15] >> 3);
+            let s1 = w[i - 2].rotate_right(17) ^ w[i - 2].rotate_right(19) ^ (w[i - 2] >> 10);
+            w[i] = w[i - 16]
+                .wrapping_add(s0)
+                .wrapping_add(w[i - 7])
+                .wrapping_add(s1);
+        }
+        let [mut a, mut b, mut c, mut d, mut e, mut f, mut g, mut h] = self.state;
+        for i in 0..64 {
+            let s1 = e.rotate_right(6) ^ e.rotate_right(11) ^ e.rotate_right(25);
+            let ch = (e & f) ^ (!e & g);
+            let t1 = h
+                .wrapping_add(s1)
+                .wrapping_add(ch)
+                .wrapping_add(K[i])
+                .wrapping_add(w[i]);
+            let s0 = a.rotate_right(2) ^ a.rotate_right(13) ^ a.rotate_right(22);
+            let maj = (a & b) ^ (a & c) ^ (b & c);
+            let t2 = s0.wrapping_add(maj);
+            h = g;
+            g = f;
+            f = e;
+            e = d.wrapping_add(t1);
+            d = c;
+            c = b;
+            b = a;
+            a = t1.wrapping_add(t2);
+        }
+        for (s, v) in self.state.iter_mut().zip([a, b, c, d, e, f, g, h]) {
+            *s = s.wrapping_add(v);
+        }
+    }
+}
+
+impl Default for Sha256 {
+    fn default() -> Self {
+        Sha256 {
+            state: [
+                0x6a09e667, 0xbb67ae85, 0x3c6ef372, 0xa54ff53a, 0x510e527f, 0x9b05688c,
+                0x1f83d9ab, 0x5be0cd19,
+            ],
+            buf: [0; 64],
+            buf_len: 0,
+            len: 0,
+        }
+    }
+}
+
+pub fn hmac_sha256(key: &[u8], parts: &[&[u8]]) -> [u8; SHA256_LEN] {
+    let mut block = [0u8; 64];
+    if key.len() > 64 {
+        block[..SHA256_LEN].copy_from_slice(&Sha256::digest(key));
+    } else {
+        block[..key.len()].copy_from_slice(key);
+    }
+    let mut inner = Sha256::default();
+    inner.update(&block.map(|b| b ^ 0x36));
+    for part in parts {
+        inner.update(part);
+    }
+    let mut outer = Sha256::default();
+    outer.update(&block.map(|b| b ^ 0x5c));
+    outer.update(&inner.finish());
+    outer.finish()
+}
+
+/// Compares in constant time (for equal lengths)
+pub fn ct_eq(a: &[u8], b: &[u8]) -> bool {
+    if a.len() != b.len() {
+        return false;
+    }
+    a.iter().zip(b).fold(0u8, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+fn quarter(s: &mut [u32; 16], a: usize, b: usize, c: usize, d: usize) {
+    s[a] = s[a].wrapping_add(s[b]);
+    s[d] = (s[d] ^ s[a]).rotate_left(16);
+    s[c] = s[c].wrapping_add(s[d]);
+    s[b] = (s[b] ^ s[c]).rotate_left(12);
+    s[a] = s[a].wrapping_add(s[b]);
+    s[d] = (s[d] ^ s[a]).rotate_left(8);
+    s[c] = s[c].wrapping_add(s[d]);
+    s[b] = (s[b] ^ s[c]).rotate_left(7);
+}
+
+fn chacha20_block(key: &[u8; KEY_LEN], counter: u32, nonce: &[u8; NONCE_LEN]) -> [u8; 64] {
+    let mut init = [0u32; 16];
+    init[..4].copy_from_slice(&[0x61707865, 0x3320646e, 0x79622d32, 0x6b206574]);
+    for i in 0..8 {
+        init[4 + i] = u32::from_le_bytes(key[i * 4..i * 4 + 4].try_into().unwrap());
+    }
+    init[12] = counter;
+    for i in 0..3 {
+        init[13 + i] = u32::from_le_bytes(nonce[i * 4..i * 4 + 4].try_into().unwrap());
+    }
+    let mut s = init;
+    for _ in 0..10 {
+        quarter(&mut s, 0, 4, 8, 12);
+        quarter(&mut s, 1, 5, 9, 13);
+        quarter(&mut s, 2, 6, 10, 14);
+        quarter(&mut s, 3, 7, 11, 15);
+        quarter(&mut s, 0, 5, 10, 15);
+        quarter(&mut s, 1, 6, 11, 12);
+        quarter(&mut s, 2, 7, 8, 13);
+        quarter(&mut s, 3, 4, 9, 14);
+    }
+    let mut out = [0u8; 64];
+    for i in 0..16 {
+        out[i * 4..i * 4 + 4].copy_from_slice(&s[i].wrapping_add(init[i]).to_le_bytes());
+    }
+    out
+}
+
+fn chacha20_xor(key: &[u8; KEY_LEN], counter: u32, nonce: &[u8; NONCE_LEN], data: &mut [u8]) {
+    for (i, chunk) in data.chunks_mut(64).enumerate() {
+        let stream = chacha20_block(key, counter.wrapping_add(i as u32), nonce);
+        for (b, k) in chunk.iter_mut().zip(stream.iter()) {
+            *b ^= k;
+        }
+    }
+}
+
+// poly1305-donna, 26 bit limbs
+struct Poly1305 {
+    r: [u32; 5],
+    h: [u32; 5],
+    pad: [u32; 4],
+}
+
+impl Poly1305 {
+    fn new(key: &[u8; 32]) -> Poly1305 {
+        let le = |i: usize| u32::from_le_bytes(key[i..i + 4].try_into().unwrap());
+        Poly1305 {
+            r: [
+                le(0) & 0x3ffffff,
+                (le(3) >> 2) & 0x3ffff03,
+                (le(6) >> 4) & 0x3ffc0ff,
+                (le(9) >> 6) & 0x3f03fff,
+                (le(12) >> 8) & 0x00fffff,
+            ],
+            h: [0; 5],
+            pad: [le(16), le(20), le(24), le(28)],
+        }
+    }
+
+    fn block(&mut self, m: &[u8; 16], hibit: u32) {
+        let le = |i: usize| u32::from_le_bytes(m[i..i + 4].try_into().unwrap());
+        let [r0, r1, r2, r3, r4] = self.r.map(|r| r as u64);
+        let (s1, s2, s3, s4) = (r1 * 5, r2 * 5, r3 * 5, r4 * 5);
+        let h = &mut self.h;
+        h[0] += le(0) & 0x3ffffff;
+        h[1] += (le(3) >> 2) & 0x3ffffff;
+        h[2] += (le(6) >> 4) & 0x3ffffff;
+        h[3] += (le(9) >> 6) & 0x3ffffff;
+        h[4] += (le(12) >> 8) | hibit;
+        let [h0, h1, h2, h3, h4] = h.map(|h| h as u64);
+        let d0 = h0 * r0 + h1 * s4 + h2 * s3 + h3 * s2 + h4 * s1;
+        let mut d1 = h0 * r1 + h1 * r0 + h2 * s4 + h3 * s3 + h4 * s2;
+        let mut d2 = h0 * r2 + h1 * r1 + h2 * r0 + h3 * s4 + h4 * s3;
+        let mut d3 = h0 * r3 + h1 * r2 + h2 * r1 + h3 * r0 + h4 * s4;
+        let mut d4 = h0 * r4 + h1 * r3 + h2 * r2 + h3 * r1 + h4 * r0;
+        let mut c = d0 >> 26;
+        h[0] = d0 as u32 & 0x3ffffff;
+        d1 += c;
+        c = d1 >> 26;
+        h[1] = d1 as u32 & 0x3ffffff;
+        d2 += c;
+        c = d2 >> 26;
+        h[2] = d2 as u32 & 0x3ffffff;
+        d3 += c;
+        c = d3 >> 26;
+        h[3] = d3 as u32 & 0x3ffffff;
+        d4 += c;
+        c = d4 >> 26;
+        h[4] = d4 as u32 & 0x3ffffff;
+        h[0] += c as u32 * 5;
+        let c = h[0] >> 26;
+        h[0] &= 0x3ffffff;
+        h[1] += c;
+    }
+
+    fn update_padded(&mut self, data: &[u8]) {
+        for chunk in data.chunks(16) {
+            let mut m = [0u8; 16];
+            m[..chunk.len()].copy_from_slice(chunk);
+            self.block(&m, 1 << 24);
+        }
+    }
+
+    fn finish(mut self) -> [u8; TAG_LEN] {
+        let h = &mut self.h;
+        let mut c = h[1] >> 26;
+        h[1] &= 0x3ffffff;
+        for i in 2..5 {
+            h[i] += c;
+            c = h[i] >> 26;
+            h[i] &= 0x3ffffff;
+        }
+        h[0] += c * 5;
+        c = h[0] >> 26;
+        h[0] &= 0x3ffffff;
+        h[1] += c;
+
+        let mut g = [0u32; 5];
+        g[0] = h[0].wrapping_add(5);
+        c = g[0] >> 26;
+        g[0] &= 0x3ffffff;
+        for i in 1..4 {
+            g[i] = h[i].wrapping_add(c);
+            c = g[i] >> 26;
+            g[i] &= 0x3ffffff;
+        }
+        // Unmasked, the carry decides whether h >= 2^130 - 5
+        g[4] = h[4].wrapping_add(c).wrapping_sub(1 << 26);
+        let mask = (g[4] >> 31).wrapping_sub(1);
+        for i in 0..5 {
+            h[i] = (h[i] & !mask) | (g[i] & mask);
+        }
+
+        let h0 = h[0] | (h[1] << 26);
+        let h1 = (h[1] >> 6) | (h[2] << 20);
+        let h2 = (h[2] >> 12) | (h[3] << 14);
+        let h3 = (h[3] >> 18) | (h[4] << 8);
+        let mut out = [0u8; TAG_LEN];
+        let mut f: u64 = h0 as u64 + self.pad[0] as u64;
+        out[0..4].copy_from_slice(&(f as u32).to_le_bytes());
+        f = h1 as u64 + self.pad[1] as u64 + (f >> 32);
+        out[4..8].copy_from_slice(&(f as u32).to_le_bytes());
+        f = h2 as u64 + self.pad[2] as u64 + (f >> 32);
+        out[8..12].copy_from_slice(&(f as u32).to_le_bytes());
+        f = h3 as u64 + self.pad[3] as u64 + (f >> 32);
+        out[12..16].copy_from_slice(&(f as u32).to_le_bytes());
+        out
+    }
+}
+
+fn aead_tag(key: &[u8; KEY_LEN], nonce: &[u8; NONCE_LEN], aad: &[u8], ct: &[u8]) -> [u8; TAG_LEN] {
+    let otk: [u8; 32] = chacha20_block(key, 0, nonce)[..32].try_into().unwrap();
+    let mut poly = Poly1305::new(&otk);
+    poly.update_padded(aad);
+    poly.update_padded(ct);
+    let mut lens = [0u8; 16];
+    lens[..8].copy_from_slice(&(aad.len() as u64).to_le_bytes());
+    lens[8..].copy_from_slice(&(ct.len() as u64).to_le_bytes());
+    poly.block(&lens, 1 << 24);
+    poly.finish()
+}
+
+/// ChaCha20-Poly1305 seal. Returns ciphertext || tag
+pub fn seal(key: &[u8; KEY_LEN], nonce: &[u8; NONCE_LEN], aad: &[u8], plain: &[u8]) -> Vec<u8> {
+    let mut out = plain.to_vec();
+    chacha20_xor(key, 1, nonce, &mut out);
+    let tag = aead_tag(key, nonce, aad, &out);
+    out.extend_from_slice(&tag);
+    out
+}
+
+/// ChaCha20-Poly1305 open. Expects ciphertext || tag
+pub fn open(key: &[u8; KEY_LEN], nonce: &[u8; NONCE_LEN], aad: &[u8], sealed: &[u8]) -> Option<Vec<u8>> {
+    if sealed.len() < TAG_LEN {
+        return None;
+    }
+    let (ct, tag) = sealed.split_at(sealed.len() - TAG_LEN);
+    if !ct_eq(&aead_tag(key, nonce, aad, ct), tag) {
+        return None;
+    }
+    let mut out = ct.to_vec();
+    chacha20_xor(key, 1, nonce, &mut out);
+    Some(out)
+}
+
+/// Unique per process and key: a random prefix plus a counter seeded from the clock
+pub fn nonce() -> [u8; NONCE_LEN] {
+    static COUNTER: AtomicU64 = AtomicU64::new(0);
+    static PREFIX: std::sync::OnceLock<u32> = std::sync::OnceLock::new();
+    let prefix = *PREFIX.get_or_init(|| {
+        let mut h = RandomState::new().build_hasher();
+        h.write_u128(
+            SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|d| d.as_nanos())
+                .unwrap_or(0),
+        );
+        h.finish() as u32
+    });
+    let _ = COUNTER.compare_exchange(
+        0,
+        SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_nanos() as u64)
+            .unwrap_or(1),
+        Ordering::Relaxed,
+        Ordering::Relaxed,
+    );
+    let count = COUNTER.fetch_add(1, Ordering::Relaxed);
+    let mut out = [0u8; NONCE_LEN];
+    out[..4].copy_from_slice(&prefix.to_be_bytes());
+    out[4..].copy_from_slice(&count.to_be_bytes());
+    out
+}
+
+const B64: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789-_";
+
+/// base64url without padding, safe in cookie values
+pub fn b64_encode(src: &[u8]) -> String {
+    let mut out = String::with_capacity(src.len().div_ceil(3) * 4);
+    for chunk in src.chunks(3) {
+        let n = chunk
+            .iter()
+            .enumerate()
+            .fold(0u32, |acc, (i, b)| acc | (*b as u32) << (16 - i * 8));
+        for i in 0..chunk.len() + 1 {
+            out.push(B64[(n >> (18 - i * 6)) as usize & 63] as char);
+        }
+    }
+    out
+}
+
+pub fn b64_decode(src: &str) -> Option<Vec<u8>> {
+    let mut out = Vec::with_capacity(src.len() * 3 / 4);
+    for chunk in src.as_bytes().chunks(4) {
+        if chunk.len() == 1 {
+            return None;
+        }
+        let mut n = 0u32;
+        for (i, c) in chunk.iter().enumerate() {
+            let v = B64.iter().position(|b| b == c)? as u32;
+            n |= v << (18 - i * 6);
+        }
+        for i in 0..chunk.len() - 1 {
+            out.push((n >> (16 - i * 8)) as u8);
+        }
+    }
+    Some(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn hex(src: &str) -> Vec<u8> {
+        let src: String = src.split_whitespace().collect();
+        (0..src.len())
+            .step_by(2)
+            .map(|i| u8::from_str_radix(&src[i..i + 2], 16).unwrap())
+            .collect()
+    }
+
+    // Standalone Poly1305, the last partial block is padded with a single 1 bit
+    fn poly1305(key: &[u8], msg: &[u8]) -> [u8; TAG_LEN] {
+        let mut poly = Poly1305::new(key.try_into().unwrap());
+        for chunk in msg.chunks(16) {
+            let mut m = [0u8; 16];
+            m[..chunk.len()].copy_from_slice(chunk);
+            if chunk.len() == 16 {
+                poly.block(&m, 1 << 24);
+            } else {
+                m[chunk.len()] = 1;
+                poly.block(&m, 0);
+            }
+        }
+        poly.finish()
+    }
+
+    const IETF: &[u8] = b"Any submission to the IETF intended by the Contributor for publication as all or part of an IETF Internet-Draft or RFC and any statement made within the context of an IETF activity is considered an \"IETF Contribution\". Such statements include oral statements in IETF sessions, as well as written and electronic communications made at any time or place, which are addressed to";
+
+    #[test]
+    fn sha256() {
+        let cases: [(&[u8], &str); 3] = [
+            (b"", "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855"),
+            (b"abc", "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"),
+            (
+                b"abcdbcdecdefdefgefghfghighijhijkijkljklmklmnlmnomnopnopq",
+                "248d6a61d20638b8e5c026930c3e6039a33ce45964ff2167f6ecedd419db06c1",
+            ),
+        ];
+        for (msg, digest) in cases {
+            assert_eq!(Sha256::digest(msg).to_vec(), hex(digest));
+        }
+        // Uneven updates cross block boundaries
+        let mut sha = Sha256::default();
+        for _ in 0..1000 {
+            sha.update(&[b'a'; 1000]);
+        }
+        assert_eq!(
+            sha.finish().to_vec(),
+            hex("cdc76e5c9914fb9281a1c7e284d73e67f1809a48a497200e046d39ccc7112cd0")
+        );
+    }
+
+    // RFC 4231 4.2 - 4.8
+    #[test]
+    fn hmac() {
+        let long_key = [0xaa; 131];
+        let cases: [(&[u8], &[u8], &str); 7] = [
+            (&[0x0b; 20], b"Hi There", "b0344c61d8db38535ca8afceaf0bf12b881dc200c9833da726e9376c2e32cff7"),
+            (
+                b"Jefe",
+                b"what do ya want for nothing?",
+                "5bdcc146bf60754e6a042426089575c75a003f089d2739839dec58b964ec3843",
+            ),
+            (&[0xaa; 20], &[0xdd; 50], "773ea91e36800e46854db8ebd09181a72959098b3ef8c122d9635514ced565fe"),
+            (
+                &hex("0102030405060708090a0b0c0d0e0f10111213141516171819"),
+                &[0xcd; 50],
+                "82558a389a443c0ea4cc819899f2083a85f0faa3e578f8077a2e3ff46729665b",
+            ),
+            (&[0x0c; 20], b"Test With Truncation", "a3b6167473100ee06e0c796c2955552b"),
+            (
+                &long_key,
+                b"Test Using Larger Than Block-Size Key - Hash Key First",
+                "60e431591ee0b67f0d8a26aacbf5b77f8e0bc6213728c5140546040f0ee37f54",
+            ),
+            (
+                &long_key,
+                b"This is a test using a larger than block-size key and a larger than block-size data. The key needs to be hashed before being used by the HMAC algorithm.",
+                "9b09ffa71b942fcb27635fbcd5b0e944bfdc63644f0713938a7f51535c3a35e2",
+            ),
+        ];
+        for (key, data, mac) in cases {
+            let mac = hex(mac);
+            assert_eq!(hmac_sha256(key, &[data])[..mac.len()], mac[..]);
+        }
+        // Split input gives the same mac
+        assert_eq!(hmac_sha256(b"Jefe", &[b"what do ya ", b"want for nothing?"]), hmac_sha256(b"Jefe", &[b"what do ya want for nothing?"]));
+    }
+
+    // RFC 8439 A.3
+    #[test]
+    fn poly1305_vectors() {
+        let r1 = "01000000000000000000000000000000";
+        let r2 = "02000000000000000000000000000000";
+        let r10 = "01000000000000000400000000000000";
+        let zero = "00000000000000000000000000000000";
+        let ff = "ffffffffffffffffffffffffffffffff";
+        let cases: Vec<(String, Vec<u8>, &str)> = vec![
+            (format!("{zero}{zero}"), vec![0; 64], zero),
+            (
+                format!("{zero}36e5f6b5c5e06070f0efca96227a863e"),
+                IETF.to_vec(),
+                "36e5f6b5c5e06070f0efca96227a863e",
+            ),
+            (
+                format!("36e5f6b5c5e06070f0efca96227a863e{zero}"),
+                IETF.to_vec(),
+                "f3477e7cd95417af89a6b8794c310cf0",
+            ),
+            (
+                "1c9240a5eb55d38af333888604f6b5f0473917c1402b80099dca5cbc207075c0".to_string(),
+                b"'Twas brillig, and the slithy toves\nDid gyre and gimble in the wabe:\nAll mimsy were the borogoves,\nAnd the mome raths outgrabe.".to_vec(),
+                "4541669a7eaaee61e708dc7cbcc5eb62",
+            ),
+            (format!("{r2}{zero}"), hex(ff), "03000000000000000000000000000000"),
+            (format!("{r2}{ff}"), hex(r2), "03000000000000000000000000000000"),
+            (
+                format!("{r1}{zero}"),
+                hex(&format!("{ff} f0ffffffffffffffffffffffffffffff 11000000000000000000000000000000")),
+                "05000000000000000000000000000000",
+            ),
+            (
+                format!("{r1}{zero}"),
+                hex(&format!("{ff} fbfefefefefefefefefefefefefefefe 01010101010101010101010101010101")),
+                zero,
+            ),
+            (format!("{r2}{zero}"), hex("fdffffffffffffffffffffffffffffff"), "faffffffffffffffffffffffffffffff"),
+            (
+                format!("{r10}{zero}"),
+                hex(&format!(
+                    "e33594d7505e43b90000000000000000 3394d7505e4379cd0100000000000000 {zero} 01000000000000000000000000000000"
+                )),
+                "14000000000000005500000000000000",
+            ),
+            (
+                format!("{r10}{zero}"),
+                hex(&format!("e33594d7505e43b90000000000000000 3394d7505e4379cd0100000000000000 {zero}")),
+                "13000000000000000000000000000000",
+            ),
+        ];
+        for (i, (key, msg, tag)) in cases.iter().enumerate() {
+            assert_eq!(poly1305(&hex(key), msg).to_vec(), hex(tag), "vector #{}", i + 1);
+        }
+    }
+
+    // RFC 8439 2.8.2
+    #[test]
+    fn aead() {
+        let key: [u8; KEY_LEN] = hex("808182838485868788898a8b8c8d8e8f909192939495969798999a9b9c9d9e9f")
+            .try_into()
+            .unwrap();
+        let nonce: [u8; NONCE_LEN] = hex("070000004041424344454647").try_into().unwrap();
+        let aad = hex("50515253c0c1c2c3c4c5c6c7");
+        let plain = b"Ladies and Gentlemen of the class of '99: If I could offer you only one tip for the future, sunscreen would be it.";
+        let sealed = seal(&key, &nonce, &aad, plain);
+        assert_eq!(
+            sealed,
+            hex("d31a8d34648e60db7b86afbc53ef7ec2a4aded51296e08fea9e2b5a736ee62d63dbea45e8ca9671282fafb69da92728b1a71de0a9e060b2905d6a5b67ecd3b3692ddbd7f2d778b8c9803aee328091b58fab324e4fad675945585808b4831d7bc3ff4def08e4b7a9de576d26586cec64b6116
+                 1ae10b594f09e26a7e902ecbd0600691")
+        );
+        assert_eq!(open(&key, &nonce, &aad, &sealed).as_deref(), Some(&plain[..]));
+        let mut forged = sealed.clone();
+        forged[0] ^= 1;
+        assert_eq!(open(&key, &nonce, &aad, &forged), None);
+        assert_eq!(open(&key, &nonce, b"", &sealed), None);
+    }
+}
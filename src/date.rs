@@ -0,0 +1,365 @@
+use std::{
+    cell::RefCell,
+    error::Error,
+    fmt::Display,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use crate::traits::New;
+
+/// A point in time with second precision as used by HTTP (RFC 9110 5.6.7)
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Debug)]
+pub struct HttpDate {
+    secs: u64,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub struct DateError(u8);
+
+const DAYS: [&str; 7] = ["Mon", "Tue", "Wed", "Thu", "Fri", "Sat", "Sun"];
+const LONG_DAYS: [&str; 7] = [
+    "Monday",
+    "Tuesday",
+    "Wednesday",
+    "Thursday",
+    "Friday",
+    "Saturday",
+    "Sunday",
+];
+const MONTHS: [&str; 12] = [
+    "Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct", "Nov", "Dec",
+];
+
+impl DateError {
+    pub const INVFORMAT: DateError = DateError(1);
+    pub const INVVALUE: DateError = DateError(2);
+    pub const INVWEEKDAY: DateError = DateError(3);
+    pub fn reason(&self) -> &str {
+        match self.0 {
+            1 => "Unknown Date Format",
+            2 => "Date Value out of Range",
+            3 => "Weekday does not match Date",
+            _ => "Error not defined",
+        }
+    }
+}
+
+impl Display for DateError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.reason())
+    }
+}
+
+impl Error for DateError {}
+
+// Howard Hinnant's days_from_civil / civil_from_days
+fn days_from_civil(y: i64, m: u32, d: u32) -> i64 {
+    let y = if m <= 2 { y - 1 } else { y };
+    let era = y.div_euclid(400);
+    let yoe = y - era * 400;
+    let mp = (m as i64 + 9) % 12;
+    let doy = (153 * mp + 2) / 5 + d as i64 - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    era * 146097 + doe - 719468
+}
+
+fn civil_from_days(z: i64) -> (i64, u32, u32) {
+    let z = z + 719468;
+    let era = z.div_euclid(146097);
+    let doe = z - era * 146097;
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let d = (doy - (153 * mp + 2) / 5 + 1) as u32;
+    let m = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+    (if m <= 2 { yoe + era * 400 + 1 } else { yoe + era * 400 }, m, d)
+}
+
+fn days_in_month(y: i64, m: u32) -> u32 {
+    match m {
+        2 if (y % 4 == 0 && y % 100 != 0) || y % 400 == 0 => 29,
+        2 => 28,
+        4 | 6 | 9 | 11 => 30,
+        _ => 31,
+    }
+}
+
+fn month(src: &str) -> Result<u32, DateError> {
+    match MONTHS.iter().position(|m| *m == src) {
+        Some(i) => Ok(i as u32 + 1),
+        None => Err(DateError::INVFORMAT),
+    }
+}
+
+fn number(src: &str, len: usize) -> Result<u32, DateError> {
+    if src.len() != len || !src.bytes().all(|b| b.is_ascii_digit()) {
+        return Err(DateError::INVFORMAT);
+    }
+    Ok(src.parse().unwrap())
+}
+
+fn time_of_day(src: &str) -> Result<(u32, u32, u32), DateError> {
+    let mut parts = src.split(':');
+    let (h, m, s) = match (parts.next(), parts.next(), parts.next(), parts.next()) {
+        (Some(h), Some(m), Some(s), None) => (number(h, 2)?, number(m, 2)?, number(s, 2)?),
+        _ => return Err(DateError::INVFORMAT),
+    };
+    // 60 is allowed for leap seconds and folded into the next minute
+    if h > 23 || m > 59 || s > 60 {
+        return Err(DateError::INVVALUE);
+    }
+    Ok((h, m, s))
+}
+
+impl HttpDate {
+    pub fn now() -> HttpDate {
+        HttpDate::from(SystemTime::now())
+    }
+
+    pub fn from_secs(secs: u64) -> HttpDate {
+        HttpDate { secs }
+    }
+
+    pub fn as_secs(&self) -> u64 {
+        self.secs
+    }
+
+    fn from_parts(
+        y: i64,
+        mo: u32,
+        d: u32,
+        (h, mi, s): (u32, u32, u32),
+        weekday: usize,
+    ) -> Result<HttpDate, DateError> {
+        if y < 1970 || d == 0 || d > days_in_month(y, mo) {
+            return Err(DateError::INVVALUE);
+        }
+        let days = days_from_civil(y, mo, d);
+        if (days + 3).rem_euclid(7) as usize != weekday {
+            return Err(DateError::INVWEEKDAY);
+        }
+        Ok(HttpDate {
+            secs: days as u64 * 86400 + h as u64 * 3600 + mi as u64 * 60 + s as u64,
+        })
+    }
+
+    /// Accepts IMF-fixdate, RFC 850 and asctime
+    pub fn parse(src: &str) -> Result<HttpDate, DateError> {
+        let src = src.trim();
+        let (weekday, rest) = src.split_once([',', ' ']).ok_or(DateError::INVFORMAT)?;
+        if src.as_bytes()[weekday.len()] == b',' {
+            let rest = rest.strip_prefix(' ').ok_or(DateError::INVFORMAT)?;
+            if let Some(weekday) = DAYS.iter().position(|d| *d == weekday) {
+                // Sun, 06 Nov 1994 08:49:37 GMT
+                let p: Vec<&str> = rest.split(' ').collect();
+                if p.len() != 5 || p[4] != "GMT" {
+                    return Err(DateError::INVFORMAT);
+                }
+                let y = number(p[2], 4)? as i64;
+                HttpDate::from_parts(y, month(p[1])?, number(p[0], 2)?, time_of_day(p[3])?, weekday)
+            } else if let Some(weekday) = LONG_DAYS.iter().position(|d| *d == weekday) {
+                // Sunday, 06-Nov-94 08:49:37 GMT
+                let p: Vec<&str> = rest.split(' ').collect();
+                if p.len() != 3 || p[2] != "GMT" {
+                    return Err(DateError::INVFORMAT);
+                }
+                let d: Vec<&str> = p[0].split('-').collect();
+                if d.len() != 3 {
+                    return Err(DateError::INVFORMAT);
+                }
+                let yy = number(d[2], 2)? as i64;
+                // RFC 9110: a year more than 50 years in the future is in the past century
+                let now = civil_from_days((HttpDate::now().secs / 86400) as i64).0;
+                let mut y = now - now % 100 + yy;
+                if y > now + 50 {
+                    y -= 100;
+                }
+                HttpDate::from_parts(y, month(d[1])?, number(d[0], 2)?, time_of_day(p[1])?, weekday)
+            } else {
+                Err(DateError::INVFORMAT)
+            }
+        } else {
+            // Sun Nov  6 08:49:37 1994
+            let weekday = DAYS
+                .iter()
+                .position(|d| *d == weekday)
+                .ok_or(DateError::INVFORMAT)?;
+            let b = rest.as_bytes();
+            if b.len() != 20 || !rest.is_ascii() || b[3] != b' ' || b[6] != b' ' || b[15] != b' ' {
+                return Err(DateError::INVFORMAT);
+            }
+            let d = match &rest[4..6] {
+                d if d.starts_with(' ') => number(&d[1..], 1)?,
+                d => number(d, 2)?,
+            };
+            let y = number(&rest[16..20], 4)? as i64;
+            HttpDate::from_parts(y, month(&rest[..3])?, d, time_of_day(&rest[7..15])?, weekday)
+        }
+    }
+
+    /// IMF-fixdate, the only format which may be generated
+    pub fn to_http_string(&self) -> String {
+        format!("{}", self)
+    }
+}
+
+impl Display for HttpDate {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let days = (self.secs / 86400) as i64;
+        let secs = self.secs % 86400;
+        let (y, m, d) = civil_from_days(days);
+        write!(
+            f,
+            "{}, {:02} {} {:04} {:02}:{:02}:{:02} GMT",
+            DAYS[(days + 3).rem_euclid(7) as usize],
+            d,
+            MONTHS[m as usize - 1],
+            y,
+            secs / 3600,
+            secs / 60 % 60,
+            secs % 60
+        )
+    }
+}
+
+impl From<SystemTime> for HttpDate {
+    fn from(value: SystemTime) -> Self {
+        HttpDate {
+            secs: value
+                .duration_since(UNIX_EPOCH)
+                .map(|d| d.as_secs())
+                .unwrap_or(0),
+        }
+    }
+}
+
+impl From<HttpDate> for SystemTime {
+    fn from(value: HttpDate) -> Self {
+        UNIX_EPOCH + Duration::from_secs(value.secs)
+    }
+}
+
+impl Default for HttpDate {
+    fn default() -> Self {
+        HttpDate::now()
+    }
+}
+
+impl New for HttpDate {}
+
+/// Value for the `Date` header, only reformatted once per second
+pub fn date_header() -> String {
+    cached(HttpDate::now())
+}
+
+fn cached(now: HttpDate) -> String {
+    thread_local! {
+        static CACHE: RefCell<(u64, String)> = const { RefCell::new((u64::MAX, String::new())) };
+    }
+    CACHE.with_borrow_mut(|(secs, cached)| {
+        if *secs != now.secs {
+            *secs = now.secs;
+            *cached = now.to_http_string();
+        }
+        cached.clone()
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // RFC 9110 5.6.7
+    const SECS: u64 = 784111777;
+
+    #[test]
+    fn formats() {
+        for src in [
+            "Sun, 06 Nov 1994 08:49:37 GMT",
+            "Sunday, 06-Nov-94 08:49:37 GMT",
+            "Sun Nov  6 08:49:37 1994",
+            "  Sun, 06 Nov 1994 08:49:37 GMT ",
+        ] {
+            assert_eq!(HttpDate::parse(src).unwrap().as_secs(), SECS, "{}", src);
+        }
+        assert_eq!(
+            HttpDate::parse("Thu Feb 29 12:00:00 2024")
+                .unwrap()
+                .to_string(),
+            "Thu, 29 Feb 2024 12:00:00 GMT"
+        );
+        assert_eq!(
+            HttpDate::from_secs(SECS).to_string(),
+            "Sun, 06 Nov 1994 08:49:37 GMT"
+        );
+        assert_eq!(
+            HttpDate::from_secs(0).to_string(),
+            "Thu, 01 Jan 1970 00:00:00 GMT"
+        );
+    }
+
+    #[test]
+    fn invalid() {
+        for (src, e) in [
+            ("Sun, 06 Nov 1994 08:49:37 UTC", DateError::INVFORMAT),
+            ("Sun, 6 Nov 1994 08:49:37 GMT", DateError::INVFORMAT),
+            ("Sun, 06 Foo 1994 08:49:37 GMT", DateError::INVFORMAT),
+            ("Sun,06 Nov 1994 08:49:37 GMT", DateError::INVFORMAT),
+            ("Sun Nov 6 08:49:37 1994", DateError::INVFORMAT),
+            ("Sun, 06 Nov 1994 24:00:00 GMT", DateError::INVVALUE),
+            ("Sun, 00 Nov 1994 08:49:37 GMT", DateError::INVVALUE),
+            ("Thu, 31 Apr 2024 08:49:37 GMT", DateError::INVVALUE),
+            ("Wed, 31 Dec 1969 23:59:59 GMT", DateError::INVVALUE),
+            ("Mon, 06 Nov 1994 08:49:37 GMT", DateError::INVWEEKDAY),
+        ] {
+            assert_eq!(HttpDate::parse(src).unwrap_err(), e, "{}", src);
+        }
+    }
+
+    #[test]
+    fn leap_days() {
+        assert!(HttpDate::parse("Thu, 29 Feb 2024 00:00:00 GMT").is_ok());
+        assert!(HttpDate::parse("Tue, 29 Feb 2000 00:00:00 GMT").is_ok());
+        for src in [
+            "Sat, 29 Feb 2025 00:00:00 GMT",
+            "Mon, 29 Feb 2100 00:00:00 GMT",
+        ] {
+            assert_eq!(HttpDate::parse(src).unwrap_err(), DateError::INVVALUE);
+        }
+        // A leap second is the first second of the next day
+        assert_eq!(
+            HttpDate::parse("Sat, 31 Dec 2016 23:59:60 GMT").unwrap(),
+            HttpDate::parse("Sun, 01 Jan 2017 00:00:00 GMT").unwrap()
+        );
+    }
+
+    #[test]
+    fn round_trip() {
+        for secs in [0, SECS, 951782400, 4107542399, HttpDate::now().as_secs()] {
+            let date = HttpDate::from_secs(secs);
+            assert_eq!(HttpDate::parse(&date.to_http_string()), Ok(date));
+        }
+        let now = SystemTime::UNIX_EPOCH + Duration::from_secs(SECS);
+        assert_eq!(SystemTime::from(HttpDate::from(now)), now);
+    }
+
+    #[test]
+    fn header_cache() {
+        assert_eq!(
+            cached(HttpDate::from_secs(SECS)),
+            "Sun, 06 Nov 1994 08:49:37 GMT"
+        );
+        assert_eq!(
+            cached(HttpDate::from_secs(SECS)),
+            "Sun, 06 Nov 1994 08:49:37 GMT"
+        );
+        assert_eq!(
+            cached(HttpDate::from_secs(SECS + 1)),
+            "Sun, 06 Nov 1994 08:49:38 GMT"
+        );
+
+        let before = HttpDate::now();
+        let date = HttpDate::parse(&date_header()).unwrap();
+        assert!(before <= date && date <= HttpDate::now());
+    }
+}
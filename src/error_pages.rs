@@ -0,0 +1,340 @@
+use std::{
+    cell::Cell,
+    collections::HashMap,
+    panic::{self, AssertUnwindSafe},
+    path::{Path, PathBuf},
+};
+
+use crate::{
+    http::{
+        request::HTTPRequest,
+        response::{HTTPResponse, StatusCode},
+        server::Client,
+        uri::{Host, UriError},
+        utils::{AllowedExtentions, ContType, HTML, JSON, PLAIN},
+    },
+    traits::New,
+};
+
+/// What an error is sent as, picked from the `Accept` header of the request
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum ErrorFormat {
+    /// Without `Accept` or when it prefers neither HTML nor JSON
+    Plain,
+    Html,
+    Json,
+}
+
+/// Builds an error page, the status is set on the result afterwards
+pub type ErrorFn = Box<dyn Fn(&HTTPRequest, &Client, StatusCode, ErrorFormat) -> HTTPResponse + Send + Sync>;
+
+pub enum ErrorPage {
+    /// Sent to clients accepting its type, `.json` for JSON and `.html` for HTML
+    File(PathBuf),
+    /// Used for every format
+    Handler(ErrorFn),
+}
+
+/// Error pages by status code, optionally per virtual host. Pages of the host
+/// come before global ones, the first page matching the format is sent.
+/// Without one, or when a page fails, the built-in page is sent
+pub struct ErrorPages {
+    pages: HashMap<PageKey, Vec<ErrorPage>>,
+}
+
+// `None` for pages of every host
+type PageKey = (Option<Host>, StatusCode);
+
+thread_local! {
+    // Set while a page is generated, errors inside a page never reach another one
+    static GENERATING: Cell<bool> = const { Cell::new(false) };
+}
+
+impl ErrorFormat {
+    /// The highest `q` of `text/html`, `application/json` and `text/plain`
+    /// wins, ties in that order. `q=0` excludes a type, `*/*` alone prefers nothing
+    pub fn negotiate(req: &HTTPRequest) -> ErrorFormat {
+        let mut best: Option<(ErrorFormat, f32)> = None;
+        let accept = req.parts.headcont.iter().filter(|header| header.key == AllowedExtentions::Accept);
+        for range in accept.flat_map(|header| header.value.split(',')) {
+            let mut params = range.split(';');
+            let media = params.next().unwrap_or("").trim().to_ascii_lowercase();
+            let q = params
+                .filter_map(|param| param.trim().strip_prefix("q="))
+                .find_map(|q| q.trim().parse::<f32>().ok())
+                .unwrap_or(1.0);
+            let format = match media.as_str() {
+                "text/html" | "application/xhtml+xml" => ErrorFormat::Html,
+                "application/json" => ErrorFormat::Json,
+                media if media.starts_with("application/") && media.ends_with("+json") => ErrorFormat::Json,
+                "text/plain" => ErrorFormat::Plain,
+                _ => continue,
+            };
+            if q <= 0.0 {
+                continue;
+            }
+            if best.is_none_or(|(other, best_q)| q > best_q || (q == best_q && format.rank() < other.rank())) {
+                best = Some((format, q));
+            }
+        }
+        best.map_or(ErrorFormat::Plain, |(format, _)| format)
+    }
+    fn rank(&self) -> u8 {
+        match self {
+            ErrorFormat::Html => 0,
+            ErrorFormat::Json => 1,
+            ErrorFormat::Plain => 2,
+        }
+    }
+    fn of_file(path: &Path) -> ErrorFormat {
+        match path.extension().and_then(|ext| ext.to_str()) {
+            Some("json") => ErrorFormat::Json,
+            Some("html" | "htm") => ErrorFormat::Html,
+            _ => ErrorFormat::Plain,
+        }
+    }
+}
+
+impl ErrorPage {
+    pub fn file(path: impl Into<PathBuf>) -> ErrorPage {
+        ErrorPage::File(path.into())
+    }
+    pub fn handler(
+        handler: impl Fn(&HTTPRequest, &Client, StatusCode, ErrorFormat) -> HTTPResponse + Send + Sync + 'static,
+    ) -> ErrorPage {
+        ErrorPage::Handler(Box::new(handler))
+    }
+
+    // `None` if the page does not fit `format` or failed
+    fn get_resp(&self, req: &HTTPRequest, res: &Client, status: StatusCode, format: ErrorFormat) -> Option<HTTPResponse> {
+        match self {
+            ErrorPage::File(path) => {
+                if format != ErrorFormat::Plain && ErrorFormat::of_file(path) != format {
+                    return None;
+                }
+                let body = res.files.get(path).ok()?;
+                let ext = path.extension().and_then(|ext| ext.to_str()).unwrap_or("");
+                Some(HTTPResponse::new().ct(ContType::from_extension(ext)).body(body))
+            }
+            // A panicking page is treated like a missing one
+            ErrorPage::Handler(handler) => {
+                panic::catch_unwind(AssertUnwindSafe(|| handler(req, res, status, format))).ok()
+            }
+        }
+    }
+}
+
+impl ErrorPages {
+    /// For every host, a `404` page with `ErrorPage::file("www/404.html")`
+    pub fn page(self, status: StatusCode, page: ErrorPage) -> ErrorPages {
+        self.push(None, status, page)
+    }
+    /// Only for requests to `host`, fails if `host` is not a valid host
+    pub fn host(self, host: &str, status: StatusCode, page: ErrorPage) -> Result<ErrorPages, UriError> {
+        let host = Host::parse(host)?;
+        Ok(self.push(Some(host), status, page))
+    }
+    fn push(mut self, host: Option<Host>, status: StatusCode, page: ErrorPage) -> ErrorPages {
+        self.pages
+            .entry((host, status))
+            .or_default()
+            .push(page);
+        self
+    }
+    pub fn is_empty(&self) -> bool {
+        self.pages.is_empty()
+    }
+
+    /// The error page for `status`, the version is set by the caller. Usually
+    /// called through `Client::error` which picks the pages of the request
+    pub fn respond(&self, req: &HTTPRequest, res: &Client, status: StatusCode) -> HTTPResponse {
+        let format = ErrorFormat::negotiate(req);
+        if GENERATING.get() {
+            return ErrorPages::builtin(status, format);
+        }
+        let mut keys = vec![(None, status)];
+        if let Some(host) = &req.parts.host {
+            keys.insert(0, (Some(host.clone()), status));
+        }
+        GENERATING.set(true);
+        let page = keys
+            .iter()
+            .filter_map(|key| self.pages.get(key))
+            .flatten()
+            .find_map(|page| page.get_resp(req, res, status, format));
+        GENERATING.set(false);
+        match page {
+            Some(page) => page.status_code(status),
+            None => ErrorPages::builtin(status, format),
+        }
+    }
+
+    /// `404 Not Found` as text, a minimal HTML page or `{"status":404,"error":"Not Found"}`
+    pub fn builtin(status: StatusCode, format: ErrorFormat) -> HTTPResponse {
+        let response = HTTPResponse::new().status_code(status);
+        match format {
+            ErrorFormat::Plain => response.ct(PLAIN).body(status.as_str().to_string().into_bytes()),
+            ErrorFormat::Html => response.ct(HTML).body(
+                format!(
+                    "<!DOCTYPE html>\n<html><head><meta charset=\"utf-8\"><title>{0}</title></head>\n<body><h1>{0}</h1></body></html>\n",
+                    status.as_str()
+                )
+                .into_bytes(),
+            ),
+            ErrorFormat::Json => response.ct(JSON).body(
+                format!("{{\"status\":{},\"error\":\"{}\"}}", status.code(), status.reason()).into_bytes(),
+            ),
+        }
+    }
+}
+
+impl Default for ErrorPages {
+    fn default() -> Self {
+        Self {
+            pages: HashMap::new(),
+        }
+    }
+}
+
+impl New for ErrorPages {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::http::{router::{Router, Routes}, utils::HTTPHeader};
+
+    fn negotiate(accept: &str) -> ErrorFormat {
+        let mut req = HTTPRequest::new();
+        req.parts.headcont.push(HTTPHeader {
+            key: AllowedExtentions::Accept,
+            value: accept.to_string(),
+        });
+        ErrorFormat::negotiate(&req)
+    }
+
+    #[test]
+    fn accept() {
+        assert_eq!(ErrorFormat::negotiate(&HTTPRequest::new()), ErrorFormat::Plain);
+        assert_eq!(negotiate("*/*"), ErrorFormat::Plain);
+        assert_eq!(negotiate("text/html"), ErrorFormat::Html);
+        assert_eq!(negotiate("text/html;q=0"), ErrorFormat::Plain);
+        assert_eq!(negotiate("application/json;q=0, text/html;q=0"), ErrorFormat::Plain);
+        assert_eq!(negotiate("text/html;q=0, application/json;q=0.1"), ErrorFormat::Json);
+        assert_eq!(negotiate("application/json, text/html"), ErrorFormat::Html);
+        assert_eq!(negotiate("text/html;q=0.5, application/problem+json"), ErrorFormat::Json);
+        assert_eq!(negotiate("text/plain, text/html;q=0.9"), ErrorFormat::Plain);
+    }
+
+    fn request(host: &str, accept: Option<&str>) -> HTTPRequest {
+        let mut req = HTTPRequest::new();
+        req.parts.host = Some(Host::parse(host).unwrap());
+        if let Some(accept) = accept {
+            req.parts.headcont.push(HTTPHeader {
+                key: AllowedExtentions::Accept,
+                value: accept.to_string(),
+            });
+        }
+        req
+    }
+
+    fn text(body: &'static str) -> ErrorPage {
+        ErrorPage::handler(move |_, _, _, _| HTTPResponse::new().body(body.as_bytes().to_vec()))
+    }
+
+    fn body(response: &HTTPResponse) -> &str {
+        std::str::from_utf8(&response.body).unwrap()
+    }
+
+    #[test]
+    fn host_first() {
+        let pages = ErrorPages::new()
+            .page(StatusCode::NOTFOUND, text("global"))
+            .host("Example.com", StatusCode::NOTFOUND, text("host"))
+            .unwrap()
+            .host("example.com", StatusCode::GONE, text("gone"))
+            .unwrap();
+        assert!(ErrorPages::new().host("a b", StatusCode::NOTFOUND, text("")).is_err());
+        let res = Client::new();
+        let response = pages.respond(&request("example.com", None), &res, StatusCode::NOTFOUND);
+        assert_eq!((response.rsheader.status_code, body(&response)), (StatusCode::NOTFOUND, "host"));
+        let response = pages.respond(&request("other.com", None), &res, StatusCode::NOTFOUND);
+        assert_eq!(body(&response), "global");
+        let response = pages.respond(&HTTPRequest::new(), &res, StatusCode::NOTFOUND);
+        assert_eq!(body(&response), "global");
+        // Only the host has one
+        let response = pages.respond(&request("other.com", None), &res, StatusCode::GONE);
+        assert_eq!(body(&response), "410 Gone");
+    }
+
+    #[test]
+    fn files_by_format() {
+        let base = std::env::temp_dir().join(format!("error-pages-{}", std::process::id()));
+        std::fs::create_dir_all(&base).unwrap();
+        std::fs::write(base.join("404.json"), "{\"custom\":true}").unwrap();
+        std::fs::write(base.join("404.html"), "<h1>custom</h1>").unwrap();
+        let pages = ErrorPages::new()
+            .page(StatusCode::NOTFOUND, ErrorPage::file(base.join("404.json")))
+            .page(StatusCode::NOTFOUND, ErrorPage::file(base.join("404.html")))
+            .page(StatusCode::GONE, ErrorPage::file(base.join("410.html")));
+        let res = Client::new();
+        let response = pages.respond(&request("x", Some("text/html")), &res, StatusCode::NOTFOUND);
+        assert_eq!(body(&response), "<h1>custom</h1>");
+        assert!(response.rsheader.fields.contains(&("Content-Type".to_string(), "text/html".to_string())));
+        let response = pages.respond(&request("x", Some("application/json")), &res, StatusCode::NOTFOUND);
+        assert_eq!(body(&response), "{\"custom\":true}");
+        // Plain takes whatever comes first
+        let response = pages.respond(&request("x", None), &res, StatusCode::NOTFOUND);
+        assert_eq!(body(&response), "{\"custom\":true}");
+        // The file does not exist
+        let response = pages.respond(&request("x", Some("text/html")), &res, StatusCode::GONE);
+        assert_eq!(response.rsheader.status_code, StatusCode::GONE);
+        assert!(body(&response).contains("<h1>410 Gone</h1>"));
+        let response = pages.respond(&request("x", Some("application/json")), &res, StatusCode::GONE);
+        assert_eq!(body(&response), "{\"status\":410,\"error\":\"Gone\"}");
+        std::fs::remove_dir_all(&base).unwrap();
+    }
+
+    #[test]
+    fn failing_pages() {
+        let pages = ErrorPages::new()
+            .page(StatusCode::NOTFOUND, ErrorPage::handler(|_, _, _, _| panic!("broken page")))
+            .page(StatusCode::NOTFOUND, text("second"))
+            .page(StatusCode::GONE, ErrorPage::handler(|_, _, _, _| panic!("broken page")));
+        let res = Client::new();
+        let response = pages.respond(&request("x", None), &res, StatusCode::NOTFOUND);
+        assert_eq!(body(&response), "second");
+        let response = pages.respond(&request("x", None), &res, StatusCode::GONE);
+        assert_eq!(body(&response), "410 Gone");
+        // Still usable after a panic
+        let response = pages.respond(&request("x", None), &res, StatusCode::NOTFOUND);
+        assert_eq!(body(&response), "second");
+    }
+
+    #[test]
+    fn nested_errors() {
+        let pages = ErrorPages::new()
+            .page(
+                StatusCode::NOTFOUND,
+                ErrorPage::handler(|req, res, _, _| {
+                    let inner = res.error(req, StatusCode::INTERNALSERVERERROR);
+                    let text = format!("outer, {}", String::from_utf8_lossy(&inner.body));
+                    inner.body(text.into_bytes())
+                }),
+            )
+            .page(StatusCode::INTERNALSERVERERROR, text("custom 500"));
+        let res = Client {
+            routes: Routes {
+                router: Router::new(),
+                errors: pages,
+            }
+            .into(),
+            ..Client::new()
+        };
+        let req = request("x", None);
+        let response = res.error(&req, StatusCode::NOTFOUND);
+        assert_eq!(response.rsheader.status_code, StatusCode::NOTFOUND);
+        assert_eq!(body(&response), "outer, 500 Internal Server Error");
+        // The guard is lifted again
+        assert_eq!(body(&res.error(&req, StatusCode::INTERNALSERVERERROR)), "custom 500");
+    }
+}
@@ -0,0 +1,410 @@
+use std::{fmt::Display, str::FromStr};
+
+#[cfg(feature = "json")]
+use serde::{Serialize, de::DeserializeOwned};
+
+#[cfg(feature = "json")]
+use crate::http::json::{Json, JsonError};
+use crate::{
+    http::{
+        cookie::CookieJar,
+        form::{Form, FormError, FormLimits},
+        method::Method,
+        request::HTTPRequest,
+        response::{HTTPResponse, StatusCode},
+        router::Params,
+        server::Client,
+        uri::{QueryError, Querys},
+        utils::{AllowedExtentions, HTTPExtentions, HTTPHeader, PLAIN},
+    },
+    traits::New,
+};
+
+/// Something a handler can take as argument
+pub trait FromRequest: Sized {
+    fn from_request(req: &HTTPRequest, res: &Client) -> Result<Self, Rejection>;
+}
+
+/// Something a handler can return
+pub trait IntoResponse {
+    fn into_response(self) -> HTTPResponse;
+}
+
+/// A failed extraction, answered as plain text with its status code
+#[derive(Debug)]
+pub struct Rejection {
+    status: StatusCode,
+    message: String,
+}
+
+impl Rejection {
+    pub fn new(status: StatusCode, message: impl Display) -> Rejection {
+        Rejection {
+            status,
+            message: message.to_string(),
+        }
+    }
+    pub fn bad_request(message: impl Display) -> Rejection {
+        Rejection::new(StatusCode::BADREQUEST, message)
+    }
+    pub fn unauthorized(message: impl Display) -> Rejection {
+        Rejection::new(StatusCode::UNAUTHORIZED, message)
+    }
+    pub fn status_code(&self) -> StatusCode {
+        self.status
+    }
+    pub fn message(&self) -> &str {
+        &self.message
+    }
+}
+
+impl Display for Rejection {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}: {}", self.status.as_str(), self.message)
+    }
+}
+
+impl std::error::Error for Rejection {}
+
+impl IntoResponse for Rejection {
+    fn into_response(self) -> HTTPResponse {
+        HTTPResponse::new()
+            .status_code(self.status)
+            .ct(PLAIN)
+            .body(self.to_string().into_bytes())
+    }
+}
+
+/// The first route parameter, `/users/:id` with `Path<u32>`. Later parameters
+/// are not read, routes like `/users/:id/orders/:order` take `Params`
+pub struct Path<T>(pub T);
+
+/// Typed query parameters
+pub struct Query<T>(pub T);
+
+/// How a type is read from the query string, usually with `Querys::parse_value`
+pub trait FromQuery: Sized {
+    fn from_query(query: &Querys) -> Result<Self, QueryError>;
+}
+
+/// Every request header
+pub struct Headers(pub HTTPExtentions);
+
+/// The raw request body
+pub struct Bytes(pub Vec<u8>);
+
+/// The id of the logged in account, `401` without a valid session
+pub struct Session(pub u16);
+
+/// A clone of a value in the application state, usually an `Arc`
+pub struct Shared<T>(pub T);
+
+impl<T> FromRequest for Path<T>
+where
+    T: FromStr,
+    T::Err: Display,
+{
+    fn from_request(req: &HTTPRequest, _: &Client) -> Result<Self, Rejection> {
+        let (name, value) = req
+            .parts
+            .params
+            .iter()
+            .next()
+            .ok_or_else(|| Rejection::bad_request("Missing path parameter"))?;
+        value.parse().map(Path).map_err(|e: T::Err| {
+            Rejection::bad_request(format!("Invalid path parameter \"{}\": {}", name, e))
+        })
+    }
+}
+
+impl FromRequest for Params {
+    fn from_request(req: &HTTPRequest, _: &Client) -> Result<Self, Rejection> {
+        Ok(req.parts.params.clone())
+    }
+}
+
+impl<T: FromQuery> FromRequest for Query<T> {
+    fn from_request(req: &HTTPRequest, _: &Client) -> Result<Self, Rejection> {
+        T::from_query(&req.parts.uri.query)
+            .map(Query)
+            .map_err(Rejection::bad_request)
+    }
+}
+
+impl FromQuery for Querys {
+    fn from_query(query: &Querys) -> Result<Self, QueryError> {
+        Ok(query.clone())
+    }
+}
+
+impl Headers {
+    /// Headers without own `AllowedExtentions` variant are compared ignoring case
+    pub fn get(&self, key: &str) -> Option<&str> {
+        let key = HTTPHeader::key_parse(key);
+        self.0
+            .iter()
+            .find(|header| match (&header.key, &key) {
+                (AllowedExtentions::Unknown(a), AllowedExtentions::Unknown(b)) => {
+                    a.eq_ignore_ascii_case(b)
+                }
+                (a, b) => a == b,
+            })
+            .map(|header| header.value.as_str())
+    }
+}
+
+impl FromRequest for Headers {
+    fn from_request(req: &HTTPRequest, _: &Client) -> Result<Self, Rejection> {
+        Ok(Headers(req.parts.headcont.clone()))
+    }
+}
+
+impl FromRequest for CookieJar {
+    fn from_request(req: &HTTPRequest, _: &Client) -> Result<Self, Rejection> {
+        Ok(CookieJar::from_request(req))
+    }
+}
+
+/// With the default `FormLimits`
+impl FromRequest for Form {
+    fn from_request(req: &HTTPRequest, _: &Client) -> Result<Self, Rejection> {
+        Form::from_request(req, &FormLimits::new()).map_err(|e| {
+            if e == FormError::TOOLARGE {
+                Rejection::new(StatusCode::PAYLOADTOOLARGE, e)
+            } else {
+                Rejection::bad_request(e)
+            }
+        })
+    }
+}
+
+impl FromRequest for Bytes {
+    fn from_request(req: &HTTPRequest, _: &Client) -> Result<Self, Rejection> {
+        Ok(Bytes(req.body.clone()))
+    }
+}
+
+impl FromRequest for Session {
+    fn from_request(req: &HTTPRequest, _: &Client) -> Result<Self, Rejection> {
+        req.parts
+            .account
+            .map(Session)
+            .ok_or_else(|| Rejection::unauthorized("Not logged in"))
+    }
+}
+
+impl FromRequest for Method {
+    fn from_request(req: &HTTPRequest, _: &Client) -> Result<Self, Rejection> {
+        Ok(req.parts.method.clone())
+    }
+}
+
+impl<T: Clone + 'static> FromRequest for Shared<T> {
+    fn from_request(_: &HTTPRequest, res: &Client) -> Result<Self, Rejection> {
+        res.state::<T>().cloned().map(Shared).ok_or_else(|| {
+            Rejection::new(
+                StatusCode::INTERNALSERVERERROR,
+                format!("No state of type {}", std::any::type_name::<T>()),
+            )
+        })
+    }
+}
+
+#[cfg(feature = "json")]
+impl<T: DeserializeOwned> FromRequest for Json<T> {
+    fn from_request(req: &HTTPRequest, _: &Client) -> Result<Self, Rejection> {
+        Json::from_request(req).map_err(|e| Rejection::new(e.status_code(), e))
+    }
+}
+
+/// `None` instead of a rejection
+impl<T: FromRequest> FromRequest for Option<T> {
+    fn from_request(req: &HTTPRequest, res: &Client) -> Result<Self, Rejection> {
+        Ok(T::from_request(req, res).ok())
+    }
+}
+
+impl IntoResponse for HTTPResponse {
+    fn into_response(self) -> HTTPResponse {
+        self
+    }
+}
+
+impl IntoResponse for String {
+    fn into_response(self) -> HTTPResponse {
+        HTTPResponse::new()
+            .status_code(StatusCode::OK)
+            .ct(PLAIN)
+            .body(self.into_bytes())
+    }
+}
+
+impl IntoResponse for &'static str {
+    fn into_response(self) -> HTTPResponse {
+        self.to_string().into_response()
+    }
+}
+
+impl IntoResponse for Vec<u8> {
+    fn into_response(self) -> HTTPResponse {
+        HTTPResponse::new().status_code(StatusCode::OK).body(self)
+    }
+}
+
+impl IntoResponse for StatusCode {
+    fn into_response(self) -> HTTPResponse {
+        HTTPResponse::new().status_code(self)
+    }
+}
+
+/// Overrides the status code of `T`
+impl<T: IntoResponse> IntoResponse for (StatusCode, T) {
+    fn into_response(self) -> HTTPResponse {
+        self.1.into_response().status_code(self.0)
+    }
+}
+
+impl<T: IntoResponse, E: IntoResponse> IntoResponse for Result<T, E> {
+    fn into_response(self) -> HTTPResponse {
+        match self {
+            Ok(value) => value.into_response(),
+            Err(e) => e.into_response(),
+        }
+    }
+}
+
+#[cfg(feature = "json")]
+impl<T: Serialize> IntoResponse for Json<T> {
+    fn into_response(self) -> HTTPResponse {
+        Json::into_response(self)
+    }
+}
+
+#[cfg(feature = "json")]
+impl IntoResponse for JsonError {
+    fn into_response(self) -> HTTPResponse {
+        JsonError::into_response(self)
+    }
+}
+
+/// A function whose arguments are all extractors, see `ApiFn::handler`
+pub trait Handler<Args>: Send + Sync + 'static {
+    fn call(&self, req: &HTTPRequest, res: &Client) -> HTTPResponse;
+}
+
+macro_rules! handler {
+    ($($arg:ident),*) => {
+        #[allow(non_snake_case)]
+        impl<F, R, $($arg,)*> Handler<($($arg,)*)> for F
+        where
+            F: Fn($($arg),*) -> R + Send + Sync + 'static,
+            R: IntoResponse,
+            $($arg: FromRequest,)*
+        {
+            fn call(&self, req: &HTTPRequest, res: &Client) -> HTTPResponse {
+                let _ = (req, res);
+                $(
+                    let $arg = match $arg::from_request(req, res) {
+                        Ok(value) => value,
+                        Err(rejection) => return rejection.into_response(),
+                    };
+                )*
+                (self)($($arg),*).into_response()
+            }
+        }
+    };
+}
+
+handler!();
+handler!(A);
+handler!(A, B);
+handler!(A, B, C);
+handler!(A, B, C, D);
+handler!(A, B, C, D, E);
+handler!(A, B, C, D, E, G);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::http::{router::Router, state::State};
+
+    // A request to `path` routed through `pattern`
+    fn request(pattern: &str, path: &str) -> HTTPRequest {
+        let mut router = Router::new();
+        router.insert(pattern, ()).unwrap();
+        let mut req = HTTPRequest::new();
+        req.parts.params = router.at(path).unwrap().params;
+        req
+    }
+
+    fn call<Args>(handler: impl Handler<Args>, req: &HTTPRequest) -> (StatusCode, String) {
+        let response = handler.call(req, &Client::new());
+        (response.rsheader.status_code, String::from_utf8(response.body.into_vec()).unwrap())
+    }
+
+    #[test]
+    fn path() {
+        let handler = |Path(id): Path<u32>| format!("user {}", id);
+        assert_eq!(call(handler, &request("/users/:id", "/users/7")), (StatusCode::OK, "user 7".to_string()));
+        let (status, body) = call(handler, &request("/users/:id", "/users/seven"));
+        assert_eq!(status, StatusCode::BADREQUEST);
+        assert_eq!(body, "400 Bad Request: Invalid path parameter \"id\": invalid digit found in string");
+        let (status, body) = call(handler, &request("/users", "/users"));
+        assert_eq!((status, body.as_str()), (StatusCode::BADREQUEST, "400 Bad Request: Missing path parameter"));
+        // Only the first parameter
+        let req = request("/users/:id/orders/:order", "/users/7/orders/9");
+        assert_eq!(call(handler, &req).1, "user 7");
+        let params = |params: Params| format!("{:?}", params.iter().collect::<Vec<_>>());
+        assert_eq!(call(params, &req).1, "[(\"id\", \"7\"), (\"order\", \"9\")]");
+    }
+
+    #[test]
+    fn session() {
+        let handler = |Session(id): Session| format!("account {}", id);
+        let mut req = HTTPRequest::new();
+        let (status, body) = call(handler, &req);
+        assert_eq!((status, body.as_str()), (StatusCode::UNAUTHORIZED, "401 Unauthorized: Not logged in"));
+        req.parts.account = Some(3);
+        assert_eq!(call(handler, &req), (StatusCode::OK, "account 3".to_string()));
+    }
+
+    #[test]
+    fn shared() {
+        let handler = |Shared(count): Shared<u32>| format!("count {}", count);
+        let res = Client {
+            state: State::new().with(3u32),
+            ..Client::new()
+        };
+        let response = handler.call(&HTTPRequest::new(), &res);
+        assert_eq!(response.rsheader.status_code, StatusCode::OK);
+        assert_eq!(&*response.body, b"count 3");
+        let (status, body) = call(handler, &HTTPRequest::new());
+        assert_eq!(status, StatusCode::INTERNALSERVERERROR);
+        assert_eq!(body, "500 Internal Server Error: No state of type u32");
+    }
+
+    #[test]
+    fn optional() {
+        let handler = |id: Option<Path<u32>>, session: Option<Session>| match (id, session) {
+            (Some(Path(id)), _) => format!("user {}", id),
+            (None, None) => "anonymous".to_string(),
+            (None, Some(_)) => "logged in".to_string(),
+        };
+        assert_eq!(call(handler, &request("/users/:id", "/users/seven")), (StatusCode::OK, "anonymous".to_string()));
+        assert_eq!(call(handler, &request("/users/:id", "/users/7")).1, "user 7");
+    }
+
+    #[test]
+    fn arity() {
+        let req = request("/users/:id", "/users/7");
+        assert_eq!(call(|| "none", &req), (StatusCode::OK, "none".to_string()));
+        assert_eq!(call(|| StatusCode::NOCONTENT, &req).0, StatusCode::NOCONTENT);
+        let six = |Path(id): Path<u32>, params: Params, method: Method, _: Headers, _: Bytes, _: Option<Session>| {
+            (StatusCode::CREATED, format!("{} {} {}", id, params.len(), method.as_str()))
+        };
+        assert_eq!(call(six, &req), (StatusCode::CREATED, "7 1 GET".to_string()));
+        // The first rejection answers, later extractors do not run
+        let rejected = |_: Session, Path(id): Path<u32>| format!("user {}", id);
+        assert_eq!(call(rejected, &request("/users/:id", "/users/x")).0, StatusCode::UNAUTHORIZED);
+    }
+}
@@ -0,0 +1,307 @@
+use std::{
+    collections::HashMap,
+    fs, io,
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex, MutexGuard},
+    time::{Duration, Instant, SystemTime},
+};
+
+use crate::traits::New;
+
+/// How a static site uses the shared `FileCache`
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Caching {
+    /// Read from disk on every request
+    Off,
+    /// Revalidated on the interval of the cache
+    Shared,
+    /// Revalidated on its own interval, `Duration::ZERO` checks on every request
+    Every(Duration),
+}
+
+/// File contents shared between requests. Bounded by a byte budget, the least
+/// recently used files are evicted first. A file is compared by mtime and size
+/// once its interval has passed and read again if either changed
+pub struct FileCache {
+    budget: usize,
+    revalidate: Duration,
+    inner: Mutex<Inner>,
+}
+
+struct Inner {
+    files: HashMap<PathBuf, Cached>,
+    used: usize,
+    clock: u64,
+}
+
+struct Cached {
+    data: Arc<[u8]>,
+    modified: Option<SystemTime>,
+    len: u64,
+    checked: Instant,
+    last_use: u64,
+}
+
+impl FileCache {
+    /// Files larger than `bytes` are never cached
+    pub fn budget(mut self, bytes: usize) -> FileCache {
+        self.budget = bytes;
+        self
+    }
+    pub fn revalidate(mut self, interval: Duration) -> FileCache {
+        self.revalidate = interval;
+        self
+    }
+
+    fn lock(&self) -> MutexGuard<'_, Inner> {
+        // A panic while holding the lock leaves the map consistent
+        self.inner.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    pub fn load(&self, path: &Path, caching: Caching) -> io::Result<Arc<[u8]>> {
+        match caching {
+            Caching::Off => fs::read(path).map(Arc::from),
+            Caching::Shared => self.get_every(path, self.revalidate),
+            Caching::Every(interval) => self.get_every(path, interval),
+        }
+    }
+
+    pub fn get(&self, path: &Path) -> io::Result<Arc<[u8]>> {
+        self.get_every(path, self.revalidate)
+    }
+
+    /// Like `get` with its own revalidation interval
+    pub fn get_every(&self, path: &Path, interval: Duration) -> io::Result<Arc<[u8]>> {
+        if let Some(data) = self.lock().hit(path, |cached| cached.checked.elapsed() < interval) {
+            return Ok(data);
+        }
+        // Disk access happens without the lock, other requests keep being served
+        let meta = match fs::metadata(path) {
+            Ok(meta) => meta,
+            Err(e) => {
+                self.invalidate(path);
+                return Err(e);
+            }
+        };
+        let modified = meta.modified().ok();
+        let unchanged = |cached: &Cached| {
+            cached.modified.is_some() && cached.modified == modified && cached.len == meta.len()
+        };
+        if let Some(data) = self.lock().hit(path, |cached| {
+            let fresh = unchanged(cached);
+            if fresh {
+                cached.checked = Instant::now();
+            }
+            fresh
+        }) {
+            return Ok(data);
+        }
+        let data: Arc<[u8]> = fs::read(path)?.into();
+        if data.len() <= self.budget {
+            let mut inner = self.lock();
+            inner.clock += 1;
+            let cached = Cached {
+                data: data.clone(),
+                modified,
+                len: data.len() as u64,
+                checked: Instant::now(),
+                last_use: inner.clock,
+            };
+            inner.used += data.len();
+            if let Some(old) = inner.files.insert(path.to_path_buf(), cached) {
+                inner.used -= old.data.len();
+            }
+            inner.evict(self.budget);
+        }
+        Ok(data)
+    }
+
+    pub fn invalidate(&self, path: &Path) {
+        let mut inner = self.lock();
+        if let Some(old) = inner.files.remove(path) {
+            inner.used -= old.data.len();
+        }
+    }
+    pub fn clear(&self) {
+        let mut inner = self.lock();
+        inner.files.clear();
+        inner.used = 0;
+    }
+    /// Bytes currently cached
+    pub fn used(&self) -> usize {
+        self.lock().used
+    }
+    pub fn len(&self) -> usize {
+        self.lock().files.len()
+    }
+    pub fn is_empty(&self) -> bool {
+        self.lock().files.is_empty()
+    }
+}
+
+impl Inner {
+    fn hit(&mut self, path: &Path, fresh: impl FnOnce(&mut Cached) -> bool) -> Option<Arc<[u8]>> {
+        self.clock += 1;
+        let clock = self.clock;
+        let cached = self.files.get_mut(path)?;
+        if !fresh(cached) {
+            return None;
+        }
+        cached.last_use = clock;
+        Some(cached.data.clone())
+    }
+    fn evict(&mut self, budget: usize) {
+        while self.used > budget {
+            let oldest = match self
+                .files
+                .iter()
+                .min_by_key(|(_, cached)| cached.last_use)
+                .map(|(path, _)| path.clone())
+            {
+                Some(oldest) => oldest,
+                None => break,
+            };
+            if let Some(old) = self.files.remove(&oldest) {
+                self.used -= old.data.len();
+            }
+        }
+    }
+}
+
+/// 64 MiB, revalidated every two seconds
+impl Default for FileCache {
+    fn default() -> Self {
+        Self {
+            budget: 64 * 1024 * 1024,
+            revalidate: Duration::from_secs(2),
+            inner: Mutex::new(Inner {
+                files: HashMap::new(),
+                used: 0,
+                clock: 0,
+            }),
+        }
+    }
+}
+
+impl New for FileCache {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct Dir(PathBuf);
+
+    impl Drop for Dir {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.0);
+        }
+    }
+
+    fn dir(name: &str) -> Dir {
+        let dir = std::env::temp_dir().join(format!("file-cache-{}-{}", std::process::id(), name));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        Dir(dir)
+    }
+
+    fn write(dir: &Dir, name: &str, content: &str) -> PathBuf {
+        let path = dir.0.join(name);
+        fs::write(&path, content).unwrap();
+        path
+    }
+
+    // Rewrites every file with a different size, cached files keep their old
+    // content. Reading a file which was not cached caches it, so it goes last
+    fn stale(cache: &FileCache, paths: &[&PathBuf]) -> Vec<bool> {
+        for path in paths {
+            let old = fs::read(path).unwrap();
+            fs::write(path, [&old[..], b"!"].concat()).unwrap();
+        }
+        paths
+            .iter()
+            .map(|path| !cache.get(path).unwrap().ends_with(b"!"))
+            .collect()
+    }
+
+    #[test]
+    fn lru() {
+        let dir = dir("lru");
+        let cache = FileCache::new().budget(10).revalidate(Duration::from_secs(3600));
+        let a = write(&dir, "a", "aaaa");
+        let b = write(&dir, "b", "bbbb");
+        let c = write(&dir, "c", "cccc");
+        cache.get(&a).unwrap();
+        cache.get(&b).unwrap();
+        assert_eq!((cache.len(), cache.used()), (2, 8));
+        // `a` was used last, `b` goes
+        cache.get(&a).unwrap();
+        cache.get(&c).unwrap();
+        assert_eq!((cache.len(), cache.used()), (2, 8));
+        assert_eq!(stale(&cache, &[&a, &c, &b]), [true, true, false]);
+    }
+
+    #[test]
+    fn oversize() {
+        let dir = dir("oversize");
+        let cache = FileCache::new().budget(4).revalidate(Duration::from_secs(3600));
+        let small = write(&dir, "small", "1234");
+        let large = write(&dir, "large", "12345");
+        assert_eq!(&*cache.get(&large).unwrap(), b"12345");
+        assert!(cache.is_empty());
+        cache.get(&small).unwrap();
+        assert_eq!((cache.len(), cache.used()), (1, 4));
+        // Grown beyond the budget, the stale copy is not served again
+        fs::write(&small, "123456").unwrap();
+        assert_eq!(&*cache.get_every(&small, Duration::ZERO).unwrap(), b"123456");
+        assert_eq!((cache.len(), cache.used()), (1, 4));
+        assert_eq!(&*cache.get(&small).unwrap(), b"1234");
+    }
+
+    #[test]
+    fn revalidation() {
+        let dir = dir("revalidate");
+        let cache = FileCache::new().revalidate(Duration::from_secs(3600));
+        let path = write(&dir, "page.html", "one");
+        assert_eq!(&*cache.load(&path, Caching::Shared).unwrap(), b"one");
+        fs::write(&path, "three").unwrap();
+        // Within the interval
+        assert_eq!(&*cache.load(&path, Caching::Shared).unwrap(), b"one");
+        assert_eq!(&*cache.load(&path, Caching::Every(Duration::ZERO)).unwrap(), b"three");
+        assert_eq!((cache.len(), cache.used()), (1, 5));
+        assert_eq!(&*cache.load(&path, Caching::Shared).unwrap(), b"three");
+        // Unchanged mtime and size, the same data is handed out
+        let first = cache.load(&path, Caching::Every(Duration::ZERO)).unwrap();
+        let second = cache.load(&path, Caching::Every(Duration::ZERO)).unwrap();
+        assert!(Arc::ptr_eq(&first, &second));
+        // Off neither reads from nor fills the cache
+        fs::write(&path, "four").unwrap();
+        assert_eq!(&*cache.load(&path, Caching::Off).unwrap(), b"four");
+        assert_eq!(&*cache.load(&path, Caching::Shared).unwrap(), b"three");
+        // A removed file is dropped
+        fs::remove_file(&path).unwrap();
+        assert!(cache.load(&path, Caching::Every(Duration::ZERO)).is_err());
+        assert_eq!((cache.len(), cache.used()), (0, 0));
+    }
+
+    #[test]
+    fn accounting() {
+        let dir = dir("accounting");
+        let cache = FileCache::new();
+        let a = write(&dir, "a", "aaaa");
+        let b = write(&dir, "b", "bb");
+        cache.get(&a).unwrap();
+        cache.get(&b).unwrap();
+        assert_eq!(cache.used(), 6);
+        cache.invalidate(&a);
+        cache.invalidate(&a);
+        assert_eq!((cache.len(), cache.used()), (1, 2));
+        cache.invalidate(&dir.0.join("missing"));
+        assert_eq!(cache.used(), 2);
+        cache.get(&a).unwrap();
+        cache.clear();
+        assert_eq!((cache.len(), cache.used()), (0, 0));
+        assert!(cache.is_empty());
+        cache.get(&b).unwrap();
+        assert_eq!(cache.used(), 2);
+    }
+}
@@ -0,0 +1,218 @@
+use std::{borrow::Cow, error::Error, fmt::Display};
+
+use crate::{
+    http::{request::HTTPRequest, uri::Querys},
+    traits::New,
+};
+
+/// An `application/x-www-form-urlencoded` body, read like a query string
+/// with `Querys::get`, `parse_value` and friends
+pub struct Form(pub Querys);
+
+pub struct FormLimits {
+    pub max_size: usize,
+    pub max_fields: usize,
+    /// Replace invalid UTF-8 with U+FFFD instead of failing
+    pub lossy: bool,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub struct FormError(u8);
+
+impl FormError {
+    pub const TOOLARGE: FormError = FormError(1);
+    pub const TOOMANYFIELDS: FormError = FormError(2);
+    pub const INVUTF8: FormError = FormError(3);
+    pub fn reason(&self) -> &str {
+        match self.0 {
+            1 => "Form Body too Large",
+            2 => "Too many Form Fields",
+            3 => "Form contains invalid UTF-8",
+            _ => "Error not defined",
+        }
+    }
+}
+
+impl Display for FormError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.reason())
+    }
+}
+
+impl Error for FormError {}
+
+impl Default for FormLimits {
+    fn default() -> Self {
+        Self {
+            max_size: 64 * 1024,
+            max_fields: 256,
+            lossy: false,
+        }
+    }
+}
+
+impl New for FormLimits {}
+
+fn hex(b: u8) -> Option<u8> {
+    match b {
+        b'0'..=b'9' => Some(b - b'0'),
+        b'a'..=b'f' => Some(b - b'a' + 10),
+        b'A'..=b'F' => Some(b - b'A' + 10),
+        _ => None,
+    }
+}
+
+/// Malformed escapes are kept as they are (WHATWG URL)
+pub fn percent_decode(src: &[u8], plus_as_space: bool) -> Vec<u8> {
+    let mut out = Vec::with_capacity(src.len());
+    let mut i = 0;
+    while i < src.len() {
+        match src[i] {
+            b'%' if i + 2 < src.len() => {
+                match (hex(src[i + 1]), hex(src[i + 2])) {
+                    (Some(h), Some(l)) => {
+                        out.push(h << 4 | l);
+                        i += 3;
+                        continue;
+                    }
+                    _ => out.push(b'%'),
+                }
+            }
+            b'+' if plus_as_space => out.push(b' '),
+            b => out.push(b),
+        }
+        i += 1;
+    }
+    out
+}
+
+/// `+` as space and invalid UTF-8 as U+FFFD, `src` is borrowed if there is nothing to decode
+pub fn decode_lossy(src: &str) -> Cow<'_, str> {
+    if !src.contains(['%', '+']) {
+        return Cow::Borrowed(src);
+    }
+    Cow::Owned(String::from_utf8_lossy(&percent_decode(src.as_bytes(), true)).into_owned())
+}
+
+// `src` itself is UTF-8, only escapes can break that
+fn decodes_to_utf8(src: &str) -> bool {
+    !src.contains('%') || std::str::from_utf8(&percent_decode(src.as_bytes(), false)).is_ok()
+}
+
+impl Form {
+    /// Checks the limits and, unless `lossy`, that every key and value decodes to UTF-8.
+    /// Keys without `=` are flags like in a query string
+    pub fn decode(src: &[u8], limits: &FormLimits) -> Result<Form, FormError> {
+        if src.len() > limits.max_size {
+            return Err(FormError::TOOLARGE);
+        }
+        let src = match std::str::from_utf8(src) {
+            Ok(src) => Cow::Borrowed(src),
+            Err(_) if limits.lossy => String::from_utf8_lossy(src),
+            Err(_) => return Err(FormError::INVUTF8),
+        };
+        let query = Querys::parse(&src);
+        for (i, field) in query.iter().enumerate() {
+            if i == limits.max_fields {
+                return Err(FormError::TOOMANYFIELDS);
+            }
+            let valid = limits.lossy
+                || (decodes_to_utf8(field.raw_key())
+                    && field.raw_value().is_none_or(decodes_to_utf8));
+            if !valid {
+                return Err(FormError::INVUTF8);
+            }
+        }
+        Ok(Form(query))
+    }
+
+    pub fn from_request(req: &HTTPRequest, limits: &FormLimits) -> Result<Form, FormError> {
+        Form::decode(&req.body, limits)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::http::uri::QueryError;
+
+    fn decode(src: &[u8]) -> Result<Querys, FormError> {
+        Form::decode(src, &FormLimits::new()).map(|Form(form)| form)
+    }
+
+    #[test]
+    fn plus_as_space() {
+        let form = decode(b"q=hello+world&plus=%2B&key+1=%41+b").unwrap();
+        assert_eq!(form.get("q").as_deref(), Some("hello world"));
+        assert_eq!(form.get("plus").as_deref(), Some("+"));
+        assert_eq!(form.get("key 1").as_deref(), Some("A b"));
+        // Malformed escapes are kept
+        assert_eq!(
+            decode(b"a=%zz%4").unwrap().get("a").as_deref(),
+            Some("%zz%4")
+        );
+    }
+
+    #[test]
+    fn repeated_keys() {
+        let form = decode(b"a=1&b=2&a=3").unwrap();
+        assert_eq!(form.get("a").as_deref(), Some("1"));
+        assert_eq!(form.get_all("a").collect::<Vec<_>>(), ["1", "3"]);
+        assert_eq!(form.parse_all::<u8>("a").unwrap(), [1, 3]);
+        let keys: Vec<_> = form.iter().map(|q| q.key()).collect();
+        assert_eq!(keys, ["a", "b", "a"]);
+    }
+
+    #[test]
+    fn keys_without_value() {
+        let form = decode(b"flag&empty=&&x=1").unwrap();
+        assert_eq!(form.len(), 3);
+        assert!(form.has("flag"));
+        assert_eq!(form.get("flag"), None);
+        assert!(matches!(
+            form.parse_value::<String>("flag"),
+            Err(QueryError::NoValue(_))
+        ));
+        assert_eq!(form.get("empty").as_deref(), Some(""));
+    }
+
+    #[test]
+    fn limits() {
+        let limits = FormLimits {
+            max_size: 7,
+            max_fields: 2,
+            lossy: false,
+        };
+        assert!(Form::decode(b"a=1&b=2", &limits).is_ok());
+        assert_eq!(
+            Form::decode(b"a=1&b=22", &limits).err(),
+            Some(FormError::TOOLARGE)
+        );
+        assert_eq!(
+            Form::decode(b"a&b&c", &limits).err(),
+            Some(FormError::TOOMANYFIELDS)
+        );
+        // Empty fields are no fields
+        assert!(Form::decode(b"a&&&b", &limits).is_ok());
+    }
+
+    #[test]
+    fn utf8() {
+        assert_eq!(decode(b"a=%C3%BC").unwrap().get("a").as_deref(), Some("ü"));
+        assert_eq!(
+            decode("a=ü".as_bytes()).unwrap().get("a").as_deref(),
+            Some("ü")
+        );
+        for src in [&b"a=%FF"[..], b"%C3=1", b"a=\xFF"] {
+            assert_eq!(decode(src).err(), Some(FormError::INVUTF8));
+        }
+
+        let lossy = FormLimits {
+            lossy: true,
+            ..FormLimits::new()
+        };
+        let Form(form) = Form::decode(b"a=%FF&b=\xFF", &lossy).unwrap();
+        assert_eq!(form.get("a").as_deref(), Some("\u{FFFD}"));
+        assert_eq!(form.get("b").as_deref(), Some("\u{FFFD}"));
+    }
+}
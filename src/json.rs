@@ -0,0 +1,193 @@
+use std::{error::Error, fmt::Display};
+
+use serde::{Serialize, de::DeserializeOwned};
+
+use crate::{
+    http::{
+        request::HTTPRequest,
+        response::{HTTPResponse, StatusCode},
+        utils::{AllowedExtentions, JSON},
+    },
+    traits::New,
+};
+
+/// A typed JSON request or response body
+pub struct Json<T>(pub T);
+
+#[derive(Debug)]
+pub struct JsonError {
+    kind: JsonErrorKind,
+    message: String,
+}
+
+#[derive(Debug)]
+enum JsonErrorKind {
+    ContentType,
+    Syntax,
+    Serialize,
+}
+
+/// `application/json` and every `+json` type, parameters are ignored
+pub fn is_json(content_type: &str) -> bool {
+    let mime = content_type
+        .split(';')
+        .next()
+        .unwrap_or("")
+        .trim()
+        .to_ascii_lowercase();
+    mime == "application/json" || (mime.starts_with("application/") && mime.ends_with("+json"))
+}
+
+impl<T: DeserializeOwned> Json<T> {
+    pub fn from_request(req: &HTTPRequest) -> Result<Json<T>, JsonError> {
+        let json = req
+            .parts
+            .headcont
+            .iter()
+            .any(|header| header.key == AllowedExtentions::ContentType && is_json(&header.value));
+        if !json {
+            return Err(JsonError {
+                kind: JsonErrorKind::ContentType,
+                message: "Expected Content-Type: application/json".to_string(),
+            });
+        }
+        serde_json::from_slice(&req.body)
+            .map(Json)
+            .map_err(|e| JsonError {
+                kind: JsonErrorKind::Syntax,
+                message: e.to_string(),
+            })
+    }
+}
+
+impl<T: Serialize> Json<T> {
+    pub fn to_bytes(&self) -> Result<Vec<u8>, JsonError> {
+        serde_json::to_vec(&self.0).map_err(|e| JsonError {
+            kind: JsonErrorKind::Serialize,
+            message: e.to_string(),
+        })
+    }
+    /// `200 OK` with the serialized value, or a `500` if it cannot be serialized
+    pub fn into_response(self) -> HTTPResponse {
+        match self.to_bytes() {
+            Ok(body) => HTTPResponse::new()
+                .status_code(StatusCode::OK)
+                .ct(JSON)
+                .body(body),
+            Err(e) => e.into_response(),
+        }
+    }
+}
+
+impl JsonError {
+    pub fn status_code(&self) -> StatusCode {
+        match self.kind {
+            JsonErrorKind::ContentType => StatusCode::UNSUPPORTEDMEDIATYPE,
+            JsonErrorKind::Syntax => StatusCode::BADREQUEST,
+            JsonErrorKind::Serialize => StatusCode::INTERNALSERVERERROR,
+        }
+    }
+    pub fn message(&self) -> &str {
+        &self.message
+    }
+    /// `{"error": "..."}`
+    pub fn to_bytes(&self) -> Vec<u8> {
+        serde_json::json!({ "error": self.message })
+            .to_string()
+            .into_bytes()
+    }
+    /// `{"error": "..."}` with the matching status code
+    pub fn into_response(self) -> HTTPResponse {
+        HTTPResponse::new()
+            .status_code(self.status_code())
+            .ct(JSON)
+            .body(self.to_bytes())
+    }
+}
+
+impl Display for JsonError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.message)
+    }
+}
+
+impl Error for JsonError {}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use super::*;
+    use crate::http::utils::HTTPHeader;
+
+    fn request(content_type: Option<&str>, body: &str) -> HTTPRequest {
+        let mut req = HTTPRequest::new();
+        if let Some(value) = content_type {
+            req.parts.headcont.push(HTTPHeader {
+                key: AllowedExtentions::ContentType,
+                value: value.to_string(),
+            });
+        }
+        req.body = body.as_bytes().to_vec();
+        req
+    }
+
+    fn parse(content_type: Option<&str>, body: &str) -> Result<Vec<u32>, JsonError> {
+        Json::from_request(&request(content_type, body)).map(|Json(v)| v)
+    }
+
+    #[test]
+    fn content_type() {
+        for content_type in [
+            "application/json",
+            "Application/JSON; charset=utf-8",
+            "application/problem+json",
+            "application/vnd.api+json ;q=1",
+        ] {
+            assert!(is_json(content_type), "{}", content_type);
+            assert_eq!(parse(Some(content_type), "[1]").unwrap(), [1]);
+        }
+        for content_type in [
+            Some("text/plain"),
+            Some("text/json"),
+            Some("application/jsonx"),
+            None,
+        ] {
+            let e = parse(content_type, "[1]").unwrap_err();
+            assert_eq!(e.status_code(), StatusCode::UNSUPPORTEDMEDIATYPE);
+            assert_eq!(e.message(), "Expected Content-Type: application/json");
+        }
+    }
+
+    #[test]
+    fn syntax() {
+        for body in ["[1,", "{\"a\": 1}", "[-1]", ""] {
+            let e = parse(Some("application/json"), body).unwrap_err();
+            assert_eq!(e.status_code(), StatusCode::BADREQUEST);
+            assert!(!e.message().is_empty());
+        }
+        let e = parse(Some("application/json"), "[1").unwrap_err();
+        assert_eq!(e.message(), "EOF while parsing a list at line 1 column 2");
+        let body = String::from_utf8(e.into_response().body.into_vec()).unwrap();
+        assert_eq!(
+            body,
+            r#"{"error":"EOF while parsing a list at line 1 column 2"}"#
+        );
+    }
+
+    #[test]
+    fn response() {
+        let res = Json(HashMap::from([("a", 1)])).into_response();
+        assert_eq!(res.rsheader.status_code, StatusCode::OK);
+        assert_eq!(&*res.body, br#"{"a":1}"#);
+        assert!(res
+            .rsheader
+            .fields
+            .contains(&("Content-Type".to_string(), JSON.as_str().to_string())));
+
+        // Map keys have to be strings
+        let res = Json(HashMap::from([((1, 2), 3)])).into_response();
+        assert_eq!(res.rsheader.status_code, StatusCode::INTERNALSERVERERROR);
+        assert_eq!(&*res.body, br#"{"error":"key must be a string"}"#);
+    }
+}
@@ -3,10 +3,27 @@
 //pub mod site;
 //pub mod uri;
 //pub mod method;
+//pub mod middleware;
+//pub mod reload;
 //pub mod request;
 //pub mod response;
+//pub mod router;
+//pub mod state;
+//pub mod static_dir;
 //pub mod utils;
 //pub mod account;
+//pub mod extract;
+//pub mod file_cache;
+//pub mod config;
+//pub mod error_pages;
+//pub mod template;
+//pub mod cookie;
+//pub mod crypto;
+//pub mod date;
+//pub mod form;
+//pub mod multipart;
+//#[cfg(feature = "json")]
+//pub mod json;
 
 // Networking Crate
 //
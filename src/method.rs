@@ -8,6 +8,7 @@ enum MV {
     Head,
     Post,
     Put,
+    Patch,
     Delete,
     Connect,
     Options,
@@ -21,6 +22,7 @@ impl Method {
     pub const HEAD: Method = Method(MV::Head);
     pub const POST: Method = Method(MV::Post);
     pub const PUT: Method = Method(MV::Put);
+    pub const PATCH: Method = Method(MV::Patch);
     pub const DELETE: Method = Method(MV::Delete);
     pub const CONNECT: Method = Method(MV::Connect);
     pub const OPTIONS: Method = Method(MV::Options);
@@ -32,6 +34,7 @@ impl Method {
             MV::Head => "HEAD",
             MV::Post => "POST",
             MV::Put => "PUT",
+            MV::Patch => "PATCH",
             MV::Delete => "DELETE",
             MV::Connect => "CONNECT",
             MV::Options => "OPTIONS",
@@ -46,6 +49,7 @@ impl Method {
             "HEAD" => Method(MV::Head),
             "POST" => Method(MV::Post),
             "PUT" => Method(MV::Put),
+            "PATCH" => Method(MV::Patch),
             "DELETE" => Method(MV::Delete),
             "CONNECT" => Method(MV::Connect),
             "OPTIONS" => Method(MV::Options),
@@ -0,0 +1,228 @@
+use crate::{
+    http::{request::HTTPRequest, response::HTTPResponse, server::Client, uri::{Host, UriError}},
+    traits::New,
+};
+
+/// Runs around the handler of a request. Both steps default to doing nothing
+pub trait Middleware: Send + Sync {
+    /// Before routing, returning a response skips the handler and every later middleware
+    fn before(&self, req: &mut HTTPRequest, res: &Client) -> Option<HTTPResponse> {
+        let _ = (req, res);
+        None
+    }
+    /// After the handler, also for responses of a later `before`
+    fn after(&self, req: &HTTPRequest, res: &Client, response: HTTPResponse) -> HTTPResponse {
+        let _ = (req, res);
+        response
+    }
+}
+
+enum Scope {
+    Global,
+    Host(Host),
+    /// Without trailing `/`, matches whole segments only
+    Prefix(Box<str>),
+}
+
+/// Global middleware runs first, then per host, then per prefix from the
+/// shortest to the longest, each in registration order. `after` runs in reverse
+pub struct MiddlewareChain {
+    entries: Vec<(Scope, Box<dyn Middleware>)>,
+}
+
+impl Scope {
+    fn applies(&self, req: &HTTPRequest) -> bool {
+        match self {
+            Scope::Global => true,
+            Scope::Host(host) => req.parts.host.as_ref() == Some(host),
+            Scope::Prefix(prefix) => req
+                .parts
+                .uri
+                .path
+                .strip_prefix(prefix.as_ref())
+                .is_some_and(|rest| rest.is_empty() || rest.starts_with('/')),
+        }
+    }
+    fn rank(&self) -> usize {
+        match self {
+            Scope::Global => 0,
+            Scope::Host(_) => 1,
+            Scope::Prefix(prefix) => 2 + prefix.len(),
+        }
+    }
+}
+
+impl MiddlewareChain {
+    pub fn global(mut self, middleware: impl Middleware + 'static) -> MiddlewareChain {
+        self.push(Scope::Global, Box::new(middleware));
+        self
+    }
+    /// Only for requests to `host`, fails if `host` is not a valid host
+    pub fn host(mut self, host: &str, middleware: impl Middleware + 'static) -> Result<MiddlewareChain, UriError> {
+        let host = Host::parse(host)?;
+        self.push(Scope::Host(host), Box::new(middleware));
+        Ok(self)
+    }
+    /// Only for paths below `prefix`, `/api` matches `/api` and `/api/x` but not `/apis`
+    pub fn prefix(mut self, prefix: &str, middleware: impl Middleware + 'static) -> MiddlewareChain {
+        let prefix = prefix.trim_end_matches('/');
+        self.push(Scope::Prefix(prefix.into()), Box::new(middleware));
+        self
+    }
+    fn push(&mut self, scope: Scope, middleware: Box<dyn Middleware>) {
+        // Stable, so equal ranks keep their registration order
+        let at = self
+            .entries
+            .partition_point(|(other, _)| other.rank() <= scope.rank());
+        self.entries.insert(at, (scope, middleware));
+    }
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+    /// Runs the matching middleware around `handler`
+    pub fn run(
+        &self,
+        req: &mut HTTPRequest,
+        res: &Client,
+        handler: impl FnOnce(&mut HTTPRequest) -> HTTPResponse,
+    ) -> HTTPResponse {
+        let active: Vec<&dyn Middleware> = self
+            .entries
+            .iter()
+            .filter(|(scope, _)| scope.applies(req))
+            .map(|(_, middleware)| middleware.as_ref())
+            .collect();
+        let mut ran = 0;
+        let mut response = None;
+        for middleware in &active {
+            ran += 1;
+            if let Some(early) = middleware.before(req, res) {
+                response = Some(early);
+                break;
+            }
+        }
+        let mut response = match response {
+            Some(response) => response,
+            None => handler(req),
+        };
+        for middleware in active[..ran].iter().rev() {
+            response = middleware.after(req, res, response);
+        }
+        response
+    }
+}
+
+impl Default for MiddlewareChain {
+    fn default() -> Self {
+        Self {
+            entries: Vec::new(),
+        }
+    }
+}
+
+impl New for MiddlewareChain {}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+
+    use super::*;
+    use crate::http::response::StatusCode;
+
+    type Log = Arc<Mutex<Vec<String>>>;
+
+    struct Tag {
+        name: &'static str,
+        stop: bool,
+        log: Log,
+    }
+
+    impl Middleware for Tag {
+        fn before(&self, _: &mut HTTPRequest, _: &Client) -> Option<HTTPResponse> {
+            self.log.lock().unwrap().push(format!("before {}", self.name));
+            self.stop
+                .then(|| HTTPResponse::new().status_code(StatusCode::UNAUTHORIZED).body(b"stop".to_vec()))
+        }
+        fn after(&self, _: &HTTPRequest, _: &Client, mut response: HTTPResponse) -> HTTPResponse {
+            self.log.lock().unwrap().push(format!("after {}", self.name));
+            response.body.to_mut().extend_from_slice(format!(" {}", self.name).as_bytes());
+            response
+        }
+    }
+
+    fn tag(log: &Log, name: &'static str) -> Tag {
+        Tag { name, stop: false, log: log.clone() }
+    }
+
+    fn stop(log: &Log, name: &'static str) -> Tag {
+        Tag { name, stop: true, log: log.clone() }
+    }
+
+    // The log of one request and the response body
+    fn run(chain: &MiddlewareChain, log: &Log, host: &str, path: &str) -> (Vec<String>, String) {
+        log.lock().unwrap().clear();
+        let mut req = HTTPRequest::new();
+        req.parts.host = Some(Host::parse(host).unwrap());
+        req.parts.uri.path = path.to_string();
+        let handled = log.clone();
+        let response = chain.run(&mut req, &Client::new(), |_| {
+            handled.lock().unwrap().push("handler".to_string());
+            HTTPResponse::new().body(b"body".to_vec())
+        });
+        let body = String::from_utf8(response.body.into_vec()).unwrap();
+        (log.lock().unwrap().clone(), body)
+    }
+
+    #[test]
+    fn order() {
+        let log = Log::default();
+        let chain = MiddlewareChain::new()
+            .prefix("/api/v1/", tag(&log, "v1"))
+            .prefix("/api", tag(&log, "api"))
+            .host("Example.com", tag(&log, "host"))
+            .unwrap()
+            .global(tag(&log, "global"))
+            .prefix("/api", tag(&log, "api2"))
+            .global(tag(&log, "global2"));
+        assert!(MiddlewareChain::new().host("a b", tag(&log, "")).is_err());
+        let (events, body) = run(&chain, &log, "example.com", "/api/v1/users");
+        assert_eq!(
+            events,
+            [
+                "before global", "before global2", "before host", "before api", "before api2", "before v1",
+                "handler",
+                "after v1", "after api2", "after api", "after host", "after global2", "after global",
+            ]
+        );
+        assert_eq!(body, "body v1 api2 api host global2 global");
+        let (_, body) = run(&chain, &log, "other.com", "/api");
+        assert_eq!(body, "body api2 api global2 global");
+    }
+
+    #[test]
+    fn prefix_boundary() {
+        let log = Log::default();
+        let chain = MiddlewareChain::new().prefix("/api", tag(&log, "api"));
+        assert_eq!(run(&chain, &log, "x", "/api").1, "body api");
+        assert_eq!(run(&chain, &log, "x", "/api/").1, "body api");
+        assert_eq!(run(&chain, &log, "x", "/api/x").1, "body api");
+        assert_eq!(run(&chain, &log, "x", "/apis").1, "body");
+        assert_eq!(run(&chain, &log, "x", "/ap").1, "body");
+        let chain = MiddlewareChain::new().prefix("/", tag(&log, "root"));
+        assert_eq!(run(&chain, &log, "x", "/").1, "body root");
+        assert_eq!(run(&chain, &log, "x", "/a").1, "body root");
+    }
+
+    #[test]
+    fn short_circuit() {
+        let log = Log::default();
+        let chain = MiddlewareChain::new()
+            .global(tag(&log, "first"))
+            .global(stop(&log, "auth"))
+            .global(tag(&log, "last"));
+        let (events, body) = run(&chain, &log, "x", "/");
+        // Neither the handler nor `last` ran, `after` of `auth` sees its own response
+        assert_eq!(events, ["before first", "before auth", "after auth", "after first"]);
+        assert_eq!(body, "stop auth first");
+    }
+}
@@ -0,0 +1,786 @@
+use std::{
+    error::Error,
+    fmt::Display,
+    fs::{self, File, OpenOptions},
+    io::{self, Read, Seek, SeekFrom, Write},
+    path::{Path, PathBuf},
+    sync::atomic::{AtomicU64, Ordering},
+};
+
+use crate::{
+    http::{
+        form::percent_decode,
+        request::HTTPRequest,
+        utils::{AllowedExtentions, HTTPHeader},
+    },
+    traits::New,
+};
+
+pub struct MultipartLimits {
+    pub max_part_size: u64,
+    pub max_total_size: u64,
+    pub max_parts: usize,
+    pub max_header_size: usize,
+    /// File parts bigger than this are moved to a temporary file
+    pub spill_threshold: usize,
+    pub temp_dir: Option<PathBuf>,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub struct MultipartError(u8);
+
+/// Every part of a `multipart/form-data` body.
+/// Temporary files are removed once this is dropped
+pub struct Multipart {
+    parts: Vec<Part>,
+}
+
+pub struct Part {
+    pub name: String,
+    pub filename: Option<String>,
+    pub content_type: Option<String>,
+    pub headers: Vec<(String, String)>,
+    pub data: PartData,
+}
+
+pub enum PartData {
+    Memory(Vec<u8>),
+    File(TempFile),
+}
+
+pub struct TempFile {
+    path: PathBuf,
+    file: File,
+    len: u64,
+    keep: bool,
+}
+
+/// Push parser, the body can be fed in chunks as it arrives
+pub struct MultipartParser<'a> {
+    delimiter: Vec<u8>,
+    limits: &'a MultipartLimits,
+    state: State,
+    buf: Vec<u8>,
+    total: u64,
+    count: usize,
+    current: Option<Part>,
+    part_len: u64,
+    done: Vec<Part>,
+}
+
+#[derive(PartialEq, Eq)]
+enum State {
+    Preamble,
+    AfterDelimiter,
+    Headers,
+    Body,
+    End,
+}
+
+impl MultipartError {
+    pub const NOBOUNDARY: MultipartError = MultipartError(1);
+    pub const MALFORMED: MultipartError = MultipartError(2);
+    pub const PARTTOOLARGE: MultipartError = MultipartError(3);
+    pub const TOOLARGE: MultipartError = MultipartError(4);
+    pub const TOOMANYPARTS: MultipartError = MultipartError(5);
+    pub const HEADERTOOLARGE: MultipartError = MultipartError(6);
+    pub const INCOMPLETE: MultipartError = MultipartError(7);
+    pub const IOERR: MultipartError = MultipartError(8);
+    pub fn reason(&self) -> &str {
+        match self.0 {
+            1 => "Missing multipart Boundary",
+            2 => "Malformed multipart Body",
+            3 => "Multipart Part too Large",
+            4 => "Multipart Body too Large",
+            5 => "Too many multipart Parts",
+            6 => "Multipart Part Header too Large",
+            7 => "Multipart Body ended early",
+            8 => "Could not write temporary File",
+            _ => "Error not defined",
+        }
+    }
+}
+
+impl Display for MultipartError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.reason())
+    }
+}
+
+impl Error for MultipartError {}
+
+impl From<io::Error> for MultipartError {
+    fn from(_: io::Error) -> Self {
+        MultipartError::IOERR
+    }
+}
+
+impl Default for MultipartLimits {
+    fn default() -> Self {
+        Self {
+            max_part_size: 16 * 1024 * 1024,
+            max_total_size: 64 * 1024 * 1024,
+            max_parts: 64,
+            max_header_size: 8 * 1024,
+            spill_threshold: 256 * 1024,
+            temp_dir: None,
+        }
+    }
+}
+
+impl New for MultipartLimits {}
+
+/// Reads `boundary` out of a `multipart/form-data` Content-Type value
+pub fn boundary(content_type: &str) -> Option<String> {
+    let mut params = content_type.split(';');
+    if !params
+        .next()?
+        .trim()
+        .eq_ignore_ascii_case("multipart/form-data")
+    {
+        return None;
+    }
+    params.find_map(|param| {
+        let (key, value) = param.split_once('=')?;
+        if !key.trim().eq_ignore_ascii_case("boundary") {
+            return None;
+        }
+        let value = value.trim();
+        let value = value
+            .strip_prefix('"')
+            .and_then(|v| v.strip_suffix('"'))
+            .unwrap_or(value);
+        (!value.is_empty() && value.len() <= 70).then(|| value.to_string())
+    })
+}
+
+/// `name="a"; filename="b"` of a Content-Disposition value,
+/// quoted values may contain `;` and `\"`
+fn disposition_param(value: &str, key: &str) -> Option<String> {
+    let mut rest = value.split_once(';')?.1;
+    while let Some((k, v)) = rest.split_once('=') {
+        let v = v.trim_start();
+        let (value, next) = match v.strip_prefix('"') {
+            Some(quoted) => {
+                let mut value = String::new();
+                let mut chars = quoted.char_indices().peekable();
+                let end = loop {
+                    match chars.next()? {
+                        (_, '\\') if chars.peek().is_some_and(|(_, c)| *c == '"') => {
+                            value.push('"');
+                            chars.next();
+                        }
+                        (i, '"') => break i + 1,
+                        (_, c) => value.push(c),
+                    }
+                };
+                let next = quoted[end..].split_once(';').map_or("", |(_, next)| next);
+                (value, next)
+            }
+            None => match v.split_once(';') {
+                Some((value, next)) => (value.trim().to_string(), next),
+                None => (v.trim().to_string(), ""),
+            },
+        };
+        if k.trim().eq_ignore_ascii_case(key) {
+            return Some(value);
+        }
+        rest = next;
+    }
+    None
+}
+
+/// `UTF-8''%C3%A4.txt` of a `filename*` parameter (RFC 8187)
+fn ext_value(src: &str) -> Option<String> {
+    let (charset, rest) = src.split_once('\'')?;
+    let (_, value) = rest.split_once('\'')?;
+    let bytes = percent_decode(value.as_bytes(), false);
+    if charset.eq_ignore_ascii_case("UTF-8") {
+        String::from_utf8(bytes).ok()
+    } else if charset.eq_ignore_ascii_case("ISO-8859-1") {
+        Some(bytes.into_iter().map(char::from).collect())
+    } else {
+        None
+    }
+}
+
+fn find(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    haystack.windows(needle.len()).position(|w| w == needle)
+}
+
+impl TempFile {
+    fn create(dir: &Path) -> io::Result<TempFile> {
+        static COUNTER: AtomicU64 = AtomicU64::new(0);
+        loop {
+            let path = dir.join(format!(
+                "fiv-upload-{}-{}",
+                std::process::id(),
+                COUNTER.fetch_add(1, Ordering::Relaxed)
+            ));
+            match OpenOptions::new()
+                .read(true)
+                .write(true)
+                .create_new(true)
+                .open(&path)
+            {
+                Ok(file) => {
+                    return Ok(TempFile {
+                        path,
+                        file,
+                        len: 0,
+                        keep: false,
+                    });
+                }
+                Err(e) if e.kind() == io::ErrorKind::AlreadyExists => continue,
+                Err(e) => return Err(e),
+            }
+        }
+    }
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+    pub fn len(&self) -> u64 {
+        self.len
+    }
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+    /// Opens the written data from the start
+    pub fn reopen(&self) -> io::Result<File> {
+        let mut file = self.file.try_clone()?;
+        file.seek(SeekFrom::Start(0))?;
+        Ok(file)
+    }
+    /// Moves the file to `to`, it is not removed afterwards
+    pub fn persist(mut self, to: &Path) -> io::Result<()> {
+        self.file.flush()?;
+        fs::rename(&self.path, to).or_else(|_| {
+            fs::copy(&self.path, to)?;
+            fs::remove_file(&self.path)
+        })?;
+        self.keep = true;
+        Ok(())
+    }
+}
+
+impl Drop for TempFile {
+    fn drop(&mut self) {
+        if !self.keep {
+            let _ = fs::remove_file(&self.path);
+        }
+    }
+}
+
+impl PartData {
+    pub fn len(&self) -> u64 {
+        match self {
+            PartData::Memory(v) => v.len() as u64,
+            PartData::File(f) => f.len(),
+        }
+    }
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+    /// Loads the whole part into memory
+    pub fn bytes(&self) -> io::Result<Vec<u8>> {
+        match self {
+            PartData::Memory(v) => Ok(v.clone()),
+            PartData::File(f) => {
+                let mut v = Vec::with_capacity(f.len() as usize);
+                f.reopen()?.read_to_end(&mut v)?;
+                Ok(v)
+            }
+        }
+    }
+}
+
+impl Part {
+    pub fn is_file(&self) -> bool {
+        self.filename.is_some()
+    }
+    /// The part as text, only for parts held in memory
+    pub fn text(&self) -> Option<&str> {
+        match &self.data {
+            PartData::Memory(v) => std::str::from_utf8(v).ok(),
+            PartData::File(_) => None,
+        }
+    }
+}
+
+impl<'a> MultipartParser<'a> {
+    pub fn new(boundary: &str, limits: &'a MultipartLimits) -> MultipartParser<'a> {
+        MultipartParser {
+            delimiter: format!("\r\n--{}", boundary).into_bytes(),
+            limits,
+            state: State::Preamble,
+            // The first delimiter may start the body without a CRLF
+            buf: b"\r\n".to_vec(),
+            total: 0,
+            count: 0,
+            current: None,
+            part_len: 0,
+            done: Vec::new(),
+        }
+    }
+
+    pub fn feed(&mut self, chunk: &[u8]) -> Result<(), MultipartError> {
+        self.total += chunk.len() as u64;
+        if self.total > self.limits.max_total_size {
+            return Err(MultipartError::TOOLARGE);
+        }
+        if self.state == State::End {
+            return Ok(());
+        }
+        self.buf.extend_from_slice(chunk);
+        while self.step()? {}
+        Ok(())
+    }
+
+    /// Parts which are complete, in order
+    pub fn drain(&mut self) -> std::vec::Drain<'_, Part> {
+        self.done.drain(..)
+    }
+
+    pub fn finish(mut self) -> Result<Vec<Part>, MultipartError> {
+        if self.state != State::End {
+            return Err(MultipartError::INCOMPLETE);
+        }
+        Ok(std::mem::take(&mut self.done))
+    }
+
+    // Returns true if there may be more to do with the current buffer
+    fn step(&mut self) -> Result<bool, MultipartError> {
+        match self.state {
+            State::Preamble => match find(&self.buf, &self.delimiter) {
+                Some(i) => {
+                    self.buf.drain(..i + self.delimiter.len());
+                    self.state = State::AfterDelimiter;
+                    Ok(true)
+                }
+                None => {
+                    let keep = self.delimiter.len() - 1;
+                    if self.buf.len() > keep {
+                        self.buf.drain(..self.buf.len() - keep);
+                    }
+                    Ok(false)
+                }
+            },
+            State::AfterDelimiter => {
+                if self.buf.len() < 2 {
+                    return Ok(false);
+                }
+                if self.buf.starts_with(b"--") {
+                    self.buf.clear();
+                    self.state = State::End;
+                    return Ok(false);
+                }
+                // Transport padding (LWSP) is allowed before the CRLF
+                let pos = match find(&self.buf, b"\r\n") {
+                    Some(pos) => pos,
+                    None if self.buf.len() > self.limits.max_header_size => {
+                        return Err(MultipartError::MALFORMED);
+                    }
+                    None => return Ok(false),
+                };
+                if !self.buf[..pos].iter().all(|b| *b == b' ' || *b == b'\t') {
+                    return Err(MultipartError::MALFORMED);
+                }
+                self.buf.drain(..pos + 2);
+                self.state = State::Headers;
+                Ok(true)
+            }
+            State::Headers => {
+                let end = if self.buf.starts_with(b"\r\n") {
+                    0
+                } else {
+                    match find(&self.buf, b"\r\n\r\n") {
+                        Some(end) => end + 2,
+                        None if self.buf.len() > self.limits.max_header_size => {
+                            return Err(MultipartError::HEADERTOOLARGE);
+                        }
+                        None => return Ok(false),
+                    }
+                };
+                if end > self.limits.max_header_size {
+                    return Err(MultipartError::HEADERTOOLARGE);
+                }
+                let head = String::from_utf8_lossy(&self.buf[..end]).into_owned();
+                self.buf.drain(..end + 2);
+                self.start_part(&head)?;
+                self.state = State::Body;
+                Ok(true)
+            }
+            State::Body => match find(&self.buf, &self.delimiter) {
+                Some(i) => {
+                    let data: Vec<u8> = self.buf.drain(..i + self.delimiter.len()).collect();
+                    self.write(&data[..i])?;
+                    let part = self.current.take().unwrap();
+                    self.done.push(part);
+                    self.state = State::AfterDelimiter;
+                    Ok(true)
+                }
+                None => {
+                    let keep = self.delimiter.len() - 1;
+                    if self.buf.len() > keep {
+                        let data: Vec<u8> = self.buf.drain(..self.buf.len() - keep).collect();
+                        self.write(&data)?;
+                    }
+                    Ok(false)
+                }
+            },
+            State::End => Ok(false),
+        }
+    }
+
+    fn start_part(&mut self, head: &str) -> Result<(), MultipartError> {
+        self.count += 1;
+        if self.count > self.limits.max_parts {
+            return Err(MultipartError::TOOMANYPARTS);
+        }
+        let headers = head
+            .split("\r\n")
+            .filter(|line| !line.is_empty())
+            .map(|line| {
+                let (key, value) = line.split_once(':').ok_or(MultipartError::MALFORMED)?;
+                Ok((key.trim().to_string(), value.trim().to_string()))
+            })
+            .collect::<Result<Vec<(String, String)>, MultipartError>>()?;
+        let header = |name: &str| {
+            headers
+                .iter()
+                .find(|(k, _)| k.eq_ignore_ascii_case(name))
+                .map(|(_, v)| v.as_str())
+        };
+        let disposition = header("Content-Disposition").ok_or(MultipartError::MALFORMED)?;
+        if !disposition
+            .split(';')
+            .next()
+            .unwrap()
+            .trim()
+            .eq_ignore_ascii_case("form-data")
+        {
+            return Err(MultipartError::MALFORMED);
+        }
+        self.current = Some(Part {
+            name: disposition_param(disposition, "name").ok_or(MultipartError::MALFORMED)?,
+            // `filename*` wins if both are sent
+            filename: disposition_param(disposition, "filename*")
+                .and_then(|v| ext_value(&v))
+                .or_else(|| disposition_param(disposition, "filename")),
+            content_type: header("Content-Type").map(|v| v.to_string()),
+            data: PartData::Memory(Vec::new()),
+            headers,
+        });
+        self.part_len = 0;
+        Ok(())
+    }
+
+    fn write(&mut self, data: &[u8]) -> Result<(), MultipartError> {
+        if data.is_empty() {
+            return Ok(());
+        }
+        self.part_len += data.len() as u64;
+        if self.part_len > self.limits.max_part_size {
+            return Err(MultipartError::PARTTOOLARGE);
+        }
+        let limits = self.limits;
+        let part = self.current.as_mut().unwrap();
+        if let PartData::Memory(v) = &mut part.data {
+            if part.filename.is_some() && v.len() + data.len() > limits.spill_threshold {
+                let dir = match &limits.temp_dir {
+                    Some(dir) => dir.clone(),
+                    None => std::env::temp_dir(),
+                };
+                let mut file = TempFile::create(&dir)?;
+                file.file.write_all(v)?;
+                file.len = v.len() as u64;
+                part.data = PartData::File(file);
+            }
+        }
+        match &mut part.data {
+            PartData::Memory(v) => v.extend_from_slice(data),
+            PartData::File(f) => {
+                f.file.write_all(data)?;
+                f.len += data.len() as u64;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Multipart {
+    pub fn parse(body: &[u8], boundary: &str, limits: &MultipartLimits) -> Result<Multipart, MultipartError> {
+        let mut parser = MultipartParser::new(boundary, limits);
+        parser.feed(body)?;
+        Ok(Multipart {
+            parts: parser.finish()?,
+        })
+    }
+
+    pub fn from_request(req: &HTTPRequest, limits: &MultipartLimits) -> Result<Multipart, MultipartError> {
+        let boundary = req
+            .parts
+            .headcont
+            .iter()
+            .find(|header: &&HTTPHeader| header.key == AllowedExtentions::ContentType)
+            .and_then(|header| boundary(&header.value))
+            .ok_or(MultipartError::NOBOUNDARY)?;
+        Multipart::parse(&req.body, &boundary, limits)
+    }
+
+    pub fn get(&self, name: &str) -> Option<&Part> {
+        self.parts.iter().find(|part| part.name == name)
+    }
+
+    pub fn get_all<'a>(&'a self, name: &'a str) -> impl Iterator<Item = &'a Part> {
+        self.parts.iter().filter(move |part| part.name == name)
+    }
+
+    pub fn iter(&self) -> std::slice::Iter<'_, Part> {
+        self.parts.iter()
+    }
+
+    pub fn into_parts(self) -> Vec<Part> {
+        self.parts
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const BODY: &[u8] = b"preamble\r\n--XyZ\r\n\
+        Content-Disposition: form-data; name=\"title\"\r\n\r\n\
+        hello\r\n--Xy\r\n--XyZ \t\r\n\
+        Content-Disposition: form-data; name=\"file\"; filename=\"a.csv\"\r\n\
+        Content-Type: text/csv\r\n\r\n\
+        1,2,3\r\n4,5,6\r\n--XyZ--\r\nepilogue";
+
+    fn parse(body: &[u8], limits: &MultipartLimits) -> Result<Vec<Part>, MultipartError> {
+        Multipart::parse(body, "XyZ", limits).map(Multipart::into_parts)
+    }
+
+    fn part(disposition: &str) -> Result<Part, MultipartError> {
+        let body = format!(
+            "--XyZ\r\nContent-Disposition: {}\r\n\r\nx\r\n--XyZ--",
+            disposition
+        );
+        parse(body.as_bytes(), &MultipartLimits::new()).map(|mut parts| parts.remove(0))
+    }
+
+    // A directory of its own, so leftover files can be counted
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir =
+            std::env::temp_dir().join(format!("fiv-multipart-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir(&dir).unwrap();
+        dir
+    }
+
+    fn files(dir: &Path) -> usize {
+        fs::read_dir(dir).unwrap().count()
+    }
+
+    #[test]
+    fn split_chunks() {
+        let limits = MultipartLimits::new();
+        for at in 0..=BODY.len() {
+            let mut parser = MultipartParser::new("XyZ", &limits);
+            parser.feed(&BODY[..at]).unwrap();
+            parser.feed(&BODY[at..]).unwrap();
+            let parts = parser.finish().unwrap();
+            assert_eq!(parts.len(), 2, "split at {}", at);
+            assert_eq!(parts[0].text(), Some("hello\r\n--Xy"));
+            assert_eq!(parts[1].text(), Some("1,2,3\r\n4,5,6"));
+        }
+        let mut parser = MultipartParser::new("XyZ", &limits);
+        for byte in BODY.chunks(1) {
+            parser.feed(byte).unwrap();
+        }
+        assert_eq!(parser.finish().unwrap().len(), 2);
+    }
+
+    #[test]
+    fn drain() {
+        let limits = MultipartLimits::new();
+        let mut parser = MultipartParser::new("XyZ", &limits);
+        let first = BODY.windows(5).position(|w| w == b"--XyZ").unwrap() + 5;
+        let second = first
+            + BODY[first..]
+                .windows(5)
+                .position(|w| w == b"--XyZ")
+                .unwrap();
+        parser.feed(&BODY[..second + 5]).unwrap();
+        assert_eq!(
+            parser.drain().map(|part| part.name).collect::<Vec<_>>(),
+            ["title"]
+        );
+        parser.feed(&BODY[second + 5..]).unwrap();
+        assert_eq!(parser.finish().unwrap()[0].name, "file");
+    }
+
+    #[test]
+    fn spill() {
+        let dir = temp_dir("spill");
+        let limits = MultipartLimits {
+            spill_threshold: 5,
+            temp_dir: Some(dir.clone()),
+            ..MultipartLimits::new()
+        };
+        let parts = parse(BODY, &limits).unwrap();
+        // Only file parts are spilled
+        assert!(matches!(parts[0].data, PartData::Memory(_)));
+        let PartData::File(file) = &parts[1].data else {
+            panic!("not spilled");
+        };
+        assert_eq!(file.path().parent(), Some(dir.as_path()));
+        assert_eq!(file.len(), 12);
+        assert_eq!(parts[1].text(), None);
+        assert_eq!(parts[1].data.bytes().unwrap(), b"1,2,3\r\n4,5,6");
+        assert_eq!(files(&dir), 1);
+        drop(parts);
+        assert_eq!(files(&dir), 0);
+
+        // Below the threshold nothing touches the disk
+        let limits = MultipartLimits {
+            spill_threshold: 12,
+            temp_dir: Some(dir.clone()),
+            ..MultipartLimits::new()
+        };
+        let parts = parse(BODY, &limits).unwrap();
+        assert!(matches!(parts[1].data, PartData::Memory(_)));
+        assert_eq!(files(&dir), 0);
+        fs::remove_dir(&dir).unwrap();
+    }
+
+    #[test]
+    fn persist() {
+        let dir = temp_dir("persist");
+        let limits = MultipartLimits {
+            spill_threshold: 0,
+            temp_dir: Some(dir.clone()),
+            ..MultipartLimits::new()
+        };
+        let mut parts = parse(BODY, &limits).unwrap();
+        let PartData::File(file) = parts.remove(1).data else {
+            panic!("not spilled");
+        };
+        let to = dir.join("kept.csv");
+        file.persist(&to).unwrap();
+        assert_eq!(fs::read(&to).unwrap(), b"1,2,3\r\n4,5,6");
+        assert_eq!(files(&dir), 1);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn disposition() {
+        let p = part("form-data; name=\"a\"; filename=\"x;y \\\"z\\\".txt\"").unwrap();
+        assert_eq!(p.name, "a");
+        assert_eq!(p.filename.as_deref(), Some("x;y \"z\".txt"));
+        assert!(p.is_file());
+
+        let p = part("Form-Data; NAME=plain ; filename=\"C:\\dir\\a.txt\"").unwrap();
+        assert_eq!(p.name, "plain");
+        assert_eq!(p.filename.as_deref(), Some("C:\\dir\\a.txt"));
+
+        let p = part("form-data; name=\"f\"; filename=\"a.txt\"; filename*=UTF-8''%C3%A4%20b.txt")
+            .unwrap();
+        assert_eq!(p.filename.as_deref(), Some("ä b.txt"));
+        let p = part("form-data; filename*=iso-8859-1'de'%E4.txt; name=f").unwrap();
+        assert_eq!(p.filename.as_deref(), Some("ä.txt"));
+        // An unknown charset falls back to `filename`
+        let p = part("form-data; name=f; filename*=koi8-r''%C1; filename=b").unwrap();
+        assert_eq!(p.filename.as_deref(), Some("b"));
+
+        let p = part("form-data; name=\"text\"").unwrap();
+        assert!(!p.is_file());
+        for disposition in [
+            "attachment; name=\"a\"",
+            "form-data; filename=\"a\"",
+            "form-data; name=\"unterminated",
+        ] {
+            assert_eq!(part(disposition).err(), Some(MultipartError::MALFORMED));
+        }
+    }
+
+    #[test]
+    fn boundaries() {
+        let limits = MultipartLimits::new();
+        let ok = b"--XyZ\r\nContent-Disposition: form-data; name=e\r\n\r\n\r\n--XyZ--";
+        assert_eq!(parse(ok, &limits).unwrap()[0].text(), Some(""));
+        // Missing delimiters or final boundary
+        assert_eq!(
+            parse(b"no delimiter", &limits).err(),
+            Some(MultipartError::INCOMPLETE)
+        );
+        assert_eq!(
+            parse(&ok[..ok.len() - 2], &limits).err(),
+            Some(MultipartError::INCOMPLETE)
+        );
+        assert_eq!(
+            parse(&ok[..ok.len() - 8], &limits).err(),
+            Some(MultipartError::INCOMPLETE)
+        );
+        assert_eq!(
+            parse(b"--XyZ\r\n--XyZ--", &limits).err(),
+            Some(MultipartError::INCOMPLETE)
+        );
+        // Only transport padding may follow a delimiter
+        for body in [&b"--XyZ x\r\n"[..], b"--XyZZ\r\n--XyZ--"] {
+            assert_eq!(parse(body, &limits).err(), Some(MultipartError::MALFORMED));
+        }
+        // Everything after the final boundary is ignored
+        let mut parser = MultipartParser::new("XyZ", &limits);
+        parser.feed(b"--XyZ--").unwrap();
+        parser.feed(b"\r\n--XyZ\r\ngarbage").unwrap();
+        assert!(parser.finish().unwrap().is_empty());
+
+        assert_eq!(
+            boundary("multipart/form-data; boundary=XyZ").as_deref(),
+            Some("XyZ")
+        );
+        assert_eq!(
+            boundary("Multipart/Form-Data;charset=utf-8; Boundary=\"a b\"").as_deref(),
+            Some("a b")
+        );
+        assert_eq!(boundary("multipart/mixed; boundary=XyZ"), None);
+        assert_eq!(boundary("multipart/form-data; boundary=\"\""), None);
+        assert_eq!(
+            boundary(&format!("multipart/form-data; boundary={}", "x".repeat(71))),
+            None
+        );
+    }
+
+    #[test]
+    fn limits() {
+        fn with(f: impl FnOnce(&mut MultipartLimits)) -> MultipartLimits {
+            let mut limits = MultipartLimits::new();
+            f(&mut limits);
+            limits
+        }
+        assert!(parse(BODY, &with(|l| l.max_part_size = 12)).is_ok());
+        assert_eq!(
+            parse(BODY, &with(|l| l.max_part_size = 11)).err(),
+            Some(MultipartError::PARTTOOLARGE)
+        );
+        let len = BODY.len() as u64;
+        assert!(parse(BODY, &with(|l| l.max_total_size = len)).is_ok());
+        assert_eq!(
+            parse(BODY, &with(|l| l.max_total_size = len - 1)).err(),
+            Some(MultipartError::TOOLARGE)
+        );
+        assert_eq!(
+            parse(BODY, &with(|l| l.max_parts = 1)).err(),
+            Some(MultipartError::TOOMANYPARTS)
+        );
+        assert_eq!(
+            parse(BODY, &with(|l| l.max_header_size = 40)).err(),
+            Some(MultipartError::HEADERTOOLARGE)
+        );
+        // The total counts every chunk, even after the final boundary
+        let limits = with(|l| l.max_total_size = len);
+        let mut parser = MultipartParser::new("XyZ", &limits);
+        parser.feed(BODY).unwrap();
+        assert_eq!(parser.feed(b"!").err(), Some(MultipartError::TOOLARGE));
+    }
+}
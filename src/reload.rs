@@ -0,0 +1,301 @@
+use std::{
+    collections::hash_map::DefaultHasher,
+    fs,
+    hash::{Hash, Hasher},
+    io,
+    path::{Path, PathBuf},
+    sync::Arc,
+    thread::{self, JoinHandle},
+    time::Duration,
+};
+
+use crate::{
+    http::{
+        config::{Config, ConfigError, Handlers},
+        router::Routes,
+        server::Client,
+    },
+    log::{log, ERROR},
+};
+
+/// Rebuilds the routes of a `Client` whenever its config file, a static site
+/// or a mounted root changes. An invalid config keeps the previous routes
+pub struct Reloader {
+    config: PathBuf,
+    handlers: Handlers,
+    debounce: Duration,
+    poll: Duration,
+}
+
+impl Reloader {
+    pub fn watching(config: impl Into<PathBuf>, handlers: Handlers) -> Reloader {
+        Reloader {
+            config: config.into(),
+            handlers,
+            debounce: Duration::from_millis(200),
+            poll: Duration::from_secs(1),
+        }
+    }
+    /// Waited after a change so editors can finish writing, 200ms by default
+    pub fn debounce(mut self, debounce: Duration) -> Reloader {
+        self.debounce = debounce;
+        self
+    }
+    /// Interval of the mtime scan where inotify is not available, 1s by default
+    pub fn poll(mut self, poll: Duration) -> Reloader {
+        self.poll = poll;
+        self
+    }
+
+    /// Parses the config and swaps the routes and error pages of `client`, cached
+    /// files and templates are dropped so changed files are read again. Returns
+    /// the watched paths
+    pub fn reload(&self, client: &Client) -> Result<Vec<PathBuf>, ConfigError> {
+        let config = Config::load(&self.config, &self.handlers)?;
+        client.routes.swap(Routes {
+            router: config.routes,
+            errors: config.errors,
+        });
+        client.files.clear();
+        client.templates.clear();
+        let mut watch = config.watch;
+        watch.push(self.config.clone());
+        Ok(watch)
+    }
+
+    /// Loads the config once right away and then on every change. Errors are
+    /// logged, the server keeps running on the previous routes
+    pub fn spawn(self, client: Arc<Client>) -> io::Result<JoinHandle<()>> {
+        thread::Builder::new()
+            .name("config-reload".to_string())
+            .spawn(move || {
+                let mut watch = vec![self.config.clone()];
+                loop {
+                    // Taken before loading, so a change during the reload is not missed
+                    let seen = fingerprint(&watch);
+                    match self.reload(&client) {
+                        Ok(paths) => watch = paths,
+                        Err(e) => log(ERROR, format!("Config reload failed, keeping the previous routes: {}", e)),
+                    }
+                    if let Err(e) = self.wait(&watch, seen) {
+                        log(ERROR, format!("Watching the config failed, falling back to polling: {}", e));
+                        self.poll_until(&watch, seen);
+                    }
+                    thread::sleep(self.debounce);
+                }
+            })
+    }
+
+    #[cfg(target_os = "linux")]
+    fn wait(&self, watch: &[PathBuf], seen: u64) -> io::Result<()> {
+        inotify::wait(watch, seen)
+    }
+
+    #[cfg(not(target_os = "linux"))]
+    fn wait(&self, watch: &[PathBuf], seen: u64) -> io::Result<()> {
+        self.poll_until(watch, seen);
+        Ok(())
+    }
+
+    fn poll_until(&self, watch: &[PathBuf], seen: u64) {
+        while fingerprint(watch) == seen {
+            thread::sleep(self.poll);
+        }
+    }
+}
+
+/// Directories are walked, symlinked directories are not followed
+fn fingerprint(watch: &[PathBuf]) -> u64 {
+    fn visit(path: &Path, hasher: &mut DefaultHasher) {
+        path.hash(hasher);
+        match fs::metadata(path) {
+            Ok(meta) => {
+                meta.len().hash(hasher);
+                meta.modified().ok().hash(hasher);
+            }
+            Err(_) => 0u8.hash(hasher),
+        }
+        if fs::symlink_metadata(path).is_ok_and(|meta| meta.is_dir()) {
+            if let Ok(read) = fs::read_dir(path) {
+                let mut entries: Vec<PathBuf> = read.filter_map(|entry| Some(entry.ok()?.path())).collect();
+                entries.sort();
+                for entry in entries.iter() {
+                    visit(entry, hasher);
+                }
+            }
+        }
+    }
+    let mut hasher = DefaultHasher::new();
+    for path in watch {
+        visit(path, &mut hasher);
+    }
+    hasher.finish()
+}
+
+#[cfg(target_os = "linux")]
+mod inotify {
+    use std::{
+        ffi::{CString, c_char, c_int},
+        fs::{self, File},
+        io::{self, Read},
+        os::{fd::FromRawFd, unix::ffi::OsStrExt},
+        path::{Path, PathBuf},
+    };
+
+    unsafe extern "C" {
+        fn inotify_init1(flags: c_int) -> c_int;
+        fn inotify_add_watch(fd: c_int, path: *const c_char, mask: u32) -> c_int;
+    }
+
+    const IN_CLOEXEC: c_int = 0o2000000;
+    // Modify, attrib, close_write, moved_from, moved_to, create, delete, delete_self, move_self
+    const MASK: u32 = 0x2 | 0x4 | 0x8 | 0x40 | 0x80 | 0x100 | 0x200 | 0x400 | 0x800;
+
+    fn add(fd: c_int, path: &Path) {
+        if let Ok(path) = CString::new(path.as_os_str().as_bytes()) {
+            // Paths which vanished in between are picked up on the next round
+            unsafe { inotify_add_watch(fd, path.as_ptr(), MASK) };
+        }
+    }
+
+    // Watches are not recursive, every directory gets its own
+    fn add_tree(fd: c_int, dir: &Path) {
+        add(fd, dir);
+        if let Ok(read) = fs::read_dir(dir) {
+            for entry in read.flatten() {
+                if entry.file_type().is_ok_and(|kind| kind.is_dir()) {
+                    add_tree(fd, &entry.path());
+                }
+            }
+        }
+    }
+
+    /// Blocks until the fingerprint of `watch` differs from `seen`
+    pub(super) fn wait(watch: &[PathBuf], seen: u64) -> io::Result<()> {
+        let fd = unsafe { inotify_init1(IN_CLOEXEC) };
+        if fd < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        // Closes the descriptor and with it every watch
+        let mut file = unsafe { File::from_raw_fd(fd) };
+        for path in watch {
+            if fs::symlink_metadata(path).is_ok_and(|meta| meta.is_dir()) {
+                add_tree(fd, path);
+            }
+            // The parent also sees files replaced by a rename
+            if let Some(parent) = path.parent() {
+                add(fd, if parent.as_os_str().is_empty() { Path::new(".") } else { parent });
+            }
+        }
+        let mut buf = [0u8; 4096];
+        // Events of unrelated files next to a watched one do not count
+        while super::fingerprint(watch) == seen {
+            // Only the wakeup matters, the events themselves are not parsed
+            let _ = file.read(&mut buf)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        http::{config::ConfigErrorKind, method::Method, router::Dispatch},
+        traits::New,
+    };
+
+    fn base(name: &str) -> PathBuf {
+        let base = std::env::temp_dir().join(format!("reload-{}-{}", std::process::id(), name));
+        let _ = fs::remove_dir_all(&base);
+        fs::create_dir_all(&base).unwrap();
+        base
+    }
+
+    fn site(path: &str) -> String {
+        format!("[[site]]\npath = \"{}\"\nrender = \"page\"\n", path)
+    }
+
+    fn found(client: &Client, path: &str) -> bool {
+        matches!(client.routes.load().dispatch(&Method::GET, path), Dispatch::Found(..))
+    }
+
+    #[test]
+    fn invalid_config_keeps_routes() {
+        let base = base("invalid");
+        let config = base.join("site.toml");
+        let reloader = Reloader::watching(&config, Handlers::new().page("page", |_, _| Vec::new()));
+        let client = Client::new();
+        assert_eq!(reloader.reload(&client).unwrap_err().kind(), ConfigErrorKind::Io);
+
+        fs::write(&config, site("/a")).unwrap();
+        assert_eq!(reloader.reload(&client).unwrap(), vec![config.clone()]);
+        assert!(found(&client, "/a"));
+
+        fs::write(&config, site("/a") + &site("/b") + "render = \"page\"\n").unwrap();
+        let e = reloader.reload(&client).unwrap_err();
+        assert_eq!((e.kind(), e.line()), (ConfigErrorKind::DuplicateKey, 7));
+        fs::write(&config, site("/b").replace("page", "missing")).unwrap();
+        assert_eq!(reloader.reload(&client).unwrap_err().kind(), ConfigErrorKind::UnknownHandler);
+        assert!(found(&client, "/a") && !found(&client, "/b"));
+
+        fs::write(&config, site("/b")).unwrap();
+        reloader.reload(&client).unwrap();
+        assert!(!found(&client, "/a") && found(&client, "/b"));
+        fs::remove_dir_all(&base).unwrap();
+    }
+
+    #[test]
+    fn held_snapshot() {
+        let base = base("snapshot");
+        let config = base.join("site.toml");
+        let reloader = Reloader::watching(&config, Handlers::new().page("page", |_, _| Vec::new()));
+        let client = Client::new();
+        fs::write(&config, site("/a")).unwrap();
+        reloader.reload(&client).unwrap();
+        let held = client.routes.load();
+        fs::write(&config, site("/b")).unwrap();
+        reloader.reload(&client).unwrap();
+        assert!(matches!(held.dispatch(&Method::GET, "/a"), Dispatch::Found(..)));
+        assert!(matches!(held.dispatch(&Method::GET, "/b"), Dispatch::NotFound));
+        assert!(found(&client, "/b") && !found(&client, "/a"));
+        // The swap hands back the table which was current
+        let previous = client.routes.swap(crate::http::router::Router::new());
+        assert!(matches!(previous.dispatch(&Method::GET, "/b"), Dispatch::Found(..)));
+        assert!(!found(&client, "/b"));
+        assert!(matches!(held.dispatch(&Method::GET, "/a"), Dispatch::Found(..)));
+        fs::remove_dir_all(&base).unwrap();
+    }
+
+    #[test]
+    fn changes() {
+        let base = base("fingerprint");
+        let file = base.join("page.html");
+        let dir = base.join("public");
+        fs::create_dir_all(dir.join("css")).unwrap();
+        fs::write(&file, "one").unwrap();
+        let watch = vec![file.clone(), dir.clone()];
+        let seen = fingerprint(&watch);
+        assert_eq!(fingerprint(&watch), seen);
+        // Unrelated files next to a watched one are ignored
+        fs::write(base.join("other.txt"), "x").unwrap();
+        assert_eq!(fingerprint(&watch), seen);
+
+        let steps: Vec<Box<dyn Fn()>> = vec![
+            Box::new(|| fs::write(&file, "three").unwrap()),
+            Box::new(|| fs::write(dir.join("css/a.css"), "a").unwrap()),
+            Box::new(|| fs::write(dir.join("css/a.css"), "ab").unwrap()),
+            Box::new(|| fs::rename(dir.join("css/a.css"), dir.join("css/b.css")).unwrap()),
+            Box::new(|| fs::remove_file(dir.join("css/b.css")).unwrap()),
+            Box::new(|| fs::remove_file(&file).unwrap()),
+        ];
+        let mut last = seen;
+        for (i, step) in steps.iter().enumerate() {
+            step();
+            let now = fingerprint(&watch);
+            assert_ne!(now, last, "step {}", i);
+            last = now;
+        }
+        fs::remove_dir_all(&base).unwrap();
+    }
+}
@@ -1,10 +1,14 @@
+use std::sync::Arc;
+
 use crate::{
     ferrors::HttpRequestErr,
     http::{
         account::SessionId,
         method::Method,
+        router::{Methods, Params, Routes},
         server::Client,
-        uri::Uri,
+        site::Endpoint,
+        uri::{normalize_path, Authority, AuthorityRef, Host, PathOptions, Uri, UriRef},
         utils::{
             cookie_parser, get_by_key, AllowedExtentions, HTTPExtentions, HTTPHeader, Version,
         },
@@ -15,10 +19,18 @@ use crate::{
 pub struct HTTPRequest {
     pub parts: RequestHeader,
     pub body: Vec<u8>,
+    /// The table the request was dispatched with, its error pages answer errors of the handler
+    pub routes: Option<Arc<Routes<Methods<Endpoint>>>>,
 }
 
 impl HTTPRequest {
+    /// Paths are normalized with `res.path_options`
     pub fn from_string(&mut self, src: &String, res: &Client) -> Option<HttpRequestErr> {
+        self.from_string_with(src, res, &res.path_options)
+    }
+    /// Routing and file lookups only ever see the normalized path. A repeated or
+    /// invalid Host header, or none on HTTP/1.1, is an error answered with `400`
+    pub fn from_string_with(&mut self, src: &str, res: &Client, opts: &PathOptions) -> Option<HttpRequestErr> {
         match src.len() {
             14.. => {
                 {
@@ -31,8 +43,25 @@ impl HTTPRequest {
                         Some(m) => Method::from_str(m),
                         None => return Some(HttpRequestErr::new()),
                     };
-                    match header.next() {
-                        Some(u) => self.parts.uri.from_string(u.to_string()),
+                    self.parts.uri = match header.next() {
+                        // authority-form is only used by CONNECT
+                        Some(u) if self.parts.method == Method::CONNECT => match Authority::parse(u) {
+                            Ok(authority) => {
+                                let mut uri = Uri::new();
+                                uri.authority = Some(authority);
+                                uri
+                            }
+                            Err(_) => return Some(HttpRequestErr::new()),
+                        },
+                        // Borrows the target until the path is normalized, only then the
+                        // components are copied
+                        Some(u) => match UriRef::parse(u) {
+                            Ok(uri) => match normalize_path(uri.path(), opts) {
+                                Ok(path) => uri.into_owned_with_path(path),
+                                Err(_) => return Some(HttpRequestErr::new()),
+                            },
+                            Err(_) => return Some(HttpRequestErr::new()),
+                        },
                         None => return Some(HttpRequestErr::new()),
                     };
                     self.parts.version = match header.next() {
@@ -55,6 +84,19 @@ impl HTTPRequest {
                         })
                         .collect::<HTTPExtentions>();
                 }
+                // RFC 9112 3.2, one valid Host header and HTTP/1.1 requires it
+                let mut hosts = self.parts.headcont.iter().filter(|header| header.key == AllowedExtentions::Host);
+                let host = match (hosts.next(), hosts.next()) {
+                    // Only the host is kept, userinfo and port are not copied
+                    (Some(header), None) => match AuthorityRef::parse(&header.value) {
+                        Ok(authority) => Some(Host::parse(authority.host()).unwrap_or_default()),
+                        Err(_) => return Some(HttpRequestErr::new()),
+                    },
+                    (None, _) if self.parts.version != Version::HTTP1_1 => None,
+                    _ => return Some(HttpRequestErr::new()),
+                };
+                // The host of an absolute-form target wins over the Host header
+                self.parts.host = self.parts.uri.host().cloned().or(host);
                 for header in self.parts.headcont.iter() {
                     if header.key == AllowedExtentions::Cookie {
                         let r = get_by_key(cookie_parser(header.value.clone()), "sessionId");
@@ -75,6 +117,7 @@ impl Default for HTTPRequest {
         Self {
             parts: RequestHeader::new(),
             body: Vec::new(),
+            routes: None,
         }
     }
 }
@@ -84,9 +127,11 @@ impl New for HTTPRequest {}
 pub struct RequestHeader {
     pub method: Method,           //Which Method
     pub uri: Uri,                 //URI
+    pub host: Option<Host>,       //Target or Host Header
     pub version: Version,         //Version
     pub headcont: HTTPExtentions, //Headers
     pub account: Option<u16>,     //ID
+    pub params: Params,           //Matched Route Parameters
 }
 
 impl New for RequestHeader {}
@@ -96,9 +141,57 @@ impl Default for RequestHeader {
         Self {
             method: Method::GET,
             uri: Uri::new(),
+            host: None,
             version: Version::new(),
             headcont: Vec::new(),
             account: None,
+            params: Params::new(),
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // `None` for a rejected request
+    fn parse(src: &str) -> Option<HTTPRequest> {
+        let mut req = HTTPRequest::new();
+        match req.from_string(&src.to_string(), &Client::new()) {
+            Some(_) => None,
+            None => Some(req),
+        }
+    }
+
+    #[test]
+    fn host_header() {
+        let req = parse("GET / HTTP/1.1\r\nhOsT: Example.com\r\n\r\n").unwrap();
+        assert_eq!(req.parts.host, Some(Host::parse("example.com").unwrap()));
+        assert!(req.parts.headcont[0].key == AllowedExtentions::Host);
+        assert!(parse("GET / HTTP/1.1\r\nAccept: */*\r\n\r\n").is_none());
+        assert!(parse("GET / HTTP/1.1\r\nHost: a\r\nhost: a\r\n\r\n").is_none());
+        assert!(parse("GET / HTTP/1.0\r\nHost: a\r\nHost: b\r\n\r\n").is_none());
+        assert!(parse("GET / HTTP/1.1\r\nHost: a b\r\n\r\n").is_none());
+        assert!(parse("GET http://a/ HTTP/1.1\r\nHost: a b\r\n\r\n").is_none());
+        assert!(parse("GET http://a/ HTTP/1.1\r\n\r\n").is_none());
+        let req = parse("GET / HTTP/1.0\r\nAccept: */*\r\n\r\n").unwrap();
+        assert_eq!(req.parts.host, None);
+        let req = parse("GET http://a/ HTTP/1.1\r\nHost: b\r\n\r\n").unwrap();
+        assert_eq!(req.parts.host, Some(Host::parse("a").unwrap()));
+        assert_eq!(parse("GET / HTTP/1.1\r\nHost:\r\n\r\n").unwrap().parts.host, Some(Host::parse("").unwrap()));
+    }
+
+    #[test]
+    fn path_options() {
+        let src = "GET /a//b/../c HTTP/1.1\r\nHost: a\r\n\r\n".to_string();
+        let mut req = HTTPRequest::new();
+        assert!(req.from_string(&src, &Client::new()).is_none());
+        assert_eq!(req.parts.uri.path, "/a/c");
+        let res = Client {
+            path_options: PathOptions { merge_slashes: false },
+            ..Client::new()
+        };
+        assert!(req.from_string(&src, &res).is_none());
+        assert_eq!(req.parts.uri.path, "/a//c");
+    }
+}
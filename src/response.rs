@@ -1,17 +1,74 @@
-use crate::{date::Date, http::{request::HTTPRequest, utils::{ContType, Version, PLAIN, SERVERS}}, traits::New, traits::LogUnwrap};
+use crate::{http::{cookie::CookieJar, date::date_header, method::Method, request::HTTPRequest, router::Dispatch, uri::Host, utils::{ContType, Version, PLAIN, SERVERS}}, traits::New};
+
+use std::{io::{self, Write}, ops::Deref, sync::Arc};
 
 use super::server::Client;
 
 
 pub struct HTTPResponse {
     pub rsheader: ResponseHeader,
-    pub body: Vec<u8>,
+    pub body: Body,
+}
+
+/// Response body, shared bodies come from the `FileCache` and are not copied
+/// until the response is written
+#[derive(Clone, Debug)]
+pub enum Body {
+    Owned(Vec<u8>),
+    Shared(Arc<[u8]>),
+}
+
+impl Body {
+    pub fn into_vec(self) -> Vec<u8> {
+        match self {
+            Body::Owned(v) => v,
+            Body::Shared(v) => v.to_vec(),
+        }
+    }
+    /// Copies a shared body first, like `Cow::to_mut`
+    pub fn to_mut(&mut self) -> &mut Vec<u8> {
+        if let Body::Shared(v) = self {
+            *self = Body::Owned(v.to_vec());
+        }
+        match self {
+            Body::Owned(v) => v,
+            Body::Shared(_) => unreachable!(),
+        }
+    }
+}
+
+impl Deref for Body {
+    type Target = [u8];
+    fn deref(&self) -> &[u8] {
+        match self {
+            Body::Owned(v) => v,
+            Body::Shared(v) => v,
+        }
+    }
+}
+
+impl From<Vec<u8>> for Body {
+    fn from(value: Vec<u8>) -> Self {
+        Body::Owned(value)
+    }
+}
+
+impl From<Arc<[u8]>> for Body {
+    fn from(value: Arc<[u8]>) -> Self {
+        Body::Shared(value)
+    }
+}
+
+impl Default for Body {
+    fn default() -> Self {
+        Body::Owned(Vec::new())
+    }
 }
 
 
 impl HTTPResponse {
     pub fn ct(mut self, ct: ContType) -> HTTPResponse {
-        self.rsheader.fields.push((String::from("Content-Type:"), ct.as_str().to_string()));
+        self.rsheader.fields.push((String::from("Content-Type"), ct.as_str().to_string()));
         self
     }
     pub fn version(mut self, ver: Version) -> HTTPResponse {
@@ -22,82 +79,124 @@ impl HTTPResponse {
         self.rsheader.status_code = ver;
         self
     }
-    pub fn body(mut self, ver: Vec<u8>) -> HTTPResponse {
-        self.body = ver;
+    pub fn body(mut self, ver: impl Into<Body>) -> HTTPResponse {
+        self.body = ver.into();
         self
     }
+    pub fn header(mut self, key: &str, value: &str) -> HTTPResponse {
+        self.rsheader.fields.push((key.to_string(), value.to_string()));
+        self
+    }
+    pub fn cookies(mut self, jar: &CookieJar) -> HTTPResponse {
+        for cookie in jar.delta() {
+            self.rsheader.fields.push((String::from("Set-Cookie"), cookie.to_header_value()));
+        }
+        self
+    }
+    /// The serialized value as body, a status still at the default `500` becomes `200`.
+    /// If `value` cannot be serialized the body is the error, headers set so far are kept
+    #[cfg(feature = "json")]
+    pub fn json<T: serde::Serialize>(mut self, value: T) -> HTTPResponse {
+        match crate::http::json::Json(value).to_bytes() {
+            Ok(body) => {
+                if self.rsheader.status_code == StatusCode::INTERNALSERVERERROR {
+                    self.rsheader.status_code = StatusCode::OK;
+                }
+                self.ct(crate::http::utils::JSON).body(body)
+            }
+            Err(e) => self
+                .status_code(e.status_code())
+                .ct(crate::http::utils::JSON)
+                .body(e.to_bytes()),
+        }
+    }
     pub fn gen_len(mut self) -> HTTPResponse {
         self.rsheader.fields.push((String::from("Content-Length"), self.body.len().to_string()));
         self
     }
-    pub fn build<'a>(mut self) -> Vec<u8> {
-        let mut str: Vec<u8> = format!(
-            "{} {}\r\n{}\r\n\r\n",
+    /// Header and body in one buffer, the body is copied into it once. Use
+    /// `write_to` to send a shared body without the copy
+    pub fn build<'a>(self) -> Vec<u8> {
+        let mut str: Vec<u8> = self.head().into_bytes();
+        str.extend_from_slice(&self.body);
+        str
+    }
+    /// Writes the header and then the body, which is not copied
+    pub fn write_to(&self, out: &mut impl Write) -> io::Result<()> {
+        out.write_all(self.head().as_bytes())?;
+        out.write_all(&self.body)
+    }
+    fn head(&self) -> String {
+        format!(
+            "{} {}\r\nDate: {}\r\nServer: {}\r\n{}\r\n\r\n",
             self.rsheader.version.to_string(),
             self.rsheader.status_code.as_str(),
+            self.rsheader.date,
+            self.rsheader.server,
             self.rsheader.fields.iter().map(|header| format!("{}: {}", header.0, header.1)).collect::<Vec<String>>().join("\r\n")
         )
-        .into_bytes();
-        str.extend_from_slice(&mut self.body);
-        str
     }
-    pub fn wrong_method(req: &HTTPRequest)->Vec<u8> {
+    /// `allow` lists the methods the path does support
+    pub fn wrong_method(req: &HTTPRequest, allow: &str)->HTTPResponse {
         HTTPResponse::new()
             .version(req.parts.version)
             .status_code(StatusCode::METHODNOTALLOWED)
+            .header("Allow", allow)
             .ct(PLAIN)
             .body("405 Method Not Allowed".to_string().into_bytes())
-            .gen_len().build()
     }
-    pub fn misdirreq(req: &HTTPRequest)->Vec<u8> {
-        let response = HTTPResponse::new()
+    pub fn options(req: &HTTPRequest, allow: &str)->HTTPResponse {
+        HTTPResponse::new()
+            .version(req.parts.version)
+            .status_code(StatusCode::NOCONTENT)
+            .header("Allow", allow)
+    }
+    pub fn not_found(req: &HTTPRequest)->HTTPResponse {
+        HTTPResponse::new()
+            .version(req.parts.version)
+            .status_code(StatusCode::NOTFOUND)
+            .ct(PLAIN)
+            .body("404 Not Found".to_string().into_bytes())
+    }
+    pub fn misdirreq(req: &HTTPRequest)->HTTPResponse {
+        HTTPResponse::new()
             .version(req.parts.version)
             .status_code(StatusCode::MISDIRECTEDREQUEST)
             .ct(PLAIN)
             .body("421 Misdirected Request: Wrong Uri".to_string().into_bytes())
-            .gen_len();
-        response.build()
     }
-    pub fn get_run(req: &HTTPRequest, res: &Client, auth: &str)-> Vec<u8> {
-        if req.parts.uri.authority.host == auth || req.parts.uri.authority.host == "" {
-            match res.paths.binary_search(&req.parts.uri.path.clone().into_boxed_str()) { //////////////////////////////////// Könnte Probleme machen wenn Path und Sites nicht gleich sind
-                Ok(index) => {
-                    let site = res.sites.iter().nth(index).unwrap_log();
-                    HTTPResponse::new()
-                        .status_code(StatusCode::OK)
-                        .version(req.parts.version)
-                        .body(site.get_page(req, res))
-                        .ct(site.file_type.clone())
-                        .gen_len()
-                        .build()
-                }
-                Err(_) => {
-                    return HTTPResponse::misdirreq(req)
-                }
-            }
-        } else {
-            return HTTPResponse::misdirreq(req)
+    /// Routes the request through the middleware of `res`. The length is set
+    /// afterwards so middleware may still change the body. `auth` is parsed
+    /// once at setup, requests for another host get a `421`
+    pub fn run(req: &mut HTTPRequest, res: &Client, auth: &Host)-> Vec<u8> {
+        let mut response = res.middleware.run(req, res, |req| HTTPResponse::route(req, res, auth));
+        response = response.version(req.parts.version);
+        if response.rsheader.status_code != StatusCode::NOCONTENT {
+            response = response.gen_len();
+        }
+        if req.parts.method == Method::HEAD {
+            response.body = Body::default();
         }
+        response.build()
     }
-    pub fn post_run(req: &HTTPRequest, res: &Client, auth: &str)-> Vec<u8> {
-        if req.parts.uri.authority.host == auth || req.parts.uri.authority.host == "" {
-            match res.api_paths.binary_search(&req.parts.uri.path.clone().into_boxed_str()) { //////////////////////////////////// Könnte Probleme machen wenn Path und Api nicht gleich sind
-                Ok(index) => {
-                    let api = res.api.iter().nth(index).unwrap_log();
-                    HTTPResponse::new()
-                        .status_code(StatusCode::OK)
-                        .version(req.parts.version)
-                        .body(api.get_resp(req, res))
-                        .ct(api.filetype.clone())
-                        .gen_len()
-                        .build()
-                }
-                Err(_) => {
-                    return HTTPResponse::misdirreq(req)
+    fn route(req: &mut HTTPRequest, res: &Client, auth: &Host)-> HTTPResponse {
+        if req.parts.host.as_ref().is_none_or(|host| host.is_empty() || host == auth) {
+            // Held until the response is built, a reload does not affect this request
+            let routes = res.routes.load();
+            req.routes = Some(routes.clone());
+            match routes.dispatch(&req.parts.method, &req.parts.uri.path) {
+                Dispatch::Found(endpoint, params) | Dispatch::Head(endpoint, params) => {
+                    req.parts.params = params;
+                    endpoint.get_resp(req, res)
                 }
+                Dispatch::Options(allow) => HTTPResponse::options(req, &allow),
+                Dispatch::MethodNotAllowed(allow) => res
+                    .error(req, StatusCode::METHODNOTALLOWED)
+                    .header("Allow", &allow),
+                Dispatch::NotFound => res.error(req, StatusCode::NOTFOUND),
             }
         } else {
-            return HTTPResponse::misdirreq(req)
+            res.error(req, StatusCode::MISDIRECTEDREQUEST)
         }
     }
 }
@@ -108,11 +207,11 @@ impl Default for HTTPResponse {
             rsheader: ResponseHeader {
                 version: Version::HTTP1_1,
                 status_code: StatusCode::INTERNALSERVERERROR,
-                date: Date::new().now().to_http_string(),
+                date: date_header(),
                 server: SERVERS,
                 fields: Vec::new()
             },
-            body: Vec::new(),
+            body: Body::default(),
         }
     }
 }
@@ -130,7 +229,7 @@ pub struct ResponseHeader {
 impl ResponseHeader {
     pub fn as_str(&self) -> String {
         let r: String = format!(
-            "{} {}\r\n{}\r\nServer: {}\r\n{}\r\n",
+            "{} {}\r\nDate: {}\r\nServer: {}\r\n{}\r\n",
             self.version.clone().to_string(),
             self.status_code.as_str(),
             self.date,
@@ -142,8 +241,10 @@ impl ResponseHeader {
 }
 
 
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
 pub struct StatusCode(Codes);
 
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
 enum Codes {
     SC100,
     SC101,
@@ -321,4 +422,139 @@ impl StatusCode {
             Codes::SC511 => "511 Network Authentication Required",
         }
     }
-}
\ No newline at end of file
+    /// `404` for `StatusCode::NOTFOUND`
+    pub fn code(&self) -> u16 {
+        self.as_str()[..3].parse().unwrap_or(500)
+    }
+    /// `Not Found` for `StatusCode::NOTFOUND`
+    pub fn reason(&self) -> &str {
+        &self.as_str()[4..]
+    }
+    pub fn from_code(code: u16) -> Option<StatusCode> {
+        ALL_CODES.iter().map(|c| StatusCode(*c)).find(|status| status.code() == code)
+    }
+}
+
+const ALL_CODES: [Codes; 56] = [
+    Codes::SC100, Codes::SC101, Codes::SC200, Codes::SC201, Codes::SC202, Codes::SC203,
+    Codes::SC204, Codes::SC205, Codes::SC206, Codes::SC300, Codes::SC301, Codes::SC302,
+    Codes::SC303, Codes::SC304, Codes::SC307, Codes::SC308, Codes::SC400, Codes::SC401,
+    Codes::SC402, Codes::SC403, Codes::SC404, Codes::SC405, Codes::SC406, Codes::SC407,
+    Codes::SC408, Codes::SC409, Codes::SC410, Codes::SC411, Codes::SC412, Codes::SC413,
+    Codes::SC414, Codes::SC415, Codes::SC416, Codes::SC417, Codes::SC418, Codes::SC421,
+    Codes::SC422, Codes::SC423, Codes::SC424, Codes::SC425, Codes::SC426, Codes::SC428,
+    Codes::SC429, Codes::SC431, Codes::SC451, Codes::SC500, Codes::SC501, Codes::SC502,
+    Codes::SC503, Codes::SC504, Codes::SC505, Codes::SC506, Codes::SC507, Codes::SC508,
+    Codes::SC510, Codes::SC511,
+];
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::http::{site::{Api, ApiFn, Endpoint}, utils::PLAIN};
+
+    fn api(method: Method, body: &'static str) -> Api {
+        Api {
+            path: "/users/:id".into(),
+            method,
+            fnp: ApiFn::raw(move |_, _| body.as_bytes().to_vec()),
+            filetype: PLAIN,
+            auth: 0,
+            headers: Vec::new(),
+        }
+    }
+
+    fn client(apis: Vec<Api>) -> Client {
+        let routes = Endpoint::route_all(Vec::new(), apis).unwrap();
+        Client { routes: routes.into(), ..Client::new() }
+    }
+
+    fn run(res: &Client, method: &str, target: &str) -> String {
+        let mut req = HTTPRequest::new();
+        let src = format!("{} {} HTTP/1.1\r\nHost: x\r\n\r\n", method, target);
+        assert!(req.from_string(&src, res).is_none(), "{}", src);
+        String::from_utf8(HTTPResponse::run(&mut req, res, &Host::parse("x").unwrap())).unwrap()
+    }
+
+    #[test]
+    fn dispatch() {
+        let res = client(vec![api(Method::GET, "user"), api(Method::DELETE, "deleted"), api(Method::from_str("PURGE"), "purged")]);
+        let out = run(&res, "GET", "/users/7");
+        assert!(out.starts_with("HTTP/1.1 200 OK\r\n") && out.ends_with("\r\n\r\nuser"), "{}", out);
+        assert!(run(&res, "PURGE", "/users/7").ends_with("\r\n\r\npurged"));
+
+        let out = run(&res, "PATCH", "/users/7");
+        assert!(out.starts_with("HTTP/1.1 405 Method Not Allowed\r\n"), "{}", out);
+        assert!(out.contains("\r\nAllow: GET, DELETE, PURGE, HEAD, OPTIONS\r\n"), "{}", out);
+
+        let out = run(&res, "OPTIONS", "/users/7");
+        assert!(out.starts_with("HTTP/1.1 204 No Content\r\n"), "{}", out);
+        assert!(out.contains("\r\nAllow: GET, DELETE, PURGE, HEAD, OPTIONS\r\n"), "{}", out);
+        assert!(!out.contains("Content-Length") && out.ends_with("\r\n\r\n"), "{}", out);
+
+        assert!(run(&res, "GET", "/nope").starts_with("HTTP/1.1 404 Not Found\r\n"));
+        assert!(run(&res, "OPTIONS", "/nope").starts_with("HTTP/1.1 404 Not Found\r\n"));
+    }
+
+    #[test]
+    fn misdirected() {
+        let res = client(vec![api(Method::GET, "user")]);
+        let auth = Host::parse("Bücher.example").unwrap();
+        for (host, status) in [("xn--bcher-kva.example", "200 OK"), ("BÜCHER.example:80", "200 OK"), ("other.example", "421 Misdirected Request")] {
+            let mut req = HTTPRequest::new();
+            let src = format!("GET /users/7 HTTP/1.1\r\nHost: {}\r\n\r\n", host);
+            assert!(req.from_string(&src, &res).is_none(), "{}", src);
+            let out = String::from_utf8(HTTPResponse::run(&mut req, &res, &auth)).unwrap();
+            assert!(out.starts_with(&format!("HTTP/1.1 {}\r\n", status)), "{}", out);
+        }
+    }
+
+    #[test]
+    fn own_options() {
+        let res = client(vec![api(Method::POST, "created"), api(Method::OPTIONS, "options")]);
+        let out = run(&res, "OPTIONS", "/users/7");
+        assert!(out.starts_with("HTTP/1.1 200 OK\r\n") && out.ends_with("\r\n\r\noptions"), "{}", out);
+        // No `GET` to answer `HEAD` with
+        let out = run(&res, "HEAD", "/users/7");
+        assert!(out.starts_with("HTTP/1.1 405 Method Not Allowed\r\n"), "{}", out);
+        assert!(out.contains("\r\nAllow: POST, OPTIONS\r\n"), "{}", out);
+    }
+
+    #[test]
+    fn head() {
+        let res = client(vec![api(Method::GET, "user")]);
+        let out = run(&res, "HEAD", "/users/7");
+        assert!(out.starts_with("HTTP/1.1 200 OK\r\n"), "{}", out);
+        assert!(out.contains("\r\nContent-Length: 4\r\n") && out.ends_with("\r\n\r\n"), "{}", out);
+        let out = run(&res, "HEAD", "/nope");
+        assert!(out.starts_with("HTTP/1.1 404 Not Found\r\n"), "{}", out);
+        assert!(out.contains("\r\nContent-Length: 13\r\n") && out.ends_with("\r\n\r\n"), "{}", out);
+    }
+
+    #[test]
+    fn write_to() {
+        let body: Arc<[u8]> = Arc::from(&b"shared"[..]);
+        let response = HTTPResponse::new().status_code(StatusCode::OK).body(body.clone()).gen_len();
+        let mut out = Vec::new();
+        response.write_to(&mut out).unwrap();
+        assert!(out.ends_with(b"\r\nContent-Length: 6\r\n\r\nshared"));
+        assert_eq!(out, response.build());
+    }
+
+    #[cfg(feature = "json")]
+    #[test]
+    fn json() {
+        let res = HTTPResponse::new().header("X-Id", "7").json([1, 2]);
+        assert_eq!(res.rsheader.status_code, StatusCode::OK);
+        assert_eq!(&*res.body, b"[1,2]");
+        assert!(res.rsheader.fields.iter().any(|(k, v)| k == "X-Id" && v == "7"));
+        let res = HTTPResponse::new().status_code(StatusCode::CREATED).json("new");
+        assert_eq!(res.rsheader.status_code, StatusCode::CREATED);
+
+        let failed = std::collections::HashMap::from([((1, 2), 3)]);
+        let res = HTTPResponse::new().status_code(StatusCode::CREATED).header("X-Id", "7").json(failed);
+        assert_eq!(res.rsheader.status_code, StatusCode::INTERNALSERVERERROR);
+        assert_eq!(&*res.body, br#"{"error":"key must be a string"}"#);
+        assert!(res.rsheader.fields.iter().any(|(k, v)| k == "X-Id" && v == "7"));
+    }
+}
@@ -0,0 +1,559 @@
+use std::{
+    error::Error,
+    fmt::Display,
+    ops::Deref,
+    sync::{Arc, RwLock},
+};
+
+use crate::{
+    http::{error_pages::ErrorPages, form::percent_decode, method::Method},
+    traits::New,
+};
+
+/// Segment trie, static segments win over `:param` which wins over `*rest`.
+/// A failed static branch falls back to the param and then the catch-all
+pub struct Router<T> {
+    root: Node<T>,
+}
+
+struct Node<T> {
+    /// Sorted by segment
+    statics: Vec<(Box<str>, Node<T>)>,
+    param: Option<(Box<str>, Box<Node<T>>)>,
+    catch_all: Option<(Box<str>, Box<Node<T>>)>,
+    route: Option<Route<T>>,
+}
+
+struct Route<T> {
+    pattern: Box<str>,
+    value: T,
+}
+
+/// Routes which can be replaced while requests are served. Requests keep
+/// the table they started with until they finish
+pub struct RouteTable<T> {
+    current: RwLock<Arc<Routes<T>>>,
+}
+
+/// A router with the error pages of the same config, swapped as one
+pub struct Routes<T> {
+    pub router: Router<T>,
+    pub errors: ErrorPages,
+}
+
+/// Matched `:param` and `*rest` values, percent-decoded
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Params(Vec<(Box<str>, String)>);
+
+/// The handlers of one route, by method
+pub struct Methods<T> {
+    handlers: Vec<(Method, T)>,
+}
+
+pub enum Dispatch<'r, T> {
+    Found(&'r T, Params),
+    /// `HEAD` without own handler, the `GET` handler answers and the caller drops the body
+    Head(&'r T, Params),
+    /// Automatic answer to `OPTIONS`, carries the `Allow` value
+    Options(String),
+    /// The path exists but not for this method, carries the `Allow` value
+    MethodNotAllowed(String),
+    NotFound,
+}
+
+pub struct Match<'r, T> {
+    pub value: &'r T,
+    /// The pattern the route was registered with
+    pub pattern: &'r str,
+    pub params: Params,
+}
+
+#[derive(Debug)]
+pub struct RouteError(u8);
+
+impl RouteError {
+    pub const DUPLICATE: RouteError = RouteError(1);
+    pub const CONFLICT: RouteError = RouteError(2);
+    pub const INVALID: RouteError = RouteError(3);
+    pub fn reason(&self) -> &str {
+        match self.0 {
+            1 => "Route already registered",
+            2 => "Route conflicts with a differently named parameter",
+            3 => "Invalid route pattern",
+            _ => "Error not defined",
+        }
+    }
+}
+
+impl Display for RouteError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.reason())
+    }
+}
+
+impl Error for RouteError {}
+
+fn segments(path: &str) -> std::str::Split<'_, char> {
+    path.strip_prefix('/').unwrap_or(path).split('/')
+}
+
+impl<T> Node<T> {
+    fn find<'r, 'p>(
+        &'r self,
+        path: &[&'p str],
+        params: &mut Vec<(&'r str, &'p str)>,
+        rest: &mut Option<(&'r str, usize)>,
+    ) -> Option<&'r Route<T>> {
+        let split = path.split_first();
+        if split.is_none() && self.route.is_some() {
+            return self.route.as_ref();
+        }
+        if let Some((segment, tail)) = split {
+            if let Ok(i) = self
+                .statics
+                .binary_search_by(|(s, _)| s.as_ref().cmp(segment))
+            {
+                if let Some(route) = self.statics[i].1.find(tail, params, rest) {
+                    return Some(route);
+                }
+            }
+            if let Some((ref name, ref child)) = self.param {
+                if !segment.is_empty() {
+                    params.push((name, segment));
+                    if let Some(route) = child.find(tail, params, rest) {
+                        return Some(route);
+                    }
+                    params.pop();
+                }
+            }
+        }
+        // Also matches nothing, `/static/*file` answers `/static` with an empty `file`
+        if let Some((ref name, ref child)) = self.catch_all {
+            if let Some(ref route) = child.route {
+                *rest = Some((name, path.len()));
+                return Some(route);
+            }
+        }
+        None
+    }
+}
+
+impl<T> Router<T> {
+    // Rejects an invalid `pattern` or one clashing with the names already registered
+    fn check(&self, pattern: &str) -> Result<(), RouteError> {
+        let mut node = Some(&self.root);
+        let mut segments = segments(pattern).peekable();
+        while let Some(segment) = segments.next() {
+            let (dynamic, name) = if let Some(name) = segment.strip_prefix('*') {
+                if segments.peek().is_some() {
+                    return Err(RouteError::INVALID);
+                }
+                (node.and_then(|n| n.catch_all.as_ref()), name)
+            } else if let Some(name) = segment.strip_prefix(':') {
+                (node.and_then(|n| n.param.as_ref()), name)
+            } else {
+                node = node.and_then(|n| {
+                    n.statics
+                        .binary_search_by(|(s, _)| s.as_ref().cmp(segment))
+                        .ok()
+                        .map(|i| &n.statics[i].1)
+                });
+                continue;
+            };
+            if name.is_empty() {
+                return Err(RouteError::INVALID);
+            }
+            node = match dynamic {
+                Some((other, _)) if other.as_ref() != name => return Err(RouteError::CONFLICT),
+                Some((_, child)) => Some(child.as_ref()),
+                None => None,
+            };
+        }
+        Ok(())
+    }
+
+    // The route slot of `pattern`, the trie is only touched once the whole pattern passed `check`
+    fn slot(&mut self, pattern: &str) -> Result<&mut Option<Route<T>>, RouteError> {
+        self.check(pattern)?;
+        let mut node = &mut self.root;
+        for segment in segments(pattern) {
+            node = if let Some(name) = segment.strip_prefix('*') {
+                node.catch_all
+                    .get_or_insert_with(|| (name.into(), Box::new(Node::new())))
+                    .1
+                    .as_mut()
+            } else if let Some(name) = segment.strip_prefix(':') {
+                node.param
+                    .get_or_insert_with(|| (name.into(), Box::new(Node::new())))
+                    .1
+                    .as_mut()
+            } else {
+                let i = match node
+                    .statics
+                    .binary_search_by(|(s, _)| s.as_ref().cmp(segment))
+                {
+                    Ok(i) => i,
+                    Err(i) => {
+                        node.statics.insert(i, (segment.into(), Node::new()));
+                        i
+                    }
+                };
+                &mut node.statics[i].1
+            };
+        }
+        Ok(&mut node.route)
+    }
+
+    /// `/users/:id/orders`, `/static/*file`. A leading `/` is optional, `*rest` also
+    /// matches an empty rest
+    pub fn insert(&mut self, pattern: &str, value: T) -> Result<(), RouteError> {
+        let slot = self.slot(pattern)?;
+        if slot.is_some() {
+            return Err(RouteError::DUPLICATE);
+        }
+        *slot = Some(Route {
+            pattern: pattern.into(),
+            value,
+        });
+        Ok(())
+    }
+
+    /// Looks up a normalized request path
+    pub fn at(&self, path: &str) -> Option<Match<'_, T>> {
+        let path: Vec<&str> = segments(path).collect();
+        let mut params = Vec::new();
+        let mut rest = None;
+        let route = self.root.find(&path, &mut params, &mut rest)?;
+        let mut params: Vec<(Box<str>, String)> = params
+            .into_iter()
+            .map(|(name, value)| (name.into(), decode(value)))
+            .collect();
+        if let Some((name, len)) = rest {
+            params.push((name.into(), decode(&path[path.len() - len..].join("/"))));
+        }
+        Some(Match {
+            value: &route.value,
+            pattern: &route.pattern,
+            params: Params(params),
+        })
+    }
+}
+
+impl<T> Router<Methods<T>> {
+    /// Registers `value` for `method` on `pattern`, other methods of the pattern are kept
+    pub fn route(&mut self, method: Method, pattern: &str, value: T) -> Result<(), RouteError> {
+        let route = self.slot(pattern)?.get_or_insert_with(|| Route {
+            pattern: pattern.into(),
+            value: Methods::new(),
+        });
+        if route.value.get(&method).is_some() {
+            return Err(RouteError::DUPLICATE);
+        }
+        route.value.handlers.push((method, value));
+        Ok(())
+    }
+
+    pub fn dispatch(&self, method: &Method, path: &str) -> Dispatch<'_, T> {
+        let found = match self.at(path) {
+            Some(found) => found,
+            None => return Dispatch::NotFound,
+        };
+        if let Some(value) = found.value.get(method) {
+            return Dispatch::Found(value, found.params);
+        }
+        if *method == Method::HEAD {
+            if let Some(value) = found.value.get(&Method::GET) {
+                return Dispatch::Head(value, found.params);
+            }
+        }
+        if *method == Method::OPTIONS {
+            return Dispatch::Options(found.value.allow());
+        }
+        Dispatch::MethodNotAllowed(found.value.allow())
+    }
+}
+
+impl<T> RouteTable<T> {
+    /// The current table, unaffected by later swaps
+    pub fn load(&self) -> Arc<Routes<T>> {
+        // Only ever holds a finished table, a poisoned lock is still consistent
+        self.current
+            .read()
+            .unwrap_or_else(|e| e.into_inner())
+            .clone()
+    }
+    /// Installs `routes` for all following requests and returns the previous table.
+    /// A bare `Router` comes without error pages
+    pub fn swap(&self, routes: impl Into<Routes<T>>) -> Arc<Routes<T>> {
+        let routes = Arc::new(routes.into());
+        let mut current = self.current.write().unwrap_or_else(|e| e.into_inner());
+        std::mem::replace(&mut *current, routes)
+    }
+}
+
+impl<T> From<Routes<T>> for RouteTable<T> {
+    fn from(value: Routes<T>) -> Self {
+        RouteTable {
+            current: RwLock::new(Arc::new(value)),
+        }
+    }
+}
+
+impl<T> From<Router<T>> for RouteTable<T> {
+    fn from(value: Router<T>) -> Self {
+        Routes::from(value).into()
+    }
+}
+
+impl<T> From<Router<T>> for Routes<T> {
+    fn from(router: Router<T>) -> Self {
+        Routes {
+            router,
+            errors: ErrorPages::new(),
+        }
+    }
+}
+
+impl<T> Deref for Routes<T> {
+    type Target = Router<T>;
+    fn deref(&self) -> &Router<T> {
+        &self.router
+    }
+}
+
+impl<T> Default for RouteTable<T> {
+    fn default() -> Self {
+        Router::new().into()
+    }
+}
+
+impl<T> New for RouteTable<T> {}
+
+impl<T> Methods<T> {
+    pub fn get(&self, method: &Method) -> Option<&T> {
+        self.handlers
+            .iter()
+            .find(|(m, _)| m == method)
+            .map(|(_, value)| value)
+    }
+    /// Value of the `Allow` header, `HEAD` and `OPTIONS` are always answered
+    pub fn allow(&self) -> String {
+        let mut methods: Vec<&str> = self.handlers.iter().map(|(m, _)| m.as_str()).collect();
+        if self.get(&Method::GET).is_some() && self.get(&Method::HEAD).is_none() {
+            methods.push("HEAD");
+        }
+        if self.get(&Method::OPTIONS).is_none() {
+            methods.push("OPTIONS");
+        }
+        methods.join(", ")
+    }
+}
+
+impl<T> Default for Methods<T> {
+    fn default() -> Self {
+        Self {
+            handlers: Vec::new(),
+        }
+    }
+}
+
+impl<T> New for Methods<T> {}
+
+fn decode(src: &str) -> String {
+    String::from_utf8_lossy(&percent_decode(src.as_bytes(), false)).into_owned()
+}
+
+impl Params {
+    pub fn get(&self, name: &str) -> Option<&str> {
+        self.0
+            .iter()
+            .find(|(key, _)| key.as_ref() == name)
+            .map(|(_, value)| value.as_str())
+    }
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.0.iter().map(|(key, value)| (key.as_ref(), value.as_str()))
+    }
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+}
+
+impl<T> Default for Node<T> {
+    fn default() -> Self {
+        Self {
+            statics: Vec::new(),
+            param: None,
+            catch_all: None,
+            route: None,
+        }
+    }
+}
+
+impl<T> New for Node<T> {}
+
+impl<T> Default for Router<T> {
+    fn default() -> Self {
+        Self {
+            root: Node::new(),
+        }
+    }
+}
+
+impl<T> New for Router<T> {}
+
+impl Default for Params {
+    fn default() -> Self {
+        Self(Vec::new())
+    }
+}
+
+impl New for Params {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn router(patterns: &[&'static str]) -> Router<&'static str> {
+        let mut router = Router::new();
+        for pattern in patterns {
+            router.insert(pattern, *pattern).unwrap();
+        }
+        router
+    }
+
+    fn matched(router: &Router<&'static str>, path: &str) -> Option<(&'static str, Vec<(String, String)>)> {
+        router.at(path).map(|m| {
+            let params = m.params.iter().map(|(k, v)| (k.to_string(), v.to_string())).collect();
+            (*m.value, params)
+        })
+    }
+
+    fn params(pairs: &[(&str, &str)]) -> Vec<(String, String)> {
+        pairs.iter().map(|(k, v)| (k.to_string(), v.to_string())).collect()
+    }
+
+    #[test]
+    fn priority() {
+        let router = router(&["/users/me", "/users/:id", "/users/*rest", "/users/me/settings", "/users/:id/orders"]);
+        assert_eq!(matched(&router, "/users/me"), Some(("/users/me", vec![])));
+        assert_eq!(matched(&router, "/users/7"), Some(("/users/:id", params(&[("id", "7")]))));
+        assert_eq!(matched(&router, "/users/7/x"), Some(("/users/*rest", params(&[("rest", "7/x")]))));
+        assert_eq!(matched(&router, "/users/me/settings"), Some(("/users/me/settings", vec![])));
+        // `me` exists as static but only `:id` continues with `orders`
+        assert_eq!(matched(&router, "/users/me/orders"), Some(("/users/:id/orders", params(&[("id", "me")]))));
+        // Neither branch continues, the catch-all takes it
+        assert_eq!(matched(&router, "/users/me/x"), Some(("/users/*rest", params(&[("rest", "me/x")]))));
+        assert_eq!(matched(&router, "/other"), None);
+        // An empty segment is not a param
+        assert_eq!(matched(&router, "/users/"), Some(("/users/*rest", params(&[("rest", "")]))));
+        assert_eq!(router.at("users/me").unwrap().pattern, "/users/me");
+    }
+
+    #[test]
+    fn catch_all() {
+        let router = router(&["/static/*file", "/*path"]);
+        assert_eq!(matched(&router, "/static/a/b.css"), Some(("/static/*file", params(&[("file", "a/b.css")]))));
+        assert_eq!(matched(&router, "/static"), Some(("/static/*file", params(&[("file", "")]))));
+        assert_eq!(matched(&router, "/static/"), Some(("/static/*file", params(&[("file", "")]))));
+        assert_eq!(matched(&router, "/"), Some(("/*path", params(&[("path", "")]))));
+        assert_eq!(matched(&router, "/staticx"), Some(("/*path", params(&[("path", "staticx")]))));
+        // An own route wins over the empty rest
+        let prefixed = self::router(&["/static", "/static/*file"]);
+        assert_eq!(matched(&prefixed, "/static"), Some(("/static", vec![])));
+    }
+
+    #[test]
+    fn decoded() {
+        let router = router(&["/users/:id", "/files/*rest"]);
+        assert_eq!(matched(&router, "/users/a%20b%C3%A9"), Some(("/users/:id", params(&[("id", "a b\u{e9}")]))));
+        assert_eq!(matched(&router, "/users/a+b"), Some(("/users/:id", params(&[("id", "a+b")]))));
+        assert_eq!(matched(&router, "/files/a%20b/c%2Fd"), Some(("/files/*rest", params(&[("rest", "a b/c/d")]))));
+        assert_eq!(matched(&router, "/users/%FF"), Some(("/users/:id", params(&[("id", "\u{fffd}")]))));
+    }
+
+    fn err(result: Result<(), RouteError>) -> u8 {
+        result.unwrap_err().0
+    }
+
+    #[test]
+    fn errors() {
+        let mut router = router(&["/users/:id", "/files/*rest"]);
+        assert_eq!(err(router.insert("/users/:id", "")), RouteError::DUPLICATE.0);
+        assert_eq!(err(router.insert("users/:id", "")), RouteError::DUPLICATE.0);
+        assert_eq!(err(router.insert("/users/:uid", "")), RouteError::CONFLICT.0);
+        assert_eq!(err(router.insert("/users/:uid/orders", "")), RouteError::CONFLICT.0);
+        assert_eq!(err(router.insert("/files/*path", "")), RouteError::CONFLICT.0);
+        assert_eq!(err(router.insert("/a/*rest/b", "")), RouteError::INVALID.0);
+        assert_eq!(err(router.insert("/a/*", "")), RouteError::INVALID.0);
+        assert_eq!(err(router.insert("/a/:", "")), RouteError::INVALID.0);
+        assert_eq!(err(router.insert("/a/:/b", "")), RouteError::INVALID.0);
+        assert_eq!(RouteError::CONFLICT.to_string(), "Route conflicts with a differently named parameter");
+    }
+
+    #[test]
+    fn rejected_patterns_leave_no_trace() {
+        let mut router = Router::new();
+        assert_eq!(err(router.insert("/q/:id/x/:", "invalid")), RouteError::INVALID.0);
+        assert_eq!(err(router.insert("/r/*rest/x", "invalid")), RouteError::INVALID.0);
+        router.insert("/s/:a/t", "s").unwrap();
+        assert_eq!(err(router.insert("/s/:a/t/:b/u/:", "invalid")), RouteError::INVALID.0);
+        // None of the names above were kept
+        router.insert("/q/:uid", "q").unwrap();
+        router.insert("/r/*path", "r").unwrap();
+        router.insert("/s/:a/t/:c", "c").unwrap();
+        assert_eq!(router.at("/q/1").unwrap().params.get("uid"), Some("1"));
+        assert_eq!(router.at("/r/1/2").unwrap().params.get("path"), Some("1/2"));
+        assert_eq!(router.at("/s/1/t/2").unwrap().params.get("c"), Some("2"));
+        assert!(router.at("/q/1/x").is_none());
+    }
+
+    fn methods(routes: &[(Method, &'static str)]) -> Router<Methods<&'static str>> {
+        let mut router = Router::new();
+        for (method, pattern) in routes {
+            router.route(method.clone(), pattern, *pattern).unwrap();
+        }
+        router
+    }
+
+    #[test]
+    fn dispatch() {
+        let router = methods(&[
+            (Method::GET, "/users/:id"),
+            (Method::DELETE, "/users/:id"),
+            (Method::from_str("PURGE"), "/users/:id"),
+            (Method::POST, "/upload"),
+            (Method::GET, "/own"),
+            (Method::HEAD, "/own"),
+            (Method::OPTIONS, "/own"),
+        ]);
+        match router.dispatch(&Method::GET, "/users/7") {
+            Dispatch::Found(value, params) => assert_eq!((*value, params.get("id")), ("/users/:id", Some("7"))),
+            _ => panic!("GET"),
+        }
+        assert!(matches!(router.dispatch(&Method::from_str("PURGE"), "/users/7"), Dispatch::Found(_, _)));
+        assert!(matches!(router.dispatch(&Method::HEAD, "/users/7"), Dispatch::Head(_, _)));
+        assert!(matches!(router.dispatch(&Method::HEAD, "/own"), Dispatch::Found(_, _)));
+        assert!(matches!(router.dispatch(&Method::OPTIONS, "/own"), Dispatch::Found(_, _)));
+        assert!(matches!(router.dispatch(&Method::GET, "/nope"), Dispatch::NotFound));
+        match router.dispatch(&Method::PATCH, "/users/7") {
+            Dispatch::MethodNotAllowed(allow) => assert_eq!(allow, "GET, DELETE, PURGE, HEAD, OPTIONS"),
+            _ => panic!("PATCH"),
+        }
+        match router.dispatch(&Method::OPTIONS, "/users/7") {
+            Dispatch::Options(allow) => assert_eq!(allow, "GET, DELETE, PURGE, HEAD, OPTIONS"),
+            _ => panic!("OPTIONS"),
+        }
+        // No `GET`, no `HEAD`
+        match router.dispatch(&Method::HEAD, "/upload") {
+            Dispatch::MethodNotAllowed(allow) => assert_eq!(allow, "POST, OPTIONS"),
+            _ => panic!("HEAD"),
+        }
+        match router.dispatch(&Method::DELETE, "/own") {
+            Dispatch::MethodNotAllowed(allow) => assert_eq!(allow, "GET, HEAD, OPTIONS"),
+            _ => panic!("DELETE"),
+        }
+    }
+}
@@ -1,6 +1,22 @@
 use std::{error::Error, fmt::Display, io::Read};
 
-use crate::tls::TLSStream;
+use crate::{
+    http::{
+        account::SessionId,
+        cookie::CookieKeys,
+        file_cache::FileCache,
+        middleware::MiddlewareChain,
+        request::HTTPRequest,
+        response::{HTTPResponse, StatusCode},
+        router::{Methods, RouteTable},
+        site::Endpoint,
+        state::State,
+        template::Templates,
+        uri::PathOptions,
+    },
+    tls::TLSStream,
+    traits::New,
+};
 
 pub struct Server<T: ToServer> {
     inner: T,
@@ -39,18 +55,105 @@ impl Display for ServerError {
 
 impl Error for ServerError {}
 
+/// Shared by every request, handlers get it as `&Client`
+pub struct Client {
+    pub sessions: Vec<SessionId>,
+    /// Routes and error pages, swapped as a whole on reload, see `reload::Reloader`
+    pub routes: RouteTable<Methods<Endpoint>>,
+    pub middleware: MiddlewareChain,
+    pub state: State,
+    pub files: FileCache,
+    /// Compiled on first use, cleared on reload
+    pub templates: Templates,
+    /// For `CookieJar::signed` and `CookieJar::private`, set by `HTTPBuilder::client`
+    pub cookie_keys: Option<CookieKeys>,
+    /// How request paths are normalized before routing, set by `HTTPBuilder::client`
+    pub path_options: PathOptions,
+}
+
+impl Client {
+    /// Shorthand for `self.state.get()`
+    pub fn state<T: 'static>(&self) -> Option<&T> {
+        self.state.get()
+    }
+    /// The error page for `status` from the table `req` was dispatched with,
+    /// sent for routing errors, missing files and failed renders
+    pub fn error(&self, req: &HTTPRequest, status: StatusCode) -> HTTPResponse {
+        match req.routes {
+            Some(ref routes) => routes.errors.respond(req, self, status),
+            None => self.routes.load().errors.respond(req, self, status),
+        }
+    }
+}
+
+impl Default for Client {
+    fn default() -> Self {
+        Self {
+            sessions: Vec::new(),
+            routes: RouteTable::new(),
+            middleware: MiddlewareChain::new(),
+            state: State::new(),
+            files: FileCache::new(),
+            templates: Templates::new(),
+            cookie_keys: None,
+            path_options: PathOptions::new(),
+        }
+    }
+}
+
+impl New for Client {}
+
 pub struct TLS {}
 
 pub struct TLSBuilder {}
 
 pub struct HTTP {}
 
-pub struct HTTPBuilder {}
+pub struct HTTPBuilder {
+    cookie_keys: Option<CookieKeys>,
+    path_options: PathOptions,
+}
+
+impl HTTPBuilder {
+    /// Keys for signed and encrypted cookies
+    pub fn cookie_keys(mut self, keys: CookieKeys) -> HTTPBuilder {
+        self.cookie_keys = Some(keys);
+        self
+    }
+    pub fn get_cookie_keys(&self) -> Option<&CookieKeys> {
+        self.cookie_keys.as_ref()
+    }
+    /// The `Client` requests of this server are handled with
+    pub fn client(&self) -> Client {
+        Client {
+            cookie_keys: self.cookie_keys.clone(),
+            path_options: self.path_options.clone(),
+            ..Client::new()
+        }
+    }
+    /// How request paths are normalized before routing
+    pub fn path_options(mut self, opts: PathOptions) -> HTTPBuilder {
+        self.path_options = opts;
+        self
+    }
+    pub fn get_path_options(&self) -> &PathOptions {
+        &self.path_options
+    }
+}
+
+impl Default for HTTPBuilder {
+    fn default() -> Self {
+        Self {
+            cookie_keys: None,
+            path_options: PathOptions::default(),
+        }
+    }
+}
 
 impl ToServer for HTTP {
     type Builder = HTTPBuilder;
     fn new() -> Self::Builder {
-        HTTPBuilder {}
+        HTTPBuilder::default()
     }
     fn open(builder: HTTPBuilder) -> Result<(), ServerError> {
         loop {
@@ -69,7 +172,7 @@ impl ToServer for HTTP {
 impl ToServer for TLS {
     type Builder = HTTPBuilder;
     fn new() -> Self::Builder {
-        HTTPBuilder {}
+        HTTPBuilder::default()
     }
     fn open(builder: HTTPBuilder) -> Result<(), ServerError> {
         loop {
@@ -1,14 +1,21 @@
-use std::{fmt::Display, io::Read};
+use std::{path::Path, sync::Arc};
 
 use crate::{
     http::{
+        file_cache::Caching,
         request::HTTPRequest,
+        response::{Body, HTTPResponse, StatusCode},
+        method::Method,
+        router::{Methods, RouteError, Router},
         server::Client,
         utils::{ContType, HTML},
-        account::AuthLevel
+        account::AuthLevel,
+        extract::Handler,
+        static_dir::StaticDir,
+        template::{TemplateError, Value},
     },
-    openfile::openfile,
-    traits::{New, OpttoString},
+    log::{log, ERROR},
+    traits::New,
 };
 
 pub struct Site {
@@ -16,115 +23,121 @@ pub struct Site {
     pub path: Box<str>,
     pub auth: AuthLevel,
     pub site: SiteType,
+    /// Added to every response
+    pub headers: Vec<(String, String)>,
 }
 
 pub enum SiteType {
     StaticSite(SSite),
     ServerSideRenderedSite(SSRSite),
+    Template(TemplateSite),
 }
 
 pub struct SSite {
     pub file_path: Box<str>,
-    pub cache: bool,
-    pub cached: Option<Box<[u8]>>,
+    /// Whether the file goes through the `FileCache` of the `Client`
+    pub cache: Caching,
 }
 
 pub struct SSRSite {
-    pub genfunc: fn(&HTTPRequest, &Client) -> Vec<u8>,
+    pub genfunc: PageFn,
 }
 
-pub struct SiteConf {
-    auth_level: u8,
-    ct: ContType,
-    online_path: String,
-    ss: bool,
-    
-    gen: Option<fn(&HTTPRequest, &Client) -> Vec<u8>>,
-
-    file_path: Option<String>,
-    cache: Option<bool>,
+/// Renders `template` of the `Templates` in the `Client` with what `data` returns
+pub struct TemplateSite {
+    pub template: Box<str>,
+    pub data: DataFn,
 }
 
+/// The values a template page is rendered with
+pub type DataFn = Box<dyn Fn(&HTTPRequest, &Client) -> Value + Send + Sync>;
+
+/// Renders a body, closures may capture anything `Send + Sync`
+pub type PageFn = Box<dyn Fn(&HTTPRequest, &Client) -> Vec<u8> + Send + Sync>;
+
+/// Builds the whole response
+pub type ResponseFn = Box<dyn Fn(&HTTPRequest, &Client) -> HTTPResponse + Send + Sync>;
+
 impl Site {
-    fn ss_inner(&self) -> &SSite {
-        match &self.site {
-            SiteType::StaticSite(ss) => ss,
-            _ => unreachable!(),
+    /// Server side rendered site from a closure
+    pub fn rendered(
+        path: &str,
+        file_type: ContType,
+        auth: AuthLevel,
+        genfunc: impl Fn(&HTTPRequest, &Client) -> Vec<u8> + Send + Sync + 'static,
+    ) -> Site {
+        Site {
+            file_type,
+            path: path.into(),
+            auth,
+            site: SiteType::ServerSideRenderedSite(SSRSite {
+                genfunc: Box::new(genfunc),
+            }),
+            headers: Vec::new(),
         }
     }
-    fn ss_mut_inner(&mut self) -> &mut SSite {
-        match &mut self.site {
-            SiteType::StaticSite(ss) => ss,
-            _ => unreachable!(),
+    /// Page from a template file, `data` builds the values for each request
+    pub fn template(
+        path: &str,
+        auth: AuthLevel,
+        template: &str,
+        data: impl Fn(&HTTPRequest, &Client) -> Value + Send + Sync + 'static,
+    ) -> Site {
+        Site {
+            file_type: HTML,
+            path: path.into(),
+            auth,
+            site: SiteType::Template(TemplateSite {
+                template: template.into(),
+                data: Box::new(data),
+            }),
+            headers: Vec::new(),
         }
     }
-}
-
-impl SSite {
-    fn cache(site: &mut Site) {
-        if site.ss_inner().cache {
-            let mut file = openfile(
-                &site.ss_inner().file_path,
-                false,
-                true,
-                false,
-                false,
-            );
-            let mut v = Vec::with_capacity(file.metadata().expect("Idk I hate Windows. ITs it fault. Metadata.").len() as usize);
-            let _ = file.read_to_end(&mut v);
-            site.ss_mut_inner().cached = Some(v.into_boxed_slice());
-        } else {
-            site.ss_mut_inner().cached = None;
+    /// Static files are shared with the cache instead of copied, a missing file
+    /// or failed render gives an empty body
+    pub fn get_page(&self, req: &HTTPRequest, data: &Client) -> Body {
+        match &self.site {
+            SiteType::StaticSite(ss) => data
+                .files
+                .load(Path::new(ss.file_path.as_ref()), ss.cache)
+                .map(Body::Shared)
+                .unwrap_or_default(),
+            SiteType::ServerSideRenderedSite(ssrs) => Body::Owned((ssrs.genfunc)(req, data)),
+            SiteType::Template(ts) => ts
+                .render(req, data)
+                .map(|page| Body::Owned(page.into_bytes()))
+                .unwrap_or_default(),
+        }
+    }
+    /// Like `get_page`, a missing file is answered with the `404` page and a
+    /// failed render is logged and answered with the `500` page
+    pub fn get_resp(&self, req: &HTTPRequest, res: &Client) -> HTTPResponse {
+        let body = match &self.site {
+            SiteType::StaticSite(ss) => match res.files.load(Path::new(ss.file_path.as_ref()), ss.cache) {
+                Ok(file) => Body::Shared(file),
+                Err(_) => return res.error(req, StatusCode::NOTFOUND),
+            },
+            SiteType::Template(ts) => match ts.render(req, res) {
+                Ok(page) => Body::Owned(page.into_bytes()),
+                Err(e) => {
+                    log(ERROR, format!("Rendering {} failed: {}", self.path, e));
+                    // Template details stay in the log
+                    return res.error(req, StatusCode::INTERNALSERVERERROR);
+                }
+            },
+            _ => self.get_page(req, res),
         };
+        HTTPResponse::new()
+            .status_code(StatusCode::OK)
+            .body(body)
+            .ct(self.file_type.clone())
     }
 }
 
-impl Site {
-    pub fn read_all(sites: &Vec<SiteConf>) -> Vec<Site> {
-        let mut sites = sites
-            .iter()
-            .map(|site| {
-                if site.ss {
-                    let mut site = Site {
-                        file_type: site.ct.clone(),
-                        path: site.online_path.clone().into_boxed_str(),
-                        auth: site.auth_level,
-                        site: SiteType::StaticSite(SSite {
-                            file_path: site.file_path.clone().unwrap().into_boxed_str(),
-                            cache: site.cache.unwrap(),
-                            cached: None,
-                        }),
-                    };
-                    SSite::cache(&mut site);
-                    site
-                } else {
-                    Site {
-                        file_type: site.ct.clone(),
-                        path: site.online_path.clone().into_boxed_str(),
-                        auth: site.auth_level,
-                        site: SiteType::ServerSideRenderedSite(SSRSite {
-                            genfunc: site.gen.unwrap(),
-                        }),
-                    }
-                }
-            })
-            .collect::<Vec<Site>>();
-        sites.sort_by(|sitea, siteb| sitea.path.cmp(&siteb.path));
-        sites
-    }
-    pub fn get_page(&self, req: &HTTPRequest, data: &Client) -> Vec<u8> {
-        match &self.site {
-            SiteType::StaticSite(ss) => {
-                if ss.cache {
-                    ss.cached.clone().unwrap().to_vec()
-                } else {
-                    let mut v = Vec::new();
-                    let _ = openfile(&ss.file_path, false, true, false, false).read_to_end(&mut v);
-                    v
-                }
-            }
-            SiteType::ServerSideRenderedSite(ssrs) => (ssrs.genfunc.clone())(req, data),
-        }
+impl TemplateSite {
+    pub fn render(&self, req: &HTTPRequest, res: &Client) -> Result<String, TemplateError> {
+        res.templates.render(&self.template, &(self.data)(req, res))
     }
 }
 
@@ -136,84 +149,102 @@ impl Default for Site {
             auth: 255,
             site: SiteType::StaticSite(SSite {
                 file_path: "index.html".to_string().into_boxed_str(),
-                cache: false,
-                cached: None,
+                cache: Caching::Off,
             }),
+            headers: Vec::new(),
         }
     }
 }
 
 impl New for Site {}
 
-impl Display for SiteConf {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        if self.ss {
-            write!(
-                f,
-                "Real Path: {}; ContentType: {}; Is Static: {}; Auth Level: {}; is_cached: {}; FilePath: {}", self.online_path, self.ss, self.auth_level, self.cache.unwrap(), self.ct.as_str(), self.file_path.clone().unwrap()
-            )
-        } else {
-            write!(
-                f,
-                "Real Path: {}; ContentType: {}; Is Static: {}; Auth Level: {}; ",
-                self.online_path,
-                self.ct.as_str(),
-                self.ss,
-                self.auth_level
-            )
-        }
-    }
+pub struct Api {
+    pub path: Box<str>,
+    pub method: Method,
+    pub fnp: ApiFn,
+    pub filetype: ContType,
+    pub auth: AuthLevel,
+    /// Added to every response
+    pub headers: Vec<(String, String)>,
 }
 
-impl SiteConf {
-    pub fn from_tuple(
-        tup: &(
-            /*ContentType:*/ &str,
-            /*OnlinePath:*/ &str,
-            /*AuthorityLevel:*/ u8,
-            /*Is Static:*/ bool,
-            /*FilePath:*/ Option<&str>,
-            /*Should Cached:*/ Option<bool>,
-            /*OnlinePath:*/ Option<fn(&HTTPRequest, &Client) -> Vec<u8>>,
-        ),
-    ) -> SiteConf {
-        SiteConf {
-            ct: ContType::from_str(tup.0),
-            online_path: tup.1.to_string(),
-            auth_level: tup.2,
-            ss: tup.3,
-            gen: tup.6,
-            file_path: tup.4.to_string(),
-            cache: tup.5,
+pub enum ApiFn {
+    /// Body only, sent as `200 OK` with the filetype of the Api
+    Raw(PageFn),
+    /// Full response with own status code and headers
+    Response(ResponseFn),
+}
+
+impl Api {
+    pub fn get_resp(&self, req: &HTTPRequest, res: &Client)->HTTPResponse  {
+        match &self.fnp {
+            ApiFn::Raw(fnp) => HTTPResponse::new()
+                .status_code(StatusCode::OK)
+                .body(fnp(req, res))
+                .ct(self.filetype.clone()),
+            ApiFn::Response(fnp) => fnp(req, res),
         }
     }
+}
 
-    pub fn new(
-        im: Vec<(
-            /*ContentType:*/ &str,
-            /*OnlinePath:*/ &str,
-            /*AuthorityLevel:*/ u8,
-            /*Is Static:*/ bool,
-            /*FilePath:*/ Option<&str>,
-            /*Should Cached:*/ Option<bool>,
-            /*OnlinePath:*/ Option<fn(&HTTPRequest, &Client) -> Vec<u8>>,
-        )>,
-    ) -> Vec<SiteConf> {
-        im.iter()
-            .map(|site| SiteConf::from_tuple(site))
-            .collect::<Vec<SiteConf>>()
+impl ApiFn {
+    pub fn raw(fnp: impl Fn(&HTTPRequest, &Client)->Vec<u8> + Send + Sync + 'static) -> ApiFn {
+        ApiFn::Raw(Box::new(fnp))
+    }
+    pub fn response(fnp: impl Fn(&HTTPRequest, &Client)->HTTPResponse + Send + Sync + 'static) -> ApiFn {
+        ApiFn::Response(Box::new(fnp))
+    }
+    /// Arguments are extracted from the request, `|Path(id): Path<u32>, Query(q): Query<Querys>| ..`.
+    /// A failed extraction answers with its rejection instead
+    pub fn handler<Args: 'static>(fnp: impl Handler<Args>) -> ApiFn {
+        ApiFn::Response(Box::new(move |req, res| fnp.call(req, res)))
     }
 }
 
-pub struct Api {
-    pub path: Box<str>,
-    pub fnp: fn(&HTTPRequest, &Client)->Vec<u8>,
-    pub filetype: ContType,
-    pub auth: AuthLevel,
+impl From<fn(&HTTPRequest, &Client)->Vec<u8>> for ApiFn {
+    fn from(value: fn(&HTTPRequest, &Client)->Vec<u8>) -> Self {
+        ApiFn::Raw(Box::new(value))
+    }
+}
+/// What a route points to
+pub enum Endpoint {
+    Site(Site),
+    Api(Api),
+    Dir(Arc<StaticDir>),
 }
 
-impl Api {
-    pub fn get_resp(&self, req: &HTTPRequest, res: &Client)->Vec<u8>  {
-        (self.fnp)(req, res)
+impl Endpoint {
+    /// Sites are registered for `GET`, each Api for its own method. Paths may
+    /// contain `:param` and `*rest`
+    pub fn route_all(sites: Vec<Site>, apis: Vec<Api>) -> Result<Router<Methods<Endpoint>>, RouteError> {
+        let mut router = Router::new();
+        for site in sites {
+            let path = site.path.clone();
+            router.route(Method::GET, &path, Endpoint::Site(site))?;
+        }
+        for api in apis {
+            let path = api.path.clone();
+            router.route(api.method.clone(), &path, Endpoint::Api(api))?;
+        }
+        Ok(router)
+    }
+    /// Registers `dir` for `GET` on its prefix and everything below it
+    pub fn mount(router: &mut Router<Methods<Endpoint>>, dir: StaticDir) -> Result<(), RouteError> {
+        let dir = Arc::new(dir);
+        if !dir.prefix().is_empty() {
+            router.route(Method::GET, dir.prefix(), Endpoint::Dir(dir.clone()))?;
+        }
+        let pattern = format!("{}/*file", dir.prefix());
+        router.route(Method::GET, &pattern, Endpoint::Dir(dir))
+    }
+    pub fn get_resp(&self, req: &HTTPRequest, res: &Client) -> HTTPResponse {
+        let (response, headers) = match self {
+            Endpoint::Site(site) => (site.get_resp(req, res), &site.headers),
+            Endpoint::Api(api) => (api.get_resp(req, res), &api.headers),
+            Endpoint::Dir(dir) => (dir.get_resp(req, res), dir.headers()),
+        };
+        headers
+            .iter()
+            .fold(response, |response, (key, value)| response.header(key, value))
     }
 }
\ No newline at end of file
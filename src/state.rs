@@ -0,0 +1,89 @@
+use std::{
+    any::{Any, TypeId, type_name},
+    collections::HashMap,
+};
+
+use crate::traits::New;
+
+/// Application state by type, one value per type. Shared between all requests,
+/// so anything mutable needs its own `Mutex` or atomics
+pub struct State {
+    values: HashMap<TypeId, Box<dyn Any + Send + Sync>>,
+}
+
+impl State {
+    pub fn with<T: Send + Sync + 'static>(mut self, value: T) -> State {
+        self.insert(value);
+        self
+    }
+    /// Returns the value it replaced
+    pub fn insert<T: Send + Sync + 'static>(&mut self, value: T) -> Option<T> {
+        self.values
+            .insert(TypeId::of::<T>(), Box::new(value))
+            .and_then(|old| old.downcast().ok())
+            .map(|old| *old)
+    }
+    pub fn get<T: 'static>(&self) -> Option<&T> {
+        self.values
+            .get(&TypeId::of::<T>())
+            .and_then(|value| value.downcast_ref())
+    }
+    /// Like `get` but panics with the type name, for state registered at startup
+    pub fn expect<T: 'static>(&self) -> &T {
+        match self.get() {
+            Some(value) => value,
+            None => panic!("No state of type {}", type_name::<T>()),
+        }
+    }
+}
+
+impl Default for State {
+    fn default() -> Self {
+        Self {
+            values: HashMap::new(),
+        }
+    }
+}
+
+impl New for State {}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+
+    use super::*;
+
+    #[test]
+    fn by_type() {
+        let state = State::new().with(7u32).with(String::from("name"));
+        assert_eq!(state.get::<u32>(), Some(&7));
+        assert_eq!(state.get::<String>().map(String::as_str), Some("name"));
+        assert_eq!(state.get::<u64>(), None);
+        assert_eq!(state.get::<&str>(), None);
+        assert_eq!(*state.expect::<u32>(), 7);
+    }
+
+    #[test]
+    fn overwrite() {
+        let mut state = State::new();
+        assert_eq!(state.insert(1u8), None);
+        assert_eq!(state.insert(2u8), Some(1));
+        assert_eq!(state.get::<u8>(), Some(&2));
+        // Other types stay untouched
+        state.insert(3u16);
+        assert_eq!(state.get::<u8>(), Some(&2));
+    }
+
+    #[test]
+    fn shared() {
+        let state = State::new().with(Arc::new(Mutex::new(0)));
+        *state.expect::<Arc<Mutex<i32>>>().lock().unwrap() += 1;
+        assert_eq!(*state.expect::<Arc<Mutex<i32>>>().lock().unwrap(), 1);
+    }
+
+    #[test]
+    #[should_panic(expected = "No state of type u64")]
+    fn expect_missing() {
+        State::new().with(1u32).expect::<u64>();
+    }
+}
@@ -0,0 +1,428 @@
+use std::{
+    fmt::Write,
+    fs,
+    path::{Component, Path, PathBuf},
+};
+
+use crate::{
+    http::{
+        file_cache::Caching,
+        form::percent_decode,
+        request::HTTPRequest,
+        response::{Body, HTTPResponse, StatusCode},
+        server::Client,
+        template::html_escape,
+        uri::to_fs_path,
+        utils::{ContType, HTML, JSON},
+    },
+    traits::New,
+};
+
+/// What a directory without index file answers with
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Listing {
+    /// `404 Not Found`
+    Off,
+    Html,
+    /// `[{"name":"a.txt","dir":false,"size":12}]`
+    Json,
+}
+
+/// Serves the files below `root` under a URL prefix. Paths are mapped with
+/// `to_fs_path`, dotfiles are hidden and symlinks may only point inside `root`
+pub struct StaticDir {
+    prefix: Box<str>,
+    root: PathBuf,
+    index: Vec<Box<str>>,
+    listing: Listing,
+    hidden: bool,
+    caching: Caching,
+    headers: Vec<(String, String)>,
+}
+
+struct Entry {
+    name: String,
+    dir: bool,
+    size: u64,
+}
+
+impl StaticDir {
+    /// `StaticDir::mount("/assets", "./public")`, serves `index.html` and no listings.
+    /// `root` is resolved once here, a root which does not exist yet serves nothing
+    pub fn mount(prefix: &str, root: impl Into<PathBuf>) -> StaticDir {
+        let root = root.into();
+        StaticDir {
+            prefix: prefix.trim_end_matches('/').into(),
+            root: root.canonicalize().unwrap_or(root),
+            index: vec!["index.html".into()],
+            listing: Listing::Off,
+            hidden: false,
+            caching: Caching::Shared,
+            headers: Vec::new(),
+        }
+    }
+    /// Index files tried in order, empty to disable them
+    pub fn index(mut self, names: &[&str]) -> StaticDir {
+        self.index = names.iter().map(|name| (*name).into()).collect();
+        self
+    }
+    pub fn listing(mut self, listing: Listing) -> StaticDir {
+        self.listing = listing;
+        self
+    }
+    /// Also serve and list names starting with `.`
+    pub fn show_hidden(mut self, hidden: bool) -> StaticDir {
+        self.hidden = hidden;
+        self
+    }
+    /// How files go through the `FileCache` of the `Client`, `Caching::Shared` by default
+    pub fn caching(mut self, caching: Caching) -> StaticDir {
+        self.caching = caching;
+        self
+    }
+    /// Added to every response of the mount
+    pub fn header(mut self, key: &str, value: &str) -> StaticDir {
+        self.headers.push((key.to_string(), value.to_string()));
+        self
+    }
+    pub fn headers(&self) -> &Vec<(String, String)> {
+        &self.headers
+    }
+    /// Without trailing `/`, empty when mounted at `/`
+    pub fn prefix(&self) -> &str {
+        &self.prefix
+    }
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    /// The file a normalized request path points to, `None` if it is outside
+    /// the mount, hidden or escapes `root` through a symlink
+    pub fn resolve(&self, path: &str) -> Option<PathBuf> {
+        let rest = path.strip_prefix(self.prefix.as_ref())?;
+        if !rest.is_empty() && !rest.starts_with('/') {
+            return None;
+        }
+        let path = to_fs_path(&self.root, rest)?;
+        if !self.hidden && path.strip_prefix(&self.root).ok()?.components().any(is_hidden) {
+            return None;
+        }
+        self.contained(&path)
+    }
+
+    // Symlinks are followed, the target has to stay below `root`
+    fn contained(&self, path: &Path) -> Option<PathBuf> {
+        let real = path.canonicalize().ok()?;
+        real.starts_with(&self.root).then_some(real)
+    }
+
+    /// Misses are answered with the `404` page of `res`
+    pub fn get_resp(&self, req: &HTTPRequest, res: &Client) -> HTTPResponse {
+        let path = &req.parts.uri.path;
+        let real = match self.resolve(path) {
+            Some(real) => real,
+            None => return res.error(req, StatusCode::NOTFOUND),
+        };
+        if !real.is_dir() {
+            return self.file(req, res, &real);
+        }
+        if !path.ends_with('/') {
+            // Relative links of the index and listing need the slash
+            let mut location = format!("{}/", path);
+            if req.parts.uri.query.is_present() {
+                location.push('?');
+                location.push_str(req.parts.uri.query.raw());
+            }
+            return HTTPResponse::new()
+                .status_code(StatusCode::MOVEDPERMANENTLY)
+                .header("Location", &location);
+        }
+        for name in self.index.iter() {
+            if let Some(index) = self.contained(&real.join(name.as_ref())) {
+                if index.is_file() {
+                    return self.file(req, res, &index);
+                }
+            }
+        }
+        match self.listing {
+            Listing::Off => res.error(req, StatusCode::NOTFOUND),
+            Listing::Html => {
+                let parent = path.len() > self.prefix.len() + 1;
+                HTTPResponse::new()
+                    .status_code(StatusCode::OK)
+                    .ct(HTML)
+                    .body(html_listing(path, parent, &self.entries(&real)).into_bytes())
+            }
+            Listing::Json => HTTPResponse::new()
+                .status_code(StatusCode::OK)
+                .ct(JSON)
+                .body(json_listing(&self.entries(&real)).into_bytes()),
+        }
+    }
+
+    // Sorted by name, without hidden entries and symlinks leaving `root`
+    fn entries(&self, dir: &Path) -> Vec<Entry> {
+        let mut entries: Vec<Entry> = match fs::read_dir(dir) {
+            Ok(read) => read
+                .filter_map(|entry| {
+                    let entry = entry.ok()?;
+                    let name = entry.file_name().into_string().ok()?;
+                    if !self.hidden && name.starts_with('.') {
+                        return None;
+                    }
+                    let meta = self.contained(&entry.path())?.metadata().ok()?;
+                    Some(Entry {
+                        name,
+                        dir: meta.is_dir(),
+                        size: if meta.is_dir() { 0 } else { meta.len() },
+                    })
+                })
+                .collect(),
+            Err(_) => Vec::new(),
+        };
+        entries.sort_by(|a, b| a.name.cmp(&b.name));
+        entries
+    }
+
+    fn file(&self, req: &HTTPRequest, res: &Client, path: &Path) -> HTTPResponse {
+        match res.files.load(path, self.caching) {
+            Ok(body) => HTTPResponse::new()
+                .status_code(StatusCode::OK)
+                .ct(ContType::from_extension(
+                    path.extension().and_then(|ext| ext.to_str()).unwrap_or(""),
+                ))
+                .body(Body::Shared(body)),
+            Err(_) => res.error(req, StatusCode::NOTFOUND),
+        }
+    }
+}
+
+fn is_hidden(component: Component) -> bool {
+    component.as_os_str().to_str().is_none_or(|name| name.starts_with('.'))
+}
+
+fn href(name: &str) -> String {
+    let mut out = String::new();
+    for b in name.bytes() {
+        if b.is_ascii_alphanumeric() || matches!(b, b'-' | b'.' | b'_' | b'~') {
+            out.push(b as char);
+        } else {
+            let _ = write!(out, "%{:02X}", b);
+        }
+    }
+    out
+}
+
+fn json_escape(src: &str) -> String {
+    let mut out = String::with_capacity(src.len());
+    for c in src.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            c if (c as u32) < 0x20 => {
+                let _ = write!(out, "\\u{:04x}", c as u32);
+            }
+            c => out.push(c),
+        }
+    }
+    out
+}
+
+fn html_listing(path: &str, parent: bool, entries: &[Entry]) -> String {
+    let title = html_escape(&String::from_utf8_lossy(&percent_decode(path.as_bytes(), false)));
+    let mut out = format!(
+        "<!DOCTYPE html>\n<html><head><meta charset=\"utf-8\"><title>Index of {0}</title></head>\n<body><h1>Index of {0}</h1>\n<ul>\n",
+        title
+    );
+    if parent {
+        out.push_str("<li><a href=\"../\">../</a></li>\n");
+    }
+    for entry in entries {
+        let slash = if entry.dir { "/" } else { "" };
+        let _ = writeln!(
+            out,
+            "<li><a href=\"{}{slash}\">{}{slash}</a></li>",
+            href(&entry.name),
+            html_escape(&entry.name)
+        );
+    }
+    out.push_str("</ul></body></html>\n");
+    out
+}
+
+fn json_listing(entries: &[Entry]) -> String {
+    let entries: Vec<String> = entries
+        .iter()
+        .map(|entry| {
+            format!(
+                "{{\"name\":\"{}\",\"dir\":{},\"size\":{}}}",
+                json_escape(&entry.name),
+                entry.dir,
+                entry.size
+            )
+        })
+        .collect();
+    format!("[{}]", entries.join(","))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Removed again on drop
+    struct TempRoot(PathBuf);
+
+    impl Drop for TempRoot {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.0);
+        }
+    }
+
+    fn tree(name: &str) -> TempRoot {
+        let base = std::env::temp_dir().join(format!("static-dir-{}-{}", std::process::id(), name));
+        let _ = fs::remove_dir_all(&base);
+        for dir in ["root/sub", "root/docs", "root/both", "root/a/.git", "outside"] {
+            fs::create_dir_all(base.join(dir)).unwrap();
+        }
+        for (path, content) in [
+            ("root/a.txt", "a"),
+            ("root/.env", "secret"),
+            ("root/a/.git/config", "secret"),
+            ("root/sub/index.html", "<p>i</p>"),
+            ("root/both/index.html", "index"),
+            ("root/both/home.htm", "home"),
+            ("root/docs/<x>&\"", "12"),
+            ("outside/secret.txt", "secret"),
+        ] {
+            fs::write(base.join(path), content).unwrap();
+        }
+        TempRoot(base)
+    }
+
+    fn get(dir: &StaticDir, res: &Client, target: &str) -> HTTPResponse {
+        let mut req = HTTPRequest::new();
+        let src = format!("GET {} HTTP/1.1\r\nHost: x\r\n\r\n", target);
+        assert!(req.from_string(&src, res).is_none(), "{}", target);
+        dir.get_resp(&req, res)
+    }
+
+    fn header<'r>(response: &'r HTTPResponse, key: &str) -> Option<&'r str> {
+        response.rsheader.fields.iter().find(|(k, _)| k == key).map(|(_, v)| v.as_str())
+    }
+
+    #[test]
+    fn traversal() {
+        let tmp = tree("traversal");
+        let dir = StaticDir::mount("/assets", tmp.0.join("root"));
+        assert!(dir.resolve("/assets/a.txt").is_some());
+        for path in [
+            "/assets/../outside/secret.txt",
+            "/assets/%2e%2e/outside/secret.txt",
+            "/assets/%2E%2E/outside/secret.txt",
+            "/assets/..%2Foutside%2Fsecret.txt",
+            "/assets/..%2foutside/secret.txt",
+            "/assets/.env",
+            "/assets/%2eenv",
+            "/assets/a/.git/config",
+            "/assets/missing",
+        ] {
+            assert_eq!(dir.resolve(path), None, "{}", path);
+        }
+        let res = Client::new();
+        for target in ["/assets/../outside/secret.txt", "/assets/%2e%2e/outside/secret.txt", "/assets/a/.git/config"] {
+            let response = get(&dir, &res, target);
+            assert_eq!(response.rsheader.status_code, StatusCode::NOTFOUND, "{}", target);
+        }
+        // Encoded slashes do not even reach the mount
+        let mut req = HTTPRequest::new();
+        assert!(req.from_string(&"GET /assets/..%2Fsecret HTTP/1.1\r\nHost: x\r\n\r\n".to_string(), &res).is_some());
+        // Only with `show_hidden`
+        let dir = dir.show_hidden(true);
+        assert!(dir.resolve("/assets/a/.git/config").is_some());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn symlinks() {
+        let tmp = tree("symlinks");
+        std::os::unix::fs::symlink(tmp.0.join("outside/secret.txt"), tmp.0.join("root/leak")).unwrap();
+        std::os::unix::fs::symlink(tmp.0.join("outside"), tmp.0.join("root/out")).unwrap();
+        std::os::unix::fs::symlink(tmp.0.join("root/a.txt"), tmp.0.join("root/inner")).unwrap();
+        let dir = StaticDir::mount("/assets", tmp.0.join("root")).listing(Listing::Json);
+        assert_eq!(dir.resolve("/assets/leak"), None);
+        assert_eq!(dir.resolve("/assets/out/secret.txt"), None);
+        assert_eq!(dir.resolve("/assets/inner"), Some(tmp.0.join("root/a.txt").canonicalize().unwrap()));
+        let body = get(&dir, &Client::new(), "/assets/").body;
+        let listing = String::from_utf8_lossy(&body);
+        assert!(listing.contains("\"inner\"") && !listing.contains("leak") && !listing.contains("\"out\""), "{}", listing);
+    }
+
+    #[test]
+    fn prefix() {
+        let tmp = tree("prefix");
+        let dir = StaticDir::mount("/assets/", tmp.0.join("root"));
+        assert_eq!(dir.prefix(), "/assets");
+        assert_eq!(dir.resolve("/assetsX/a.txt"), None);
+        assert_eq!(dir.resolve("/assets.txt"), None);
+        assert_eq!(dir.resolve("/other/a.txt"), None);
+        assert_eq!(dir.resolve("/assets"), Some(tmp.0.join("root").canonicalize().unwrap()));
+        let dir = StaticDir::mount("/", tmp.0.join("root"));
+        assert!(dir.resolve("/a.txt").is_some());
+    }
+
+    #[test]
+    fn index_and_redirect() {
+        let tmp = tree("index");
+        let res = Client::new();
+        let dir = StaticDir::mount("/assets", tmp.0.join("root"));
+        let response = get(&dir, &res, "/assets/sub/");
+        assert_eq!(response.rsheader.status_code, StatusCode::OK);
+        assert_eq!(&*response.body, b"<p>i</p>");
+        assert_eq!(header(&response, "Content-Type"), Some("text/html"));
+        assert_eq!(&*get(&dir, &res, "/assets/both/").body, b"index");
+        let dir = dir.index(&["home.htm", "index.html"]);
+        assert_eq!(&*get(&dir, &res, "/assets/both/").body, b"home");
+        // Without index and listing
+        assert_eq!(get(&dir, &res, "/assets/docs/").rsheader.status_code, StatusCode::NOTFOUND);
+
+        let response = get(&dir, &res, "/assets/sub");
+        assert_eq!(response.rsheader.status_code, StatusCode::MOVEDPERMANENTLY);
+        assert_eq!(header(&response, "Location"), Some("/assets/sub/"));
+        let response = get(&dir, &res, "/assets/sub?a=1&b=%20");
+        assert_eq!(header(&response, "Location"), Some("/assets/sub/?a=1&b=%20"));
+        let response = get(&dir, &res, "/assets/sub?");
+        assert_eq!(header(&response, "Location"), Some("/assets/sub/?"));
+    }
+
+    #[test]
+    fn listings() {
+        let tmp = tree("listings");
+        let res = Client::new();
+        let dir = StaticDir::mount("/assets", tmp.0.join("root")).listing(Listing::Html);
+        let html = String::from_utf8(get(&dir, &res, "/assets/docs/").body.into_vec()).unwrap();
+        assert!(html.contains("<title>Index of /assets/docs/</title>"), "{}", html);
+        assert!(html.contains("<li><a href=\"../\">../</a></li>"), "{}", html);
+        assert!(html.contains("<li><a href=\"%3Cx%3E%26%22\">&lt;x&gt;&amp;&quot;</a></li>"), "{}", html);
+        let html = String::from_utf8(get(&dir, &res, "/assets/").body.into_vec()).unwrap();
+        assert!(!html.contains("../") && !html.contains(".env"), "{}", html);
+        assert!(html.contains("<a href=\"docs/\">docs/</a>"), "{}", html);
+
+        let dir = StaticDir::mount("/assets", tmp.0.join("root")).listing(Listing::Json);
+        let response = get(&dir, &res, "/assets/docs/");
+        assert_eq!(header(&response, "Content-Type"), Some("application/json"));
+        assert_eq!(&*response.body, br#"[{"name":"<x>&\"","dir":false,"size":2}]"#);
+    }
+
+    #[test]
+    fn cached() {
+        let tmp = tree("cached");
+        let res = Client::new();
+        let dir = StaticDir::mount("/assets", tmp.0.join("root"));
+        assert_eq!(&*get(&dir, &res, "/assets/a.txt").body, b"a");
+        assert_eq!(res.files.len(), 1);
+        let dir = StaticDir::mount("/assets", tmp.0.join("root")).caching(Caching::Off);
+        res.files.clear();
+        assert_eq!(&*get(&dir, &res, "/assets/a.txt").body, b"a");
+        assert!(res.files.is_empty());
+    }
+}
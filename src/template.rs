@@ -0,0 +1,1004 @@
+// {{ value }} escaped, {{ value | raw }} as is, {# comment #}
+// {% if a %} {% elif not b %} {% else %} {% endif %}, also `a == "x"` and `a != 1`
+// {% for item in list %} {% endfor %}, `loop.index`, `loop.first` and `loop.last` inside
+// {% include "name" %}, {% extends "name" %} as first tag, {% block name %} {% endblock %}
+
+use std::{
+    collections::{BTreeMap, HashMap},
+    error::Error,
+    fmt::Display,
+    fs,
+    path::Path,
+    sync::{Arc, RwLock},
+};
+
+use crate::traits::New;
+
+/// Data a template renders, missing values render as `Null`
+#[derive(Clone, Debug, PartialEq, Default)]
+pub enum Value {
+    #[default]
+    Null,
+    Bool(bool),
+    Int(i64),
+    Float(f64),
+    Str(String),
+    List(Vec<Value>),
+    Map(BTreeMap<String, Value>),
+}
+
+/// A parsed template, rendered through `Templates` so includes and layouts resolve
+pub struct Template {
+    name: String,
+    nodes: Vec<Node>,
+    extends: Option<(String, Loc)>,
+    blocks: HashMap<String, Vec<Node>>,
+}
+
+/// Compiled templates by name. Names which were not inserted are read as file
+/// paths on first use, relative names in a file resolve against its directory
+pub struct Templates {
+    compiled: RwLock<HashMap<String, Arc<Template>>>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TemplateError {
+    kind: TemplateErrorKind,
+    template: String,
+    line: usize,
+    col: usize,
+    detail: String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TemplateErrorKind {
+    Io,
+    Syntax,
+    UnknownTag,
+    Unclosed,
+    DuplicateBlock,
+    NotFound,
+    Recursion,
+    Render,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Loc {
+    line: usize,
+    col: usize,
+}
+
+enum Node {
+    Text(String),
+    Output { expr: Expr, raw: bool, loc: Loc },
+    If { branches: Vec<(Expr, Vec<Node>)>, otherwise: Vec<Node> },
+    For { var: String, expr: Expr, body: Vec<Node>, loc: Loc },
+    Include { name: String, loc: Loc },
+    Block(String),
+}
+
+#[derive(Debug)]
+enum Expr {
+    Path(Vec<String>),
+    Literal(Value),
+    Not(Box<Expr>),
+    Compare(Box<Expr>, Box<Expr>, bool),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Tok {
+    Ident(String),
+    Str(String),
+    Num(Value),
+    Op(&'static str),
+}
+
+enum Seg {
+    Text(String),
+    Output(Vec<Tok>, Loc),
+    Tag(String, Vec<Tok>, Loc),
+}
+
+const MAX_DEPTH: usize = 32;
+
+impl TemplateError {
+    fn new(kind: TemplateErrorKind, template: &str, loc: Loc, detail: impl Display) -> TemplateError {
+        TemplateError {
+            kind,
+            template: template.to_string(),
+            line: loc.line,
+            col: loc.col,
+            detail: detail.to_string(),
+        }
+    }
+    pub fn kind(&self) -> TemplateErrorKind {
+        self.kind
+    }
+    pub fn template(&self) -> &str {
+        &self.template
+    }
+    /// 1-based, 0 if the template could not be read
+    pub fn line(&self) -> usize {
+        self.line
+    }
+    pub fn col(&self) -> usize {
+        self.col
+    }
+    pub fn detail(&self) -> &str {
+        &self.detail
+    }
+}
+
+impl TemplateErrorKind {
+    pub fn reason(&self) -> &str {
+        match self {
+            TemplateErrorKind::Io => "Could not read Template",
+            TemplateErrorKind::Syntax => "Syntax Error",
+            TemplateErrorKind::UnknownTag => "Unknown Tag",
+            TemplateErrorKind::Unclosed => "Unclosed Tag",
+            TemplateErrorKind::DuplicateBlock => "Duplicate Block",
+            TemplateErrorKind::NotFound => "Template not found",
+            TemplateErrorKind::Recursion => "Too deeply nested",
+            TemplateErrorKind::Render => "Render Error",
+        }
+    }
+}
+
+impl Display for TemplateError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} in {}:{}:{}: {}",
+            self.kind.reason(),
+            self.template,
+            self.line,
+            self.col,
+            self.detail
+        )
+    }
+}
+
+impl Error for TemplateError {}
+
+impl Value {
+    /// An empty map, filled with `with`
+    pub fn map() -> Value {
+        Value::Map(BTreeMap::new())
+    }
+    /// Sets `key`, turns anything but a map into one first
+    pub fn with(mut self, key: &str, value: impl Into<Value>) -> Value {
+        if !matches!(self, Value::Map(_)) {
+            self = Value::map();
+        }
+        if let Value::Map(ref mut map) = self {
+            map.insert(key.to_string(), value.into());
+        }
+        self
+    }
+    pub fn get(&self, key: &str) -> Option<&Value> {
+        match self {
+            Value::Map(map) => map.get(key),
+            Value::List(list) => list.get(key.parse::<usize>().ok()?),
+            _ => None,
+        }
+    }
+    pub fn is_truthy(&self) -> bool {
+        match self {
+            Value::Null => false,
+            Value::Bool(b) => *b,
+            Value::Int(i) => *i != 0,
+            Value::Float(f) => *f != 0.0,
+            Value::Str(s) => !s.is_empty(),
+            Value::List(list) => !list.is_empty(),
+            Value::Map(map) => !map.is_empty(),
+        }
+    }
+    fn kind(&self) -> &str {
+        match self {
+            Value::Null => "null",
+            Value::Bool(_) => "boolean",
+            Value::Int(_) | Value::Float(_) => "number",
+            Value::Str(_) => "string",
+            Value::List(_) => "list",
+            Value::Map(_) => "map",
+        }
+    }
+}
+
+impl From<bool> for Value {
+    fn from(value: bool) -> Self {
+        Value::Bool(value)
+    }
+}
+
+macro_rules! int_value {
+    ($($t:ty),*) => {
+        $(impl From<$t> for Value {
+            fn from(value: $t) -> Self {
+                Value::Int(value as i64)
+            }
+        })*
+    };
+}
+
+int_value!(i8, i16, i32, i64, u8, u16, u32, u64, usize, isize);
+
+impl From<f64> for Value {
+    fn from(value: f64) -> Self {
+        Value::Float(value)
+    }
+}
+
+impl From<&str> for Value {
+    fn from(value: &str) -> Self {
+        Value::Str(value.to_string())
+    }
+}
+
+impl From<String> for Value {
+    fn from(value: String) -> Self {
+        Value::Str(value)
+    }
+}
+
+impl<T: Into<Value>> From<Vec<T>> for Value {
+    fn from(value: Vec<T>) -> Self {
+        Value::List(value.into_iter().map(Into::into).collect())
+    }
+}
+
+impl<T: Into<Value>> From<Option<T>> for Value {
+    fn from(value: Option<T>) -> Self {
+        value.map(Into::into).unwrap_or(Value::Null)
+    }
+}
+
+impl<V: Into<Value>> From<BTreeMap<String, V>> for Value {
+    fn from(value: BTreeMap<String, V>) -> Self {
+        Value::Map(value.into_iter().map(|(k, v)| (k, v.into())).collect())
+    }
+}
+
+impl<V: Into<Value>> From<HashMap<String, V>> for Value {
+    fn from(value: HashMap<String, V>) -> Self {
+        Value::Map(value.into_iter().map(|(k, v)| (k, v.into())).collect())
+    }
+}
+
+#[cfg(feature = "json")]
+impl From<serde_json::Value> for Value {
+    fn from(value: serde_json::Value) -> Self {
+        match value {
+            serde_json::Value::Null => Value::Null,
+            serde_json::Value::Bool(b) => Value::Bool(b),
+            serde_json::Value::Number(n) => match n.as_i64() {
+                Some(i) => Value::Int(i),
+                None => Value::Float(n.as_f64().unwrap_or(0.0)),
+            },
+            serde_json::Value::String(s) => Value::Str(s),
+            serde_json::Value::Array(list) => list.into(),
+            serde_json::Value::Object(map) => {
+                Value::Map(map.into_iter().map(|(k, v)| (k, v.into())).collect())
+            }
+        }
+    }
+}
+
+pub fn html_escape(src: &str) -> String {
+    let mut out = String::with_capacity(src.len());
+    for c in src.chars() {
+        match c {
+            '&' => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '"' => out.push_str("&quot;"),
+            '\'' => out.push_str("&#39;"),
+            c => out.push(c),
+        }
+    }
+    out
+}
+
+fn loc_at(src: &str, pos: usize) -> Loc {
+    let before = &src[..pos];
+    let line_start = before.rfind('\n').map(|i| i + 1).unwrap_or(0);
+    Loc {
+        line: before.matches('\n').count() + 1,
+        col: before[line_start..].chars().count() + 1,
+    }
+}
+
+fn tokenize(src: &str, name: &str, loc: Loc) -> Result<Vec<Tok>, TemplateError> {
+    let syntax = |detail: String| TemplateError::new(TemplateErrorKind::Syntax, name, loc, detail);
+    let mut toks = Vec::new();
+    let mut chars = src.char_indices().peekable();
+    while let Some(&(i, c)) = chars.peek() {
+        if c.is_whitespace() {
+            chars.next();
+        } else if c == '"' {
+            chars.next();
+            let mut s = String::new();
+            loop {
+                match chars.next() {
+                    Some((_, '"')) => break,
+                    Some((_, '\\')) => match chars.next() {
+                        Some((_, c @ ('"' | '\\'))) => s.push(c),
+                        Some((_, 'n')) => s.push('\n'),
+                        _ => return Err(syntax("Invalid escape in string".to_string())),
+                    },
+                    Some((_, c)) => s.push(c),
+                    None => return Err(syntax("Unterminated string".to_string())),
+                }
+            }
+            toks.push(Tok::Str(s));
+        } else if c.is_ascii_digit() || (c == '-' && src[i + 1..].starts_with(|c: char| c.is_ascii_digit())) {
+            let end = src[i + 1..]
+                .find(|c: char| !(c.is_ascii_digit() || c == '.'))
+                .map(|e| i + 1 + e)
+                .unwrap_or(src.len());
+            let word = &src[i..end];
+            let num = if let Ok(int) = word.parse() {
+                Value::Int(int)
+            } else if let Ok(float) = word.parse() {
+                Value::Float(float)
+            } else {
+                return Err(syntax(format!("Invalid number \"{}\"", word)));
+            };
+            toks.push(Tok::Num(num));
+            while chars.peek().is_some_and(|&(j, _)| j < end) {
+                chars.next();
+            }
+        } else if c.is_alphabetic() || c == '_' {
+            let end = src[i..]
+                .find(|c: char| !(c.is_alphanumeric() || c == '_' || c == '.'))
+                .map(|e| i + e)
+                .unwrap_or(src.len());
+            toks.push(Tok::Ident(src[i..end].to_string()));
+            while chars.peek().is_some_and(|&(j, _)| j < end) {
+                chars.next();
+            }
+        } else {
+            let op = ["==", "!=", "|", ","]
+                .into_iter()
+                .find(|op| src[i..].starts_with(op))
+                .ok_or_else(|| syntax(format!("Unexpected \"{}\"", c)))?;
+            toks.push(Tok::Op(op));
+            for _ in 0..op.len() {
+                chars.next();
+            }
+        }
+    }
+    Ok(toks)
+}
+
+
+fn segments(src: &str, name: &str) -> Result<Vec<Seg>, TemplateError> {
+    let mut segs = Vec::new();
+    // Start of the pending text, `pos` also skips lone `{`
+    let mut start = 0;
+    let mut pos = 0;
+    while let Some(found) = src[pos..].find('{').map(|i| pos + i) {
+        let close = match src.as_bytes().get(found + 1) {
+            Some(b'{') => "}}",
+            Some(b'%') => "%}",
+            Some(b'#') => "#}",
+            _ => {
+                pos = found + 1;
+                continue;
+            }
+        };
+        if start < found {
+            segs.push(Seg::Text(src[start..found].to_string()));
+        }
+        let loc = loc_at(src, found);
+        let end = src[found + 2..].find(close).map(|i| found + 2 + i).ok_or_else(|| {
+            TemplateError::new(TemplateErrorKind::Unclosed, name, loc, format!("Missing \"{}\"", close))
+        })?;
+        let inner = &src[found + 2..end];
+        match close {
+            "}}" => segs.push(Seg::Output(tokenize(inner, name, loc)?, loc)),
+            "%}" => {
+                let mut toks = tokenize(inner, name, loc)?.into_iter();
+                match toks.next() {
+                    Some(Tok::Ident(keyword)) => segs.push(Seg::Tag(keyword, toks.collect(), loc)),
+                    _ => {
+                        return Err(TemplateError::new(
+                            TemplateErrorKind::Syntax,
+                            name,
+                            loc,
+                            "Expected a tag name",
+                        ));
+                    }
+                }
+            }
+            _ => {}
+        }
+        pos = end + 2;
+        start = pos;
+    }
+    if start < src.len() {
+        segs.push(Seg::Text(src[start..].to_string()));
+    }
+    Ok(segs)
+}
+
+struct Parser<'s> {
+    name: &'s str,
+    segs: std::vec::IntoIter<Seg>,
+    blocks: HashMap<String, Vec<Node>>,
+}
+
+// Closing tags which end the nodes of an enclosing tag
+const ENDS: [&str; 5] = ["elif", "else", "endif", "endfor", "endblock"];
+
+// The closing tag with its arguments
+type Closing = (String, Vec<Tok>, Loc);
+
+impl Parser<'_> {
+    fn error(&self, kind: TemplateErrorKind, loc: Loc, detail: impl Display) -> TemplateError {
+        TemplateError::new(kind, self.name, loc, detail)
+    }
+
+    // Nodes up to one of `ends`, returns that tag. `None` at the end of the template
+    fn nodes(&mut self, ends: &[&str]) -> Result<(Vec<Node>, Option<Closing>), TemplateError> {
+        let mut nodes = Vec::new();
+        while let Some(seg) = self.segs.next() {
+            match seg {
+                Seg::Text(text) => nodes.push(Node::Text(text)),
+                Seg::Output(toks, loc) => {
+                    let mut parts = toks.split(|tok| *tok == Tok::Op("|"));
+                    let expr = self.expr(parts.next().unwrap_or_default(), loc)?;
+                    let mut raw = false;
+                    for filter in parts {
+                        match filter {
+                            [Tok::Ident(f)] if f == "raw" => raw = true,
+                            [Tok::Ident(f)] if f == "escape" => raw = false,
+                            _ => return Err(self.error(TemplateErrorKind::Syntax, loc, "Unknown filter, expected raw or escape")),
+                        }
+                    }
+                    nodes.push(Node::Output { expr, raw, loc });
+                }
+                Seg::Tag(keyword, toks, loc) => {
+                    if ends.contains(&keyword.as_str()) {
+                        return Ok((nodes, Some((keyword, toks, loc))));
+                    }
+                    nodes.push(self.tag(keyword, toks, loc)?);
+                }
+            }
+        }
+        Ok((nodes, None))
+    }
+
+    // Nodes which have to be closed by `ends`, `open` is reported if they are not
+    fn closed(&mut self, ends: &[&str], open: &str, loc: Loc) -> Result<(Vec<Node>, String, Vec<Tok>), TemplateError> {
+        match self.nodes(ends)? {
+            (nodes, Some((keyword, toks, _))) => Ok((nodes, keyword, toks)),
+            (_, None) => Err(self.error(
+                TemplateErrorKind::Unclosed,
+                loc,
+                format!("{{% {} %}} without {{% {} %}}", open, ends[ends.len() - 1]),
+            )),
+        }
+    }
+
+    fn tag(&mut self, keyword: String, toks: Vec<Tok>, loc: Loc) -> Result<Node, TemplateError> {
+        match keyword.as_str() {
+            "if" => {
+                let mut branches = Vec::new();
+                let mut cond = self.expr(&toks, loc)?;
+                loop {
+                    let (body, end, toks) = self.closed(&["elif", "else", "endif"], "if", loc)?;
+                    branches.push((cond, body));
+                    match end.as_str() {
+                        "elif" => cond = self.expr(&toks, loc)?,
+                        "else" => {
+                            let (otherwise, _, _) = self.closed(&["endif"], "else", loc)?;
+                            return Ok(Node::If { branches, otherwise });
+                        }
+                        _ => {
+                            return Ok(Node::If {
+                                branches,
+                                otherwise: Vec::new(),
+                            });
+                        }
+                    }
+                }
+            }
+            "for" => {
+                let (var, expr) = match toks.as_slice() {
+                    [Tok::Ident(var), Tok::Ident(kw), rest @ ..] if kw == "in" && !var.contains('.') => {
+                        (var.clone(), self.expr(rest, loc)?)
+                    }
+                    _ => return Err(self.error(TemplateErrorKind::Syntax, loc, "Expected {% for name in list %}")),
+                };
+                let (body, _, _) = self.closed(&["endfor"], "for", loc)?;
+                Ok(Node::For { var, expr, body, loc })
+            }
+            "include" => match toks.as_slice() {
+                [Tok::Str(name)] => Ok(Node::Include {
+                    name: name.clone(),
+                    loc,
+                }),
+                _ => Err(self.error(TemplateErrorKind::Syntax, loc, "Expected {% include \"name\" %}")),
+            },
+            "block" => {
+                let name = match toks.as_slice() {
+                    [Tok::Ident(name)] if !name.contains('.') => name.clone(),
+                    _ => return Err(self.error(TemplateErrorKind::Syntax, loc, "Expected {% block name %}")),
+                };
+                let (body, _, _) = self.closed(&["endblock"], "block", loc)?;
+                if self.blocks.insert(name.clone(), body).is_some() {
+                    return Err(self.error(TemplateErrorKind::DuplicateBlock, loc, &name));
+                }
+                Ok(Node::Block(name))
+            }
+            "extends" => Err(self.error(TemplateErrorKind::Syntax, loc, "{% extends %} has to be the first tag")),
+            keyword if ENDS.contains(&keyword) => Err(self.error(
+                TemplateErrorKind::Syntax,
+                loc,
+                format!("Unexpected {{% {} %}}", keyword),
+            )),
+            keyword => Err(self.error(TemplateErrorKind::UnknownTag, loc, keyword)),
+        }
+    }
+
+    fn expr(&self, toks: &[Tok], loc: Loc) -> Result<Expr, TemplateError> {
+        let at = toks.iter().position(|tok| matches!(tok, Tok::Op("==" | "!=")));
+        match at {
+            Some(at) => Ok(Expr::Compare(
+                Box::new(self.unary(&toks[..at], loc)?),
+                Box::new(self.unary(&toks[at + 1..], loc)?),
+                toks[at] == Tok::Op("!="),
+            )),
+            None => self.unary(toks, loc),
+        }
+    }
+
+    fn unary(&self, toks: &[Tok], loc: Loc) -> Result<Expr, TemplateError> {
+        match toks {
+            [Tok::Ident(not), rest @ ..] if not == "not" => Ok(Expr::Not(Box::new(self.unary(rest, loc)?))),
+            [Tok::Str(s)] => Ok(Expr::Literal(Value::Str(s.clone()))),
+            [Tok::Num(n)] => Ok(Expr::Literal(n.clone())),
+            [Tok::Ident(word)] => Ok(match word.as_str() {
+                "true" => Expr::Literal(Value::Bool(true)),
+                "false" => Expr::Literal(Value::Bool(false)),
+                "null" => Expr::Literal(Value::Null),
+                path => {
+                    if path.split('.').any(str::is_empty) {
+                        return Err(self.error(TemplateErrorKind::Syntax, loc, format!("Invalid name \"{}\"", path)));
+                    }
+                    Expr::Path(path.split('.').map(str::to_string).collect())
+                }
+            }),
+            [] => Err(self.error(TemplateErrorKind::Syntax, loc, "Expected a value")),
+            _ => Err(self.error(TemplateErrorKind::Syntax, loc, "Expected a single value")),
+        }
+    }
+}
+
+impl Template {
+    pub fn compile(name: &str, src: &str) -> Result<Template, TemplateError> {
+        let mut segs = segments(src, name)?;
+        // Only whitespace may come before `extends`
+        let first_tag = segs
+            .iter()
+            .position(|seg| !matches!(seg, Seg::Text(text) if text.trim().is_empty()));
+        let extends = match first_tag.map(|i| (i, &segs[i])) {
+            Some((i, Seg::Tag(keyword, toks, loc))) if keyword == "extends" => match toks.as_slice() {
+                [Tok::Str(parent)] => {
+                    let extends = (parent.clone(), *loc);
+                    segs.drain(..=i);
+                    Some(extends)
+                }
+                _ => {
+                    return Err(TemplateError::new(
+                        TemplateErrorKind::Syntax,
+                        name,
+                        *loc,
+                        "Expected {% extends \"name\" %}",
+                    ));
+                }
+            },
+            _ => None,
+        };
+        let mut parser = Parser {
+            name,
+            segs: segs.into_iter(),
+            blocks: HashMap::new(),
+        };
+        let nodes = match parser.nodes(&[])? {
+            (nodes, None) => nodes,
+            (_, Some((keyword, _, loc))) => {
+                return Err(parser.error(TemplateErrorKind::Syntax, loc, format!("Unexpected {{% {} %}}", keyword)));
+            }
+        };
+        Ok(Template {
+            name: name.to_string(),
+            nodes,
+            extends,
+            blocks: parser.blocks,
+        })
+    }
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+    /// Includes and layouts are read as files, inserted ones need `Templates::render`
+    pub fn render(&self, data: &Value) -> Result<String, TemplateError> {
+        let templates = Templates::new();
+        let mut renderer = Renderer::new(&templates, data);
+        renderer.template(self)?;
+        Ok(renderer.out)
+    }
+}
+
+impl Templates {
+    /// Compiles `src` under `name`, replacing an earlier template of that name
+    pub fn insert(&self, name: &str, src: &str) -> Result<(), TemplateError> {
+        let template = Arc::new(Template::compile(name, src)?);
+        self.write().insert(name.to_string(), template);
+        Ok(())
+    }
+    /// The compiled template, a file is read and compiled on first use
+    pub fn get(&self, name: &str) -> Result<Arc<Template>, TemplateError> {
+        if let Some(template) = self.read().get(name) {
+            return Ok(template.clone());
+        }
+        let src = fs::read_to_string(name).map_err(|e| {
+            TemplateError::new(TemplateErrorKind::Io, name, Loc { line: 0, col: 0 }, e)
+        })?;
+        let template = Arc::new(Template::compile(name, &src)?);
+        self.write().insert(name.to_string(), template.clone());
+        Ok(template)
+    }
+    pub fn render(&self, name: &str, data: &Value) -> Result<String, TemplateError> {
+        let template = self.get(name)?;
+        let mut renderer = Renderer::new(self, data);
+        renderer.template(&template)?;
+        Ok(renderer.out)
+    }
+    /// Drops every compiled template, files are read again on next use
+    pub fn clear(&self) {
+        self.write().clear();
+    }
+
+    fn read(&self) -> std::sync::RwLockReadGuard<'_, HashMap<String, Arc<Template>>> {
+        self.compiled.read().unwrap_or_else(|e| e.into_inner())
+    }
+    fn write(&self) -> std::sync::RwLockWriteGuard<'_, HashMap<String, Arc<Template>>> {
+        self.compiled.write().unwrap_or_else(|e| e.into_inner())
+    }
+
+    // `name` as referenced from the template `from`, `None` if it does not exist
+    fn resolve(&self, from: &str, name: &str) -> Result<Option<Arc<Template>>, TemplateError> {
+        if let Some(template) = self.read().get(name) {
+            return Ok(Some(template.clone()));
+        }
+        let relative = Path::new(from)
+            .parent()
+            .filter(|dir| !dir.as_os_str().is_empty() && Path::new(name).is_relative())
+            .map(|dir| dir.join(name).to_string_lossy().into_owned());
+        for candidate in relative.iter().map(String::as_str).chain([name]) {
+            if self.read().contains_key(candidate) || Path::new(candidate).is_file() {
+                return self.get(candidate).map(Some);
+            }
+        }
+        Ok(None)
+    }
+}
+
+impl Default for Templates {
+    fn default() -> Self {
+        Self {
+            compiled: RwLock::new(HashMap::new()),
+        }
+    }
+}
+
+impl New for Templates {}
+
+struct Renderer<'r> {
+    templates: &'r Templates,
+    data: &'r Value,
+    scopes: Vec<(String, Value)>,
+    out: String,
+    depth: usize,
+}
+
+fn equal(a: &Value, b: &Value) -> bool {
+    match (a, b) {
+        (Value::Int(a), Value::Float(b)) | (Value::Float(b), Value::Int(a)) => *a as f64 == *b,
+        (a, b) => a == b,
+    }
+}
+
+impl<'r> Renderer<'r> {
+    fn new(templates: &'r Templates, data: &'r Value) -> Renderer<'r> {
+        Renderer {
+            templates,
+            data,
+            scopes: Vec::new(),
+            out: String::new(),
+            depth: 0,
+        }
+    }
+
+    fn find(&self, from: &Template, name: &str, loc: Loc) -> Result<Arc<Template>, TemplateError> {
+        self.templates.resolve(&from.name, name)?.ok_or_else(|| {
+            TemplateError::new(TemplateErrorKind::NotFound, &from.name, loc, name)
+        })
+    }
+
+    // Renders the outermost layout of `template`, blocks come from the most derived template
+    fn template(&mut self, template: &Template) -> Result<(), TemplateError> {
+        let mut parents: Vec<Arc<Template>> = Vec::new();
+        let mut child = template;
+        while let Some((ref parent, loc)) = child.extends {
+            if parents.len() >= MAX_DEPTH {
+                return Err(TemplateError::new(TemplateErrorKind::Recursion, &child.name, loc, "Layouts extend each other"));
+            }
+            let parent = self.find(child, parent, loc)?;
+            parents.push(parent);
+            child = parents.last().map(Arc::as_ref).unwrap_or(template);
+        }
+        let mut chain = vec![template];
+        chain.extend(parents.iter().map(Arc::as_ref));
+        let root = chain[chain.len() - 1];
+        self.nodes(&root.nodes, &chain, root)
+    }
+
+    fn nodes(&mut self, nodes: &[Node], chain: &[&Template], current: &Template) -> Result<(), TemplateError> {
+        for node in nodes {
+            match node {
+                Node::Text(text) => self.out.push_str(text),
+                Node::Output { expr, raw, loc } => {
+                    let text = match self.eval(expr) {
+                        Value::Null => String::new(),
+                        Value::Bool(b) => b.to_string(),
+                        Value::Int(i) => i.to_string(),
+                        Value::Float(f) => f.to_string(),
+                        Value::Str(s) => s,
+                        other => {
+                            return Err(TemplateError::new(
+                                TemplateErrorKind::Render,
+                                &current.name,
+                                *loc,
+                                format!("Cannot output a {}", other.kind()),
+                            ));
+                        }
+                    };
+                    if *raw {
+                        self.out.push_str(&text);
+                    } else {
+                        self.out.push_str(&html_escape(&text));
+                    }
+                }
+                Node::If { branches, otherwise } => {
+                    let body = branches
+                        .iter()
+                        .find(|(cond, _)| self.eval(cond).is_truthy())
+                        .map(|(_, body)| body)
+                        .unwrap_or(otherwise);
+                    self.nodes(body, chain, current)?;
+                }
+                Node::For { var, expr, body, loc } => {
+                    let items = match self.eval(expr) {
+                        Value::List(items) => items,
+                        Value::Map(map) => map
+                            .into_iter()
+                            .map(|(key, value)| Value::map().with("key", key).with("value", value))
+                            .collect(),
+                        Value::Null => Vec::new(),
+                        other => {
+                            return Err(TemplateError::new(
+                                TemplateErrorKind::Render,
+                                &current.name,
+                                *loc,
+                                format!("Cannot loop over a {}", other.kind()),
+                            ));
+                        }
+                    };
+                    let length = items.len();
+                    for (i, item) in items.into_iter().enumerate() {
+                        let info = Value::map()
+                            .with("index", i + 1)
+                            .with("index0", i)
+                            .with("first", i == 0)
+                            .with("last", i + 1 == length)
+                            .with("length", length);
+                        self.scopes.push((var.clone(), item));
+                        self.scopes.push(("loop".to_string(), info));
+                        let result = self.nodes(body, chain, current);
+                        self.scopes.truncate(self.scopes.len() - 2);
+                        result?;
+                    }
+                }
+                Node::Include { name, loc } => {
+                    if self.depth >= MAX_DEPTH {
+                        return Err(TemplateError::new(TemplateErrorKind::Recursion, &current.name, *loc, name));
+                    }
+                    let included = self.find(current, name, *loc)?;
+                    self.depth += 1;
+                    let result = self.template(&included);
+                    self.depth -= 1;
+                    result?;
+                }
+                Node::Block(name) => {
+                    if let Some((owner, body)) = chain
+                        .iter()
+                        .find_map(|template| template.blocks.get(name).map(|body| (*template, body)))
+                    {
+                        self.nodes(body, chain, owner)?;
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn lookup(&self, path: &[String]) -> Option<&Value> {
+        let (first, rest) = path.split_first()?;
+        let mut value = self
+            .scopes
+            .iter()
+            .rev()
+            .find(|(name, _)| name == first)
+            .map(|(_, value)| value)
+            .or_else(|| self.data.get(first))?;
+        for key in rest {
+            value = value.get(key)?;
+        }
+        Some(value)
+    }
+
+    fn eval(&self, expr: &Expr) -> Value {
+        match expr {
+            Expr::Path(path) => self.lookup(path).cloned().unwrap_or_default(),
+            Expr::Literal(value) => value.clone(),
+            Expr::Not(expr) => Value::Bool(!self.eval(expr).is_truthy()),
+            Expr::Compare(a, b, negated) => Value::Bool(equal(&self.eval(a), &self.eval(b)) != *negated),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn data() -> Value {
+        Value::map()
+            .with("title", "<Hi & 'you'>")
+            .with("html", "<b>x</b>")
+            .with("user", Value::map().with("name", "Ann").with("admin", true))
+            .with("items", vec!["a", "b", "c"])
+            .with("n", 3)
+            .with("f", 1.5)
+            .with("none", Option::<i32>::None)
+    }
+
+    fn render(src: &str) -> Result<String, TemplateError> {
+        Template::compile("t", src)?.render(&data())
+    }
+
+    fn error(src: &str) -> (TemplateErrorKind, usize, usize) {
+        let e = render(src).unwrap_err();
+        (e.kind(), e.line(), e.col())
+    }
+
+    #[test]
+    fn escaping() {
+        assert_eq!(render("{{ title }}").unwrap(), "&lt;Hi &amp; &#39;you&#39;&gt;");
+        assert_eq!(render("{{ html | raw }}").unwrap(), "<b>x</b>");
+        assert_eq!(render("{{ html | raw | escape }}").unwrap(), "&lt;b&gt;x&lt;/b&gt;");
+        assert_eq!(render("{{ \"\\\"q\\\"\" }}").unwrap(), "&quot;q&quot;");
+        assert_eq!(render("{{ user.name }}{{ missing.x }}{# {{ title }} #}!").unwrap(), "Ann!");
+        assert_eq!(render("{{ n }} {{ f }} {{ user.admin }}").unwrap(), "3 1.5 true");
+        assert_eq!(render("a { b } {c").unwrap(), "a { b } {c");
+    }
+
+    #[test]
+    fn conditionals() {
+        assert_eq!(render("{% if user.admin %}A{% elif n %}B{% else %}C{% endif %}").unwrap(), "A");
+        assert_eq!(render("{% if not user.admin %}A{% elif n == 3 %}B{% else %}C{% endif %}").unwrap(), "B");
+        assert_eq!(render("{% if n != 3 %}A{% elif none %}B{% else %}C{% endif %}").unwrap(), "C");
+        assert_eq!(render("{% if user.name == \"Ann\" %}y{% endif %}{% if n == 3.0 %}z{% endif %}").unwrap(), "yz");
+        assert_eq!(render("{% if missing %}x{% endif %}-").unwrap(), "-");
+    }
+
+    #[test]
+    fn loops() {
+        let src = "{% for i in items %}{{ loop.index }}{{ i }}{% if not loop.last %},{% endif %}{% endfor %}";
+        assert_eq!(render(src).unwrap(), "1a,2b,3c");
+        assert_eq!(render("{% for e in user %}{{ e.key }}={{ e.value }};{% endfor %}").unwrap(), "admin=true;name=Ann;");
+        assert_eq!(render("{% for i in none %}x{% endfor %}-").unwrap(), "-");
+        let nested = "{% for i in items %}{% for j in items %}{% if i == j %}{{ i }}{{ loop.index0 }}{% endif %}{% endfor %}{% endfor %}";
+        assert_eq!(render(nested).unwrap(), "a0b1c2");
+        // The loop variable is gone after the loop
+        assert_eq!(render("{% for n in items %}{% endfor %}{{ n }}").unwrap(), "3");
+    }
+
+    #[test]
+    fn layouts() {
+        let templates = Templates::new();
+        templates
+            .insert(
+                "base",
+                "<title>{% block title %}Site{% endblock %}</title>{% block body %}{% endblock %}{% include \"foot\" %}",
+            )
+            .unwrap();
+        templates
+            .insert("mid", "{% extends \"base\" %}{% block body %}[{% block inner %}mid{% endblock %}]{% endblock %}")
+            .unwrap();
+        templates
+            .insert(
+                "page",
+                "\n{% extends \"mid\" %}{% block title %}{{ user.name }}{% endblock %}{% block inner %}{% for i in items %}{% include \"item\" %}{% endfor %}{% endblock %}",
+            )
+            .unwrap();
+        templates.insert("item", "<{{ i }}>").unwrap();
+        templates.insert("foot", "|{{ n }}").unwrap();
+        assert_eq!(templates.render("page", &data()).unwrap(), "<title>Ann</title>[<a><b><c>]|3");
+        assert_eq!(templates.render("mid", &data()).unwrap(), "<title>Site</title>[mid]|3");
+
+        templates.insert("missing", "\n{% include \"nope\" %}").unwrap();
+        let e = templates.render("missing", &data()).unwrap_err();
+        assert_eq!((e.kind(), e.template(), e.line()), (TemplateErrorKind::NotFound, "missing", 2));
+        assert_eq!(templates.render("nofile", &data()).unwrap_err().kind(), TemplateErrorKind::Io);
+    }
+
+    #[test]
+    fn relative_files() {
+        let dir = std::env::temp_dir().join(format!("templates-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("layout.html"), "<main>{% block main %}{% endblock %}</main>").unwrap();
+        fs::write(
+            dir.join("post.html"),
+            "{% extends \"layout.html\" %}{% block main %}{{ title }}{% include \"part.html\" %}{% endblock %}",
+        )
+        .unwrap();
+        fs::write(dir.join("part.html"), "!").unwrap();
+        let post = dir.join("post.html").display().to_string();
+        let templates = Templates::new();
+        let rendered = templates.render(&post, &Value::map().with("title", "a&b"));
+        fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(rendered.unwrap(), "<main>a&amp;b!</main>");
+    }
+
+    #[test]
+    fn recursion() {
+        let templates = Templates::new();
+        templates.insert("self", "{% include \"self\" %}").unwrap();
+        templates.insert("a", "{% include \"b\" %}").unwrap();
+        templates.insert("b", "{% include \"a\" %}").unwrap();
+        templates.insert("x", "{% extends \"y\" %}").unwrap();
+        templates.insert("y", "{% extends \"x\" %}").unwrap();
+        for name in ["self", "a", "x"] {
+            assert_eq!(templates.render(name, &data()).unwrap_err().kind(), TemplateErrorKind::Recursion);
+        }
+        // Deep but finite nesting still renders
+        for depth in 0..MAX_DEPTH - 1 {
+            templates.insert(&format!("d{}", depth), &format!("{{% include \"d{}\" %}}", depth + 1)).unwrap();
+        }
+        templates.insert(&format!("d{}", MAX_DEPTH - 1), "end").unwrap();
+        assert_eq!(templates.render("d0", &data()).unwrap(), "end");
+    }
+
+    #[test]
+    fn errors() {
+        assert_eq!(error("ab\n  {% if n %}x"), (TemplateErrorKind::Unclosed, 2, 3));
+        assert_eq!(error("a\n{{ n "), (TemplateErrorKind::Unclosed, 2, 1));
+        assert_eq!(error("äö {% frob %}"), (TemplateErrorKind::UnknownTag, 1, 4));
+        assert_eq!(error("\n\n{% endif %}"), (TemplateErrorKind::Syntax, 3, 1));
+        assert_eq!(error("\n {{ items }}"), (TemplateErrorKind::Render, 2, 2));
+        assert_eq!(error("{% for i in n %}{% endfor %}"), (TemplateErrorKind::Render, 1, 1));
+        assert_eq!(error("{{ n | upper }}"), (TemplateErrorKind::Syntax, 1, 1));
+        assert_eq!(error("x\n{% for in items %}{% endfor %}"), (TemplateErrorKind::Syntax, 2, 1));
+        assert_eq!(
+            error("{% block a %}{% endblock %}\n{% block a %}{% endblock %}"),
+            (TemplateErrorKind::DuplicateBlock, 2, 1)
+        );
+        assert_eq!(error("x{% extends \"b\" %}"), (TemplateErrorKind::Syntax, 1, 2));
+        let e = render("ab\n  {% if n %}x").unwrap_err();
+        assert_eq!(e.template(), "t");
+        assert!(e.to_string().contains("2:3"), "{}", e);
+    }
+}
@@ -0,0 +1,167 @@
+use crate::traits::New;
+
+use super::{
+    Port, Uri, UriError,
+    parse::{is_pchar, is_sub_delim, is_unreserved},
+};
+
+/// Builds a `Uri` from decoded components, everything which is not allowed
+/// in a component is percent-encoded
+pub struct UriBuilder {
+    scheme: Option<String>,
+    userinfo: Option<String>,
+    host: Option<String>,
+    port: Option<Port>,
+    path: String,
+    query: Option<String>,
+    fragment: Option<String>,
+}
+
+fn encode(src: &str, allowed: impl Fn(u8) -> bool, out: &mut String) {
+    for b in src.bytes() {
+        if allowed(b) {
+            out.push(b as char);
+        } else {
+            out.push_str(&format!("%{:02X}", b));
+        }
+    }
+}
+
+fn is_query_char(b: u8) -> bool {
+    // `&`, `=` and `+` are taken by the query format
+    (is_pchar(b) || b == b'/' || b == b'?') && !matches!(b, b'&' | b'=' | b'+')
+}
+
+impl Default for UriBuilder {
+    fn default() -> Self {
+        Self {
+            scheme: None,
+            userinfo: None,
+            host: None,
+            port: None,
+            path: String::new(),
+            query: None,
+            fragment: None,
+        }
+    }
+}
+
+impl New for UriBuilder {}
+
+impl UriBuilder {
+    pub fn scheme(mut self, scheme: &str) -> Self {
+        self.scheme = Some(scheme.to_string());
+        self
+    }
+    pub fn userinfo(mut self, username: &str, password: Option<&str>) -> Self {
+        let allowed = |b| is_unreserved(b) || is_sub_delim(b);
+        let mut s = String::new();
+        encode(username, allowed, &mut s);
+        if let Some(password) = password {
+            s.push(':');
+            encode(password, allowed, &mut s);
+        }
+        self.userinfo = Some(s);
+        self
+    }
+    /// IP-literals are passed with their brackets, `[::1]`
+    pub fn host(mut self, host: &str) -> Self {
+        let mut s = String::new();
+        if host.starts_with('[') {
+            s.push_str(host);
+        } else {
+            encode(host, |b| is_unreserved(b) || is_sub_delim(b), &mut s);
+        }
+        self.host = Some(s);
+        self
+    }
+    pub fn port(mut self, port: Port) -> Self {
+        self.port = Some(port);
+        self
+    }
+    /// Replaces the path, `/` separates segments
+    pub fn path(mut self, path: &str) -> Self {
+        self.path.clear();
+        encode(path, |b| is_pchar(b) || b == b'/', &mut self.path);
+        self
+    }
+    /// Appends one segment, a `/` inside it is encoded
+    pub fn segment(mut self, segment: &str) -> Self {
+        if !self.path.ends_with('/') {
+            self.path.push('/');
+        }
+        encode(segment, is_pchar, &mut self.path);
+        self
+    }
+    /// Appends `key=value`
+    pub fn query(mut self, key: &str, value: &str) -> Self {
+        let query = self.query.get_or_insert_with(String::new);
+        if !query.is_empty() {
+            query.push('&');
+        }
+        encode(key, is_query_char, query);
+        query.push('=');
+        encode(value, is_query_char, query);
+        self
+    }
+    /// Appends a parameter without value, `?debug`
+    pub fn flag(mut self, key: &str) -> Self {
+        let query = self.query.get_or_insert_with(String::new);
+        if !query.is_empty() {
+            query.push('&');
+        }
+        encode(key, is_query_char, query);
+        self
+    }
+    pub fn fragment(mut self, fragment: &str) -> Self {
+        let mut s = String::new();
+        encode(fragment, |b| is_pchar(b) || b == b'/' || b == b'?', &mut s);
+        self.fragment = Some(s);
+        self
+    }
+    /// Fails for an invalid scheme or host, the rest is always encoded correctly
+    pub fn build(self) -> Result<Uri, UriError> {
+        let mut s = String::new();
+        if let Some(scheme) = self.scheme {
+            s.push_str(&scheme);
+            s.push(':');
+        }
+        let has_authority = self.host.is_some() || self.userinfo.is_some() || self.port.is_some();
+        if has_authority {
+            s.push_str("//");
+            if let Some(userinfo) = self.userinfo {
+                s.push_str(&userinfo);
+                s.push('@');
+            }
+            s.push_str(self.host.as_deref().unwrap_or(""));
+            if let Some(port) = self.port {
+                s.push_str(&format!(":{}", port));
+            }
+            if !self.path.is_empty() && !self.path.starts_with('/') {
+                s.push('/');
+            }
+        } else if self.path.starts_with("//") {
+            // Would be read as an authority
+            s.push_str("/.");
+        } else if s.is_empty() && self.path.split('/').next().unwrap().contains(':') {
+            // Would be read as a scheme
+            s.push_str("./");
+        }
+        s.push_str(&self.path);
+        if let Some(query) = self.query {
+            s.push('?');
+            s.push_str(&query);
+        }
+        if let Some(fragment) = self.fragment {
+            s.push('#');
+            s.push_str(&fragment);
+        }
+        Uri::parse(&s)
+    }
+}
+
+impl Uri {
+    pub fn builder() -> UriBuilder {
+        UriBuilder::new()
+    }
+}
@@ -0,0 +1,290 @@
+use std::{
+    fmt::Display,
+    net::{Ipv4Addr, Ipv6Addr},
+};
+
+use crate::{http::form::percent_decode, traits::New};
+
+use super::{
+    UriError, UriErrorKind,
+    normalize::normalize_encoding,
+    parse::{check, is_sub_delim, is_unreserved},
+};
+
+/// RFC 3986 3.2.2, reg-names are lowercased
+#[derive(Clone, PartialEq, Eq, Hash, Debug)]
+pub enum Host {
+    Ipv4(Ipv4Addr),
+    /// Address and the RFC 6874 zone id (`[fe80::1%25eth0]`)
+    Ipv6(Ipv6Addr, Option<String>),
+    /// `v1.something`, without brackets
+    IpFuture(String),
+    /// Internationalized labels are stored as Punycode, `xn--mller-kva`
+    RegName(String),
+}
+
+fn ip_literal(src: &str) -> Result<Host, UriError> {
+    // src is without the brackets, positions are relative to the `[`
+    let err = |pos| Err(UriError::new(UriErrorKind::InvalidHost, pos));
+    if let Some(rest) = src.strip_prefix(['v', 'V']) {
+        let (version, addr) = match rest.split_once('.') {
+            Some(parts) => parts,
+            None => return err(1),
+        };
+        if version.is_empty() || !version.bytes().all(|b| b.is_ascii_hexdigit()) {
+            return err(2);
+        }
+        if addr.is_empty() {
+            return err(src.len() + 1);
+        }
+        if let Some(i) = addr
+            .bytes()
+            .position(|b| !(is_unreserved(b) || is_sub_delim(b) || b == b':'))
+        {
+            return err(version.len() + 3 + i);
+        }
+        return Ok(Host::IpFuture(format!(
+            "v{}.{}",
+            version.to_ascii_lowercase(),
+            addr
+        )));
+    }
+    let (addr, zone) = match src.split_once("%25") {
+        Some((addr, zone)) => {
+            if zone.is_empty() {
+                return err(src.len() + 1);
+            }
+            let pos = addr.len() + 4;
+            check(zone, is_unreserved, UriErrorKind::InvalidHost).map_err(|e| e.offset(pos))?;
+            let zone = normalize_encoding(zone)
+                .map_err(|e| UriError::new(UriErrorKind::InvalidHost, pos + e.pos()))?;
+            (addr, Some(zone))
+        }
+        None => (src, None),
+    };
+    match addr.parse::<Ipv6Addr>() {
+        Ok(ip) => Ok(Host::Ipv6(ip, zone)),
+        Err(_) => err(1),
+    }
+}
+
+// IDNA 2008 via the UTS 46 mapping, the result is lowercase ASCII with Punycode labels
+fn idna_to_ascii(src: &str) -> Result<String, UriError> {
+    idna::domain_to_ascii(src).map_err(|_| UriError::new(UriErrorKind::InvalidHost, 0))
+}
+
+fn has_ace_label(src: &str) -> bool {
+    src.split('.')
+        .any(|label| label.get(..4).is_some_and(|p| p.eq_ignore_ascii_case("xn--")))
+}
+
+// Lowercases everything but the hex digits of escapes
+fn lowercase(src: &str) -> String {
+    let mut out = String::with_capacity(src.len());
+    let mut escape = 0;
+    for c in src.chars() {
+        if c == '%' {
+            escape = 2;
+            out.push(c);
+        } else if escape > 0 {
+            escape -= 1;
+            out.push(c);
+        } else {
+            out.push(c.to_ascii_lowercase());
+        }
+    }
+    out
+}
+
+impl Host {
+    pub fn parse(src: &str) -> Result<Host, UriError> {
+        if let Some(inner) = src.strip_prefix('[') {
+            return match inner.strip_suffix(']') {
+                Some(inner) => ip_literal(inner),
+                None => Err(UriError::new(UriErrorKind::InvalidHost, src.len())),
+            };
+        }
+        if let Ok(ip) = src.parse::<Ipv4Addr>() {
+            return Ok(Host::Ipv4(ip));
+        }
+        // Unicode only comes from configuration or the Host header, never from a parsed URI
+        if !src.is_ascii() {
+            return idna_to_ascii(src).map(Host::RegName);
+        }
+        // Anything else, like `1.2.3.256`, is a reg-name
+        check(
+            src,
+            |b| is_unreserved(b) || is_sub_delim(b),
+            UriErrorKind::InvalidHost,
+        )?;
+        if !src.contains('%') && !has_ace_label(src) {
+            return Ok(Host::RegName(src.to_ascii_lowercase()));
+        }
+        let name = normalize_encoding(src)
+            .map_err(|e| UriError::new(UriErrorKind::InvalidHost, e.pos()))?;
+        // `m%C3%BCller.de` and `xn--mller-kva.de` are the same domain
+        if let Ok(decoded) = String::from_utf8(percent_decode(name.as_bytes(), false)) {
+            if !decoded.is_ascii() || has_ace_label(&decoded) {
+                return idna_to_ascii(&decoded).map(Host::RegName);
+            }
+        }
+        Ok(Host::RegName(lowercase(&name)))
+    }
+
+    /// Checks `src` like `parse`, only escapes, zone ids and IDNA hosts allocate
+    pub(super) fn validate(src: &str) -> Result<(), UriError> {
+        if src.starts_with('[') || !src.is_ascii() || src.contains('%') || has_ace_label(src) {
+            return Host::parse(src).map(|_| ());
+        }
+        // IPv4 addresses are valid reg-names as well
+        check(
+            src,
+            |b| is_unreserved(b) || is_sub_delim(b),
+            UriErrorKind::InvalidHost,
+        )
+    }
+
+    /// The reg-name with Punycode labels decoded, for display only
+    pub fn to_unicode(&self) -> String {
+        match self {
+            Host::RegName(name) => idna::domain_to_unicode(name).0,
+            host => host.to_string(),
+        }
+    }
+
+    pub fn is_ip(&self) -> bool {
+        matches!(self, Host::Ipv4(_) | Host::Ipv6(..))
+    }
+
+    pub fn is_empty(&self) -> bool {
+        matches!(self, Host::RegName(name) if name.is_empty())
+    }
+}
+
+impl Default for Host {
+    fn default() -> Self {
+        Host::RegName(String::new())
+    }
+}
+
+impl New for Host {}
+
+impl Display for Host {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Host::Ipv4(ip) => write!(f, "{}", ip),
+            Host::Ipv6(ip, None) => write!(f, "[{}]", ip),
+            Host::Ipv6(ip, Some(zone)) => write!(f, "[{}%25{}]", ip, zone),
+            Host::IpFuture(future) => write!(f, "[{}]", future),
+            Host::RegName(name) => write!(f, "{}", name),
+        }
+    }
+}
+
+/// Compares with a textual host, after parsing and normalizing it.
+/// `other` is parsed on every call, compare parsed hosts per request
+impl PartialEq<str> for Host {
+    fn eq(&self, other: &str) -> bool {
+        match Host::parse(other) {
+            Ok(other) => *self == other,
+            Err(_) => false,
+        }
+    }
+}
+
+impl PartialEq<&str> for Host {
+    fn eq(&self, other: &&str) -> bool {
+        *self == **other
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn round_trip(src: &str) -> String {
+        let host = Host::parse(src).unwrap();
+        assert_eq!(Host::parse(&host.to_string()).unwrap(), host, "{}", src);
+        host.to_string()
+    }
+
+    #[test]
+    fn ipv4() {
+        assert_eq!(
+            Host::parse("127.0.0.1").unwrap(),
+            Host::Ipv4(Ipv4Addr::LOCALHOST)
+        );
+        assert_eq!(round_trip("10.0.0.255"), "10.0.0.255");
+        // Not an address, but still a valid reg-name
+        assert_eq!(
+            Host::parse("1.2.3.256").unwrap(),
+            Host::RegName("1.2.3.256".into())
+        );
+        assert!(Host::parse("10.0.0.1").unwrap().is_ip());
+    }
+
+    #[test]
+    fn ipv6() {
+        assert_eq!(round_trip("[::1]"), "[::1]");
+        assert_eq!(round_trip("[2001:DB8:0:0::1]"), "[2001:db8::1]");
+        let host = Host::parse("[fe80::1%25eth0]").unwrap();
+        assert_eq!(
+            host,
+            Host::Ipv6("fe80::1".parse().unwrap(), Some("eth0".into()))
+        );
+        assert_eq!(round_trip("[fe80::1%25eth0]"), "[fe80::1%25eth0]");
+        // Escapes in the zone id are normalized
+        assert_eq!(round_trip("[fe80::1%25e%74h%2d0]"), "[fe80::1%25eth-0]");
+        assert!(host.is_ip());
+        for src in [
+            "[::1",
+            "[::1%25]",
+            "[::1%eth0]",
+            "[::1%25a/b]",
+            "[1::2::3]",
+            "[127.0.0.1]",
+        ] {
+            assert!(Host::parse(src).is_err(), "{}", src);
+        }
+    }
+
+    #[test]
+    fn ip_future() {
+        assert_eq!(
+            Host::parse("[v1.a:b]").unwrap(),
+            Host::IpFuture("v1.a:b".into())
+        );
+        assert_eq!(round_trip("[VF.Abc!]"), "[vf.Abc!]");
+        assert!(!Host::parse("[v1.x]").unwrap().is_ip());
+        for src in ["[v1]", "[v.x]", "[vg.x]", "[v1.]", "[v1.a/b]"] {
+            assert!(Host::parse(src).is_err(), "{}", src);
+        }
+    }
+
+    #[test]
+    fn reg_name() {
+        assert_eq!(round_trip("Example.COM"), "example.com");
+        assert_eq!(round_trip("münchen.de"), "xn--mnchen-3ya.de");
+        assert_eq!(round_trip("M%C3%BCnchen.de"), "xn--mnchen-3ya.de");
+        assert_eq!(round_trip("XN--MNCHEN-3YA.de"), "xn--mnchen-3ya.de");
+        assert_eq!(
+            Host::parse("xn--mnchen-3ya.de").unwrap().to_unicode(),
+            "münchen.de"
+        );
+        assert_eq!(round_trip("a%2db"), "a-b");
+        assert!(Host::parse("").unwrap().is_empty());
+        for src in ["a b", "a/b", "a%zz", "xn--a.de"] {
+            assert!(Host::parse(src).is_err(), "{}", src);
+        }
+    }
+
+    #[test]
+    fn compare() {
+        let host = Host::parse("münchen.de").unwrap();
+        assert!(host == "MÜNCHEN.de");
+        assert!(host == "xn--mnchen-3ya.de");
+        assert!(host != "muenchen.de");
+        assert!(host != "a b");
+        assert!(Host::parse("[::1]").unwrap() == "[0::1]");
+    }
+}
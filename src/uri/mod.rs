@@ -1,44 +1,72 @@
 use crate::traits::New;
-use std::{default::Default, fmt::Display, vec::Vec};
+use std::{
+    default::Default,
+    fmt::{Debug, Display},
+    vec::Vec,
+};
 
+mod builder;
+mod host;
+mod normalize;
+mod parse;
+mod query;
+mod resolve;
+mod uri_ref;
+
+pub use builder::UriBuilder;
+pub use host::Host;
+pub use normalize::{PathOptions, normalize_path, remove_dot_segments, to_fs_path};
+pub use parse::{UriError, UriErrorKind};
+pub use query::{Query, QueryError, QueryIter, Querys};
+pub use uri_ref::{AuthorityRef, UriRef};
+
+#[derive(Clone, PartialEq, Eq, Hash)]
 pub struct UserInfo {
     pub username: Username,
     pub password: Option<Password>,
 }
 
+#[derive(Clone, PartialEq, Eq, Hash)]
 pub struct Authority {
     pub creds: Option<UserInfo>,
     pub host: Host,
-    pub port: Port,
+    /// `None` if absent or empty, see `Uri::port` for the scheme default
+    pub port: Option<Port>,
 }
 
+/// `==` compares the parsed components but not how scheme and host were
+/// spelled, see `equivalent`
+#[derive(Clone)]
 pub struct Uri {
     pub scheme: Scheme,
-    pub authority: Authority,
+    /// `None` if there was no `//`, `Some` with an empty host for `file:///`
+    pub authority: Option<Authority>,
     pub path: Path,
     pub query: Querys,
-    pub fragment: Fragment,
+    pub fragment: Option<Fragment>,
+    spelling: Option<Spelling>,
 }
 
-pub struct Query {
-    key: String,
-    value: String,
+// `scheme://authority` as it was parsed, only kept where it differs from the
+// canonical spelling. Printed for as long as the components still match it
+#[derive(Clone)]
+struct Spelling {
+    scheme: Scheme,
+    authority: Option<Authority>,
+    text: Box<str>,
 }
 
 pub type Port = u16;
 
-pub type Host = String;
-
 pub type Password = String;
 
 pub type Username = String;
 
 pub type Path = String;
 
-pub type Querys = Vec<Query>;
-
 pub type Fragment = String;
 
+#[derive(Clone, PartialEq, Eq, Hash)]
 pub enum Scheme {
     HTTP,
     HTTPS,
@@ -135,31 +163,47 @@ impl Default for Uri {
     fn default() -> Self {
         Uri {
             scheme: Scheme::new(),
-            authority: Authority::new(),
+            authority: None,
             path: Path::new(),
             query: Querys::new(),
-            fragment: Fragment::new(),
+            fragment: None,
+            spelling: None,
         }
     }
 }
 
 impl Scheme {
+    /// Ignores case, only schemes other than http and https are copied
     fn from_str(src: &str) -> Scheme {
-        match src {
-            "http" => Scheme::HTTP,
-            "https" => Scheme::HTTPS,
-            "" => Scheme::Unknown,
-            _ => Scheme::Other(src.to_string()),
+        if src.eq_ignore_ascii_case("http") {
+            Scheme::HTTP
+        } else if src.eq_ignore_ascii_case("https") {
+            Scheme::HTTPS
+        } else if src.is_empty() {
+            Scheme::Unknown
+        } else {
+            Scheme::Other(src.to_ascii_lowercase())
         }
     }
-    fn as_str(&self) -> &str {
+    /// Lowercase, empty for relative references
+    pub fn as_str(&self) -> &str {
         match self {
-            Scheme::HTTP => "HTTP",
-            Scheme::HTTPS => "HTTPS",
-            Scheme::Unknown => "Unknown",
+            Scheme::HTTP => "http",
+            Scheme::HTTPS => "https",
+            Scheme::Unknown => "",
             Scheme::Other(e) => e,
         }
     }
+    pub fn default_port(&self) -> Option<Port> {
+        default_port(self.as_str())
+    }
+}
+
+fn default_port(scheme: &str) -> Option<Port> {
+    [("http", 80), ("ws", 80), ("https", 443), ("wss", 443), ("ftp", 21)]
+        .iter()
+        .find(|(name, _)| name.eq_ignore_ascii_case(scheme))
+        .map(|(_, port)| *port)
 }
 
 impl Default for Scheme {
@@ -173,7 +217,7 @@ impl Default for Authority {
         Self {
             creds: None,
             host: Host::new(),
-            port: 80,
+            port: None,
         }
     }
 }
@@ -225,92 +269,83 @@ impl Uri {
         0,     0,     0,     0,     0,     0,     0,     0,     0,     0,
         0,     0,     0,     0,     0,     0
     ];
-    pub fn from_string(&mut self, mut src: String) {
-        if let Some((scheme, str)) = src.split_once("://") {
-            self.scheme = Scheme::from_str(scheme);
-            src = str.to_string();
-        } else {
-            self.scheme = Scheme::Unknown
-        };
-        if let Some((authority, exe)) = src.split_once('/') {
-            let mut auth = authority.to_string().clone();
-            if let Some((userinfo, hostport)) = auth.split_once('@') {
-                let auth_without_port = hostport.to_string();
-                self.authority.creds = match userinfo.split_once(':') {
-                    Some((username, password)) => Some(UserInfo {
-                        username: username.to_string(),
-                        password: Some(password.to_string()),
-                    }),
-                    None => Some(UserInfo {
-                        username: userinfo.to_string(),
-                        password: None,
-                    }),
-                };
-                auth = auth_without_port;
-            } else {
-                self.authority.creds = None
-            };
-            if let Some((host, port)) = auth.split_once(':') {
-                self.authority.port = match port.parse::<u16>() {
-                    Ok(port) => port,
-                    Err(_) => Port::default(),
-                };
-                self.authority.host = host.to_string();
-            } else {
-                self.authority.port = Port::default();
-                self.authority.host = auth
-            };
-            src = exe.to_string().clone();
-        } else {
-            self.authority = Authority::new()
-        };
+    pub fn host(&self) -> Option<&Host> {
+        self.authority.as_ref().map(|authority| &authority.host)
+    }
+}
 
-        if let Some((str, fragment)) = src.split_once('#') {
-            self.fragment = fragment.to_string();
-            src = str.to_string();
-        } else {
-            self.fragment = String::new()
-        };
+/// The uri as it was parsed, `HTTP://A%2Eb:080/` prints as sent. Once the
+/// scheme or authority is changed they are printed like `recompose` does,
+/// which is also the spelling of uris that were not parsed
+impl Display for Uri {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.spelling {
+            Some(ref spelling) if spelling.scheme == self.scheme && spelling.authority == self.authority => {
+                f.write_str(&spelling.text)?;
+                f.write_str(&self.recompose_tail())
+            }
+            _ => f.write_str(&self.recompose()),
+        }
+    }
+}
 
-        if let Some((path, query)) = src.split_once('?') {
-            self.path = path.to_string();
-            self.query = query
-                .split('&')
-                .filter_map(|query| match query.split_once('=') {
-                    Some((key, value)) => Some(Query {
-                        key: key.to_string(),
-                        value: value.to_string(),
-                    }),
-                    None => None,
-                })
-                .collect::<Querys>()
-        } else {
-            self.path = src
-        };
+impl PartialEq for Uri {
+    fn eq(&self, other: &Uri) -> bool {
+        self.scheme == other.scheme
+            && self.authority == other.authority
+            && self.path == other.path
+            && self.query == other.query
+            && self.fragment == other.fragment
     }
 }
 
-impl Display for Uri {
+impl Eq for Uri {}
+
+/// Like `==`, without the spelling of scheme and host
+impl std::hash::Hash for Uri {
+    fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+        self.scheme.hash(state);
+        self.authority.hash(state);
+        self.path.hash(state);
+        self.query.hash(state);
+        self.fragment.hash(state);
+    }
+}
+
+impl Display for Authority {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        let userinfo = match &self.authority.creds {
+        f.write_str(&self.recompose())
+    }
+}
+
+impl Debug for Uri {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let userinfo = match self.authority.as_ref().and_then(|a| a.creds.as_ref()) {
             Some(e) => {
                 format!("{}", e)
             }
             None => "Contains no Userinfo".to_string(),
         };
+        let (host, port) = match &self.authority {
+            Some(authority) => (
+                authority.host.to_string(),
+                authority.port.unwrap_or_default(),
+            ),
+            None => (String::new(), Port::default()),
+        };
         write!(
             f,
             "Protokoll: {}\n{}\nHost: {}\nPort: {}\nPath: {}\nQuery: {}\nFragment: {}",
             self.scheme,
             userinfo,
-            self.authority.host,
-            self.authority.port,
+            host,
+            port,
             self.path,
             self.query
                 .iter()
                 .map(|query| { format!("{query}") })
                 .collect::<String>(),
-            self.fragment
+            self.fragment.as_deref().unwrap_or("")
         )
     }
 }
@@ -330,10 +365,77 @@ impl Display for UserInfo {
         write!(f, "    Username: {}\n    Password: {}", self.username, str)
     }
 }
+//At char at Email
 
-impl Display for Query {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "[{}: {}]", self.key, self.value)
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn round_trip() {
+        for src in [
+            "http://a/b/c/d;p?q",
+            "https://user:pw@example.com:8443/a%20b/?x=1&y&z=%C3%BC#frag",
+            "http://[::1]:8080/",
+            "http://[fe80::1%25eth0]/",
+            "http://127.0.0.1/",
+            "mailto:user@example.com",
+            "urn:isbn:0451450523",
+            "file:///etc/hosts",
+            "//example.com/a",
+            "/a/b?c",
+            "../a",
+            "?q",
+            "#f",
+            "",
+        ] {
+            assert_eq!(Uri::parse(src).unwrap().to_string(), src);
+        }
+    }
+
+    #[test]
+    fn spelling() {
+        for (src, canonical) in [
+            ("http://a:/", "http://a/"),
+            ("http://a:080/", "http://a:80/"),
+            ("http://[0:0::1]/", "http://[::1]/"),
+            ("http://[0:0::1%25Eth0]/", "http://[::1%25Eth0]/"),
+            ("http://A%2Eb/", "http://a.b/"),
+            ("HTTP://A%2Eb:080/", "http://a.b:80/"),
+            ("HTTP://Example.COM/", "http://example.com/"),
+            ("Mailto:User@Example.com", "mailto:User@Example.com"),
+            ("//User@Example.COM:/x?Y#Z", "//User@example.com/x?Y#Z"),
+        ] {
+            let uri = Uri::parse(src).unwrap();
+            assert_eq!(uri.to_string(), src);
+            assert_eq!(uri.recompose(), canonical);
+            let again = Uri::parse(canonical).unwrap();
+            assert_eq!(again.to_string(), canonical);
+            assert!(uri == again && uri.equivalent(&again), "{}", src);
+        }
+    }
+
+    #[test]
+    fn changed_spelling() {
+        let mut uri = Uri::parse("HTTP://A%2Eb:080/x?q").unwrap();
+        // Path, query and fragment do not touch the spelling
+        uri.path = "/y".to_string();
+        uri.fragment = Some("f".to_string());
+        assert_eq!(uri.to_string(), "HTTP://A%2Eb:080/y?q#f");
+        uri.authority.as_mut().unwrap().port = Some(81);
+        assert_eq!(uri.to_string(), "http://a.b:81/y?q#f");
+        uri.authority.as_mut().unwrap().port = Some(80);
+        assert_eq!(uri.to_string(), "HTTP://A%2Eb:080/y?q#f");
+        uri.scheme = Scheme::HTTPS;
+        assert_eq!(uri.to_string(), "https://a.b:80/y?q#f");
+
+        let mut uri = Uri::parse("HTTP://Example.com:80/a/./b").unwrap();
+        uri.normalize();
+        assert_eq!(uri.to_string(), "http://example.com/a/b");
+        let mut uri = Uri::parse("HTTP://Example.com/").unwrap();
+        uri.normalize();
+        assert_eq!(uri.to_string(), "http://example.com/");
+        let joined = Uri::parse("HTTP://Example.com/a/b").unwrap().join(&Uri::parse("c").unwrap());
+        assert_eq!(joined.to_string(), "http://example.com/a/c");
     }
 }
-//At char at Email
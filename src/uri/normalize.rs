@@ -0,0 +1,213 @@
+use std::path::{Path as FsPath, PathBuf};
+
+use crate::traits::New;
+
+use super::{
+    Port, Querys, Scheme, Uri,
+    parse::{UriError, UriErrorKind, is_unreserved},
+};
+
+#[derive(Clone)]
+pub struct PathOptions {
+    /// `/a//b` becomes `/a/b`
+    pub merge_slashes: bool,
+}
+
+impl Default for PathOptions {
+    fn default() -> Self {
+        Self {
+            merge_slashes: true,
+        }
+    }
+}
+
+impl New for PathOptions {}
+
+fn hex(b: u8) -> u8 {
+    match b {
+        b'0'..=b'9' => b - b'0',
+        b'a'..=b'f' => b - b'a' + 10,
+        _ => b - b'A' + 10,
+    }
+}
+
+/// RFC 3986 5.2.4
+pub fn remove_dot_segments(path: &str) -> String {
+    let mut input = path;
+    let mut output: Vec<&str> = Vec::new();
+    while !input.is_empty() {
+        if let Some(rest) = input.strip_prefix("../") {
+            input = rest;
+        } else if let Some(rest) = input.strip_prefix("./") {
+            input = rest;
+        } else if input.starts_with("/./") {
+            input = &input[2..];
+        } else if input == "/." {
+            input = "/";
+        } else if input.starts_with("/../") {
+            input = &input[3..];
+            output.pop();
+        } else if input == "/.." {
+            input = "/";
+            output.pop();
+        } else if input == "." || input == ".." {
+            input = "";
+        } else {
+            let start = if input.starts_with('/') { 1 } else { 0 };
+            let end = input[start..]
+                .find('/')
+                .map(|i| i + start)
+                .unwrap_or(input.len());
+            output.push(&input[..end]);
+            input = &input[end..];
+        }
+    }
+    output.concat()
+}
+
+// Decodes escaped unreserved characters and uppercases the other escapes,
+// `strict` refuses the escapes which would change the meaning of a path
+fn escapes(src: &str, strict: bool) -> Result<String, UriError> {
+    let b = src.as_bytes();
+    let mut out = String::with_capacity(src.len());
+    // Text between escapes is copied as a whole, multi-byte characters stay intact
+    let mut copied = 0;
+    let mut i = 0;
+    while i < b.len() {
+        if b[i] == b'%'
+            && i + 2 < b.len()
+            && b[i + 1].is_ascii_hexdigit()
+            && b[i + 2].is_ascii_hexdigit()
+        {
+            out.push_str(&src[copied..i]);
+            let c = hex(b[i + 1]) << 4 | hex(b[i + 2]);
+            match c {
+                b'/' | b'\\' if strict => {
+                    return Err(UriError::new(UriErrorKind::EncodedSlash, i));
+                }
+                0 if strict => return Err(UriError::new(UriErrorKind::EncodedNul, i)),
+                c if is_unreserved(c) => out.push(c as char),
+                c => out.push_str(&format!("%{:02X}", c)),
+            }
+            i += 3;
+            copied = i;
+        } else {
+            i += 1;
+        }
+    }
+    out.push_str(&src[copied..]);
+    Ok(out)
+}
+
+/// Decodes escaped unreserved characters, uppercases the other escapes and
+/// refuses encoded `/`, `\` and NUL which would change the meaning of the path
+pub fn normalize_encoding(path: &str) -> Result<String, UriError> {
+    escapes(path, true)
+}
+
+/// Normalized form used for routing and file lookups
+pub fn normalize_path(path: &str, opts: &PathOptions) -> Result<String, UriError> {
+    let mut path = normalize_encoding(path)?;
+    if opts.merge_slashes {
+        let mut merged = String::with_capacity(path.len());
+        for c in path.chars() {
+            if c != '/' || !merged.ends_with('/') {
+                merged.push(c);
+            }
+        }
+        path = merged;
+    }
+    let mut path = remove_dot_segments(&path);
+    if path.is_empty() {
+        path.push('/');
+    }
+    Ok(path)
+}
+
+/// Maps a normalized path below `root`, `None` if a segment could escape it
+pub fn to_fs_path(root: &FsPath, normalized: &str) -> Option<PathBuf> {
+    let mut out = root.to_path_buf();
+    for segment in normalized.split('/').filter(|s| !s.is_empty()) {
+        let decoded = crate::http::form::percent_decode(segment.as_bytes(), false);
+        let decoded = String::from_utf8(decoded).ok()?;
+        if decoded == "."
+            || decoded == ".."
+            || decoded.contains(['/', '\\', ':', '\0'])
+        {
+            return None;
+        }
+        out.push(decoded);
+    }
+    Some(out)
+}
+
+impl Uri {
+    /// Replaces `path` with its normalized form
+    pub fn normalize_path(&mut self, opts: &PathOptions) -> Result<(), UriError> {
+        self.path = normalize_path(&self.path, opts)?;
+        Ok(())
+    }
+
+    /// The explicit port, otherwise the default of the scheme
+    pub fn port(&self) -> Option<Port> {
+        self.authority
+            .as_ref()
+            .and_then(|authority| authority.port)
+            .or_else(|| self.scheme.default_port())
+    }
+
+    /// RFC 3986 6.2.2 and 6.2.3: case, percent-encoding, dot-segments and
+    /// default ports. Hosts and schemes are already lowercased by `parse`
+    pub fn normalize(&mut self) {
+        let escapes = |src: &str| escapes(src, false).unwrap_or_else(|_| src.to_string());
+        let default_port = self.scheme.default_port();
+        if let Some(ref mut authority) = self.authority {
+            if let Some(ref mut creds) = authority.creds {
+                creds.username = escapes(&creds.username);
+                creds.password = creds.password.as_deref().map(escapes);
+            }
+            if authority.port.is_some() && authority.port == default_port {
+                authority.port = None;
+            }
+        }
+        self.path = remove_dot_segments(&escapes(&self.path));
+        if self.path.is_empty()
+            && self.authority.is_some()
+            && matches!(self.scheme, Scheme::HTTP | Scheme::HTTPS)
+        {
+            self.path.push('/');
+        }
+        if self.query.is_present() {
+            self.query = Querys::parse(&escapes(self.query.raw()));
+        }
+        self.fragment = self.fragment.as_deref().map(escapes);
+        self.spelling = None;
+    }
+
+    /// Compares the normalized forms, `http://Example.com:80/a/./b` is
+    /// equivalent to `http://example.com/a/b`
+    pub fn equivalent(&self, other: &Uri) -> bool {
+        let mut a = self.clone();
+        let mut b = other.clone();
+        a.normalize();
+        b.normalize();
+        a == b
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn escapes() {
+        let opts = PathOptions::new();
+        assert_eq!(normalize_path("/m%c3%bcller/%7euser", &opts).unwrap(), "/m%C3%BCller/~user");
+        assert_eq!(normalize_path("/müller/ä%41/日本", &opts).unwrap(), "/müller/äA/日本");
+        assert_eq!(normalize_path("/a//b/./../c", &opts).unwrap(), "/a/c");
+        let keep = PathOptions { merge_slashes: false };
+        assert_eq!(normalize_path("/a//b", &keep).unwrap(), "/a//b");
+        assert!(normalize_path("/a%2Fb", &opts).is_err());
+        assert!(normalize_path("/a%00", &opts).is_err());
+    }
+}
@@ -0,0 +1,341 @@
+// RFC 3986 Appendix A, every component is checked and nothing falls back to a default
+
+use std::{error::Error, fmt::Display};
+
+use super::{Authority, AuthorityRef, Host, Port, Uri, UriRef};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UriError {
+    kind: UriErrorKind,
+    pos: usize,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UriErrorKind {
+    InvalidChar,
+    InvalidPercent,
+    InvalidScheme,
+    InvalidUserinfo,
+    InvalidHost,
+    InvalidPort,
+    InvalidPath,
+    InvalidQuery,
+    InvalidFragment,
+    EncodedSlash,
+    EncodedNul,
+}
+
+impl UriError {
+    pub(super) fn new(kind: UriErrorKind, pos: usize) -> UriError {
+        UriError { kind, pos }
+    }
+    pub fn kind(&self) -> UriErrorKind {
+        self.kind
+    }
+    /// Byte offset in the parsed string
+    pub fn pos(&self) -> usize {
+        self.pos
+    }
+    pub(super) fn offset(mut self, by: usize) -> UriError {
+        self.pos += by;
+        self
+    }
+}
+
+impl UriErrorKind {
+    pub fn reason(&self) -> &str {
+        match self {
+            UriErrorKind::InvalidChar => "Invalid Character",
+            UriErrorKind::InvalidPercent => "Invalid Percent Encoding",
+            UriErrorKind::InvalidScheme => "Invalid Scheme",
+            UriErrorKind::InvalidUserinfo => "Invalid Userinfo",
+            UriErrorKind::InvalidHost => "Invalid Host",
+            UriErrorKind::InvalidPort => "Invalid Port",
+            UriErrorKind::InvalidPath => "Invalid Path",
+            UriErrorKind::InvalidQuery => "Invalid Query",
+            UriErrorKind::InvalidFragment => "Invalid Fragment",
+            UriErrorKind::EncodedSlash => "Encoded Slash in Path",
+            UriErrorKind::EncodedNul => "Encoded NUL in Path",
+        }
+    }
+}
+
+impl Display for UriError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} at Position {}", self.kind.reason(), self.pos)
+    }
+}
+
+impl Error for UriError {}
+
+pub(super) fn is_unreserved(b: u8) -> bool {
+    b.is_ascii_alphanumeric() || matches!(b, b'-' | b'.' | b'_' | b'~')
+}
+
+pub(super) fn is_sub_delim(b: u8) -> bool {
+    matches!(
+        b,
+        b'!' | b'$' | b'&' | b'\'' | b'(' | b')' | b'*' | b'+' | b',' | b';' | b'='
+    )
+}
+
+pub(super) fn is_pchar(b: u8) -> bool {
+    is_unreserved(b) || is_sub_delim(b) || b == b':' || b == b'@'
+}
+
+/// Checks `src` against `allowed` and that every `%` starts a valid escape
+pub(super) fn check(
+    src: &str,
+    allowed: impl Fn(u8) -> bool,
+    kind: UriErrorKind,
+) -> Result<(), UriError> {
+    let b = src.as_bytes();
+    let mut i = 0;
+    while i < b.len() {
+        if b[i] == b'%' {
+            if i + 2 >= b.len()
+                || !b[i + 1].is_ascii_hexdigit()
+                || !b[i + 2].is_ascii_hexdigit()
+            {
+                return Err(UriError::new(UriErrorKind::InvalidPercent, i));
+            }
+            i += 3;
+            continue;
+        }
+        if !allowed(b[i]) {
+            return Err(UriError::new(kind, i));
+        }
+        i += 1;
+    }
+    Ok(())
+}
+
+fn check_scheme(src: &str) -> Result<(), UriError> {
+    let b = src.as_bytes();
+    if !b[0].is_ascii_alphabetic() {
+        return Err(UriError::new(UriErrorKind::InvalidScheme, 0));
+    }
+    if let Some(i) = b
+        .iter()
+        .position(|c| !(c.is_ascii_alphanumeric() || matches!(c, b'+' | b'-' | b'.')))
+    {
+        return Err(UriError::new(UriErrorKind::InvalidScheme, i));
+    }
+    Ok(())
+}
+
+impl<'a> AuthorityRef<'a> {
+    /// `[ userinfo "@" ] host [ ":" port ]`, also the authority-form of CONNECT
+    pub fn parse(src: &'a str) -> Result<AuthorityRef<'a>, UriError> {
+        let (userinfo, host_port, offset) = match src.rfind('@') {
+            Some(at) => {
+                let userinfo = &src[..at];
+                check(
+                    userinfo,
+                    |b| is_unreserved(b) || is_sub_delim(b) || b == b':',
+                    UriErrorKind::InvalidUserinfo,
+                )?;
+                (Some(userinfo), &src[at + 1..], at + 1)
+            }
+            None => (None, src, 0),
+        };
+        // The port starts after the last `:` which is not inside an IP-literal
+        let port_start = match host_port.rfind(':') {
+            Some(i) if !host_port[i..].contains(']') => Some(i),
+            _ => None,
+        };
+        let (host, port) = match port_start {
+            Some(i) => (&host_port[..i], Some(&host_port[i + 1..])),
+            None => (host_port, None),
+        };
+        Host::validate(host).map_err(|e| e.offset(offset))?;
+        let port = match port {
+            Some(port) => {
+                let pos = offset + host.len() + 1;
+                if let Some(i) = port.bytes().position(|b| !b.is_ascii_digit()) {
+                    return Err(UriError::new(UriErrorKind::InvalidPort, pos + i));
+                }
+                match port {
+                    "" => None,
+                    port => Some(
+                        port.parse::<Port>()
+                            .map_err(|_| UriError::new(UriErrorKind::InvalidPort, pos))?,
+                    ),
+                }
+            }
+            None => None,
+        };
+        Ok(AuthorityRef {
+            src,
+            userinfo,
+            host,
+            port,
+        })
+    }
+}
+
+impl Authority {
+    /// `[ userinfo "@" ] host [ ":" port ]`, also the authority-form of CONNECT
+    pub fn parse(src: &str) -> Result<Authority, UriError> {
+        AuthorityRef::parse(src).map(AuthorityRef::into_owned)
+    }
+}
+
+impl<'a> UriRef<'a> {
+    /// Parses a URI-reference (absolute URI or relative reference) without allocating
+    pub fn parse(src: &'a str) -> Result<UriRef<'a>, UriError> {
+        if let Some(i) = src
+            .bytes()
+            .position(|b| Uri::VALIDCHARS[b as usize] == 0 && b != b'%')
+        {
+            return Err(UriError::new(UriErrorKind::InvalidChar, i));
+        }
+
+        let mut uri = UriRef {
+            src,
+            scheme: None,
+            authority: None,
+            path: "",
+            query: None,
+            fragment: None,
+        };
+        let mut rest = src;
+        let mut pos = 0;
+
+        // Fragment and query are split first, they may contain every other delimiter
+        if let Some(i) = rest.find('#') {
+            let fragment = &rest[i + 1..];
+            check(
+                fragment,
+                |b| is_pchar(b) || b == b'/' || b == b'?',
+                UriErrorKind::InvalidFragment,
+            )
+            .map_err(|e| e.offset(i + 1))?;
+            uri.fragment = Some(fragment);
+            rest = &rest[..i];
+        }
+        if let Some(i) = rest.find('?') {
+            let query = &rest[i + 1..];
+            check(
+                query,
+                |b| is_pchar(b) || b == b'/' || b == b'?',
+                UriErrorKind::InvalidQuery,
+            )
+            .map_err(|e| e.offset(i + 1))?;
+            uri.query = Some(query);
+            rest = &rest[..i];
+        }
+
+        // Only a leading letter makes a scheme, `1a:b` and `:a` are relative
+        // paths and rejected below for the `:` in their first segment
+        if let Some(i) = rest.find([':', '/']) {
+            if rest.as_bytes()[i] == b':' && rest.as_bytes()[0].is_ascii_alphabetic() {
+                check_scheme(&rest[..i])?;
+                uri.scheme = Some(&rest[..i]);
+                rest = &rest[i + 1..];
+                pos = i + 1;
+            }
+        }
+
+        if let Some(hier) = rest.strip_prefix("//") {
+            let end = hier.find('/').unwrap_or(hier.len());
+            uri.authority =
+                Some(AuthorityRef::parse(&hier[..end]).map_err(|e| e.offset(pos + 2))?);
+            rest = &hier[end..];
+            pos += 2 + end;
+        } else if uri.scheme.is_none() {
+            // path-noscheme: the first segment must not contain `:`
+            let first = rest.split('/').next().unwrap();
+            if let Some(i) = first.find(':') {
+                return Err(UriError::new(UriErrorKind::InvalidPath, pos + i));
+            }
+        }
+
+        check(
+            rest,
+            |b| is_pchar(b) || b == b'/',
+            UriErrorKind::InvalidPath,
+        )
+        .map_err(|e| e.offset(pos))?;
+        uri.path = rest;
+        Ok(uri)
+    }
+}
+
+impl Uri {
+    /// Parses a URI-reference (absolute URI or relative reference)
+    pub fn parse(src: &str) -> Result<Uri, UriError> {
+        UriRef::parse(src).map(UriRef::into_owned)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn error(src: &str) -> (UriErrorKind, usize) {
+        let e = UriRef::parse(src).unwrap_err();
+        (e.kind(), e.pos())
+    }
+
+    #[test]
+    fn scheme() {
+        assert_eq!(error("ht!tp://a"), (UriErrorKind::InvalidScheme, 2));
+        assert_eq!(error("h_p:x"), (UriErrorKind::InvalidScheme, 1));
+        // Not a scheme at all, but a relative path with `:` in its first segment
+        assert_eq!(error("1a:b"), (UriErrorKind::InvalidPath, 2));
+        assert_eq!(error(":a"), (UriErrorKind::InvalidPath, 0));
+        assert!(UriRef::parse("./a:b").is_ok());
+        assert!(UriRef::parse("a/b:c").is_ok());
+    }
+
+    #[test]
+    fn port() {
+        assert_eq!(error("http://a:8x/"), (UriErrorKind::InvalidPort, 10));
+        assert_eq!(error("http://u@a:+1/"), (UriErrorKind::InvalidPort, 11));
+        assert_eq!(error("http://a:99999/"), (UriErrorKind::InvalidPort, 9));
+        let uri = UriRef::parse("http://a:/").unwrap();
+        let authority = uri.authority.unwrap();
+        assert_eq!((authority.host, authority.port), ("a", None));
+    }
+
+    #[test]
+    fn ip_literal() {
+        // An unclosed `[` is reported where the `]` is missing
+        assert_eq!(error("http://[v1.x/"), (UriErrorKind::InvalidHost, 12));
+        assert_eq!(error("http://[::g]/"), (UriErrorKind::InvalidHost, 8));
+        assert_eq!(error("http://[v1]/"), (UriErrorKind::InvalidHost, 8));
+        assert_eq!(error("http://[vx.a]/"), (UriErrorKind::InvalidHost, 9));
+        assert_eq!(error("http://[v1.]/"), (UriErrorKind::InvalidHost, 11));
+        assert_eq!(error("http://[v1.a%]/"), (UriErrorKind::InvalidHost, 12));
+        assert_eq!(error("http://[::1%25]/"), (UriErrorKind::InvalidHost, 14));
+    }
+
+    #[test]
+    fn percent() {
+        assert_eq!(error("/a%zz"), (UriErrorKind::InvalidPercent, 2));
+        assert_eq!(error("/a?b=%4"), (UriErrorKind::InvalidPercent, 5));
+        assert_eq!(error("/#%"), (UriErrorKind::InvalidPercent, 2));
+        assert_eq!(error("http://a%2/"), (UriErrorKind::InvalidPercent, 8));
+        assert_eq!(error("http://u%zz@a/"), (UriErrorKind::InvalidPercent, 8));
+        assert!(UriRef::parse("/a%2F%41").is_ok());
+    }
+
+    #[test]
+    fn authority() {
+        let uri = UriRef::parse("file:///etc/hosts").unwrap();
+        assert_eq!(uri.authority.unwrap().host, "");
+        assert_eq!(uri.path, "/etc/hosts");
+        let uri = UriRef::parse("//").unwrap();
+        assert_eq!(uri.authority.unwrap().as_str(), "");
+
+        let uri = UriRef::parse("http://u:p@h/").unwrap();
+        assert_eq!(uri.authority.unwrap().userinfo, Some("u:p"));
+        let uri = UriRef::parse("http://@h/").unwrap();
+        assert_eq!(uri.authority.unwrap().userinfo, Some(""));
+        assert_eq!(error("http://u[@h/"), (UriErrorKind::InvalidUserinfo, 8));
+        assert_eq!(error("http://u@h@x/"), (UriErrorKind::InvalidUserinfo, 8));
+        assert_eq!(error("http://a^b/"), (UriErrorKind::InvalidChar, 8));
+        assert_eq!(error("http://a=[b/"), (UriErrorKind::InvalidHost, 9));
+    }
+}
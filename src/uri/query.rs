@@ -0,0 +1,301 @@
+use std::{borrow::Cow, error::Error, fmt::Display, str::FromStr};
+
+use crate::{http::form::decode_lossy, traits::New};
+
+/// One `key=value` or `flag` parameter of a query string, borrowed from its
+/// `Querys`. Key and value are only decoded when asked for
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub struct Query<'a> {
+    raw_key: &'a str,
+    raw_value: Option<&'a str>,
+}
+
+/// Query parameters in the order they were sent, repeated keys are kept.
+/// Only the raw string is stored, parameters are split and decoded on access
+#[derive(Clone, PartialEq, Eq, Hash, Debug)]
+pub struct Querys {
+    raw: Option<String>,
+}
+
+/// The parameters of a `Querys`, empty fields like in `a&&b` are skipped
+pub struct QueryIter<'a> {
+    fields: std::str::Split<'a, char>,
+}
+
+#[derive(Debug)]
+pub enum QueryError {
+    Missing(String),
+    NoValue(String),
+    Invalid {
+        key: String,
+        value: String,
+        reason: String,
+    },
+}
+
+impl<'a> Query<'a> {
+    pub fn parse(src: &'a str) -> Query<'a> {
+        let (raw_key, raw_value) = match src.split_once('=') {
+            Some((key, value)) => (key, Some(value)),
+            None => (src, None),
+        };
+        Query { raw_key, raw_value }
+    }
+    /// Borrowed unless the key contains escapes or `+`
+    pub fn key(&self) -> Cow<'a, str> {
+        decode_lossy(self.raw_key)
+    }
+    /// `None` for flags like `?debug`, `Some("")` for `?debug=`
+    pub fn value(&self) -> Option<Cow<'a, str>> {
+        self.raw_value.map(decode_lossy)
+    }
+    pub fn raw_key(&self) -> &'a str {
+        self.raw_key
+    }
+    pub fn raw_value(&self) -> Option<&'a str> {
+        self.raw_value
+    }
+    pub fn is_flag(&self) -> bool {
+        self.raw_value.is_none()
+    }
+    fn is(&self, key: &str) -> bool {
+        self.key() == key
+    }
+}
+
+impl Querys {
+    pub fn parse(src: &str) -> Querys {
+        Querys {
+            raw: Some(src.to_string()),
+        }
+    }
+    /// The query string as it was received, without `?`
+    pub fn raw(&self) -> &str {
+        self.raw.as_deref().unwrap_or("")
+    }
+    /// False if the uri had no `?` at all
+    pub fn is_present(&self) -> bool {
+        self.raw.is_some()
+    }
+    /// First value of `key`, flags have no value
+    pub fn get(&self, key: &str) -> Option<Cow<'_, str>> {
+        self.iter().find(|q| q.is(key)).and_then(|q| q.value())
+    }
+    pub fn get_all<'a>(&'a self, key: &'a str) -> impl Iterator<Item = Cow<'a, str>> {
+        self.iter().filter(move |q| q.is(key)).filter_map(|q| q.value())
+    }
+    /// True if `key` is present at all, with or without value
+    pub fn has(&self, key: &str) -> bool {
+        self.iter().any(|q| q.is(key))
+    }
+    /// First value of `key` parsed as `T`
+    pub fn parse_value<T>(&self, key: &str) -> Result<T, QueryError>
+    where
+        T: FromStr,
+        T::Err: Display,
+    {
+        let query = self
+            .iter()
+            .find(|q| q.is(key))
+            .ok_or_else(|| QueryError::Missing(key.to_string()))?;
+        let value = query
+            .value()
+            .ok_or_else(|| QueryError::NoValue(key.to_string()))?;
+        value.parse().map_err(|e: T::Err| QueryError::Invalid {
+            key: key.to_string(),
+            value: value.into_owned(),
+            reason: e.to_string(),
+        })
+    }
+    /// Like `parse_value` but a missing key is `Ok(None)`
+    pub fn parse_opt<T>(&self, key: &str) -> Result<Option<T>, QueryError>
+    where
+        T: FromStr,
+        T::Err: Display,
+    {
+        match self.parse_value(key) {
+            Ok(v) => Ok(Some(v)),
+            Err(QueryError::Missing(_)) => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+    /// Every value of `key` parsed as `T`
+    pub fn parse_all<T>(&self, key: &str) -> Result<Vec<T>, QueryError>
+    where
+        T: FromStr,
+        T::Err: Display,
+    {
+        self.get_all(key)
+            .map(|value| {
+                value.parse().map_err(|e: T::Err| QueryError::Invalid {
+                    key: key.to_string(),
+                    value: value.into_owned(),
+                    reason: e.to_string(),
+                })
+            })
+            .collect()
+    }
+    pub fn iter(&self) -> QueryIter<'_> {
+        QueryIter {
+            fields: self.raw().split('&'),
+        }
+    }
+    pub fn len(&self) -> usize {
+        self.iter().count()
+    }
+    pub fn is_empty(&self) -> bool {
+        self.iter().next().is_none()
+    }
+}
+
+impl<'a> Iterator for QueryIter<'a> {
+    type Item = Query<'a>;
+    fn next(&mut self) -> Option<Query<'a>> {
+        self.fields
+            .find(|field| !field.is_empty())
+            .map(Query::parse)
+    }
+}
+
+impl Default for Querys {
+    fn default() -> Self {
+        Self { raw: None }
+    }
+}
+
+impl New for Querys {}
+
+impl<'a> IntoIterator for &'a Querys {
+    type Item = Query<'a>;
+    type IntoIter = QueryIter<'a>;
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+impl Display for Query<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.value() {
+            Some(value) => write!(f, "[{}: {}]", self.key(), value),
+            None => write!(f, "[{}]", self.key()),
+        }
+    }
+}
+
+impl Display for QueryError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            QueryError::Missing(key) => write!(f, "Missing parameter \"{}\"", key),
+            QueryError::NoValue(key) => write!(f, "Parameter \"{}\" has no value", key),
+            QueryError::Invalid { key, value, reason } => write!(
+                f,
+                "Invalid value \"{}\" for parameter \"{}\": {}",
+                value, key, reason
+            ),
+        }
+    }
+}
+
+impl Error for QueryError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn flags() {
+        let query = Querys::parse("debug&empty=&a=1");
+        let fields: Vec<_> = query.iter().collect();
+        assert!(fields[0].is_flag());
+        assert_eq!(fields[0].value(), None);
+        assert!(!fields[1].is_flag());
+        assert_eq!(fields[1].value().as_deref(), Some(""));
+        assert!(query.has("debug"));
+        assert_eq!(query.get("debug"), None);
+        assert_eq!(query.get("empty").as_deref(), Some(""));
+        assert!(!query.has("missing"));
+    }
+
+    #[test]
+    fn order() {
+        let query = Querys::parse("b=1&&a=2&b=3&c&b");
+        let keys: Vec<_> = query.iter().map(|q| q.key()).collect();
+        assert_eq!(keys, ["b", "a", "b", "c", "b"]);
+        assert_eq!(query.len(), 5);
+        assert_eq!(query.get("b").as_deref(), Some("1"));
+        // Flags have no value to collect
+        assert_eq!(query.get_all("b").collect::<Vec<_>>(), ["1", "3"]);
+        assert_eq!(query.get_all("x").count(), 0);
+    }
+
+    #[test]
+    fn decoded() {
+        let query = Querys::parse("a%20b=c+d&%C3%BC=%FF");
+        let first = query.iter().next().unwrap();
+        assert_eq!(first.raw_key(), "a%20b");
+        assert_eq!(first.raw_value(), Some("c+d"));
+        assert_eq!(query.get("a b").as_deref(), Some("c d"));
+        assert_eq!(query.get("ü").as_deref(), Some("\u{FFFD}"));
+        assert_eq!(query.raw(), "a%20b=c+d&%C3%BC=%FF");
+    }
+
+    #[test]
+    fn presence() {
+        assert!(!Querys::new().is_present());
+        assert!(Querys::new().is_empty());
+        let empty = Querys::parse("");
+        assert!(empty.is_present());
+        assert!(empty.is_empty());
+        assert!(Querys::parse("&&").is_empty());
+    }
+
+    #[test]
+    fn parse_value() {
+        let query = Querys::parse("page=2&flag&page=x&n=1&n=2");
+        assert_eq!(query.parse_value::<u32>("page").unwrap(), 2);
+        assert!(matches!(
+            query.parse_value::<u32>("size"),
+            Err(QueryError::Missing(key)) if key == "size"
+        ));
+        assert!(matches!(
+            query.parse_value::<u32>("flag"),
+            Err(QueryError::NoValue(key)) if key == "flag"
+        ));
+        let e = Querys::parse("page=x")
+            .parse_value::<u32>("page")
+            .unwrap_err();
+        assert!(
+            matches!(&e, QueryError::Invalid { key, value, .. } if key == "page" && value == "x")
+        );
+        assert_eq!(
+            e.to_string(),
+            "Invalid value \"x\" for parameter \"page\": invalid digit found in string"
+        );
+    }
+
+    #[test]
+    fn parse_opt() {
+        let query = Querys::parse("a=1&b&c=x");
+        assert_eq!(query.parse_opt::<u8>("a").unwrap(), Some(1));
+        assert_eq!(query.parse_opt::<u8>("z").unwrap(), None);
+        assert!(matches!(
+            query.parse_opt::<u8>("b"),
+            Err(QueryError::NoValue(_))
+        ));
+        assert!(matches!(
+            query.parse_opt::<u8>("c"),
+            Err(QueryError::Invalid { .. })
+        ));
+    }
+
+    #[test]
+    fn parse_all() {
+        let query = Querys::parse("n=1&n&n=3&m=1&m=x");
+        assert_eq!(query.parse_all::<u8>("n").unwrap(), [1, 3]);
+        assert!(query.parse_all::<u8>("z").unwrap().is_empty());
+        assert!(matches!(
+            query.parse_all::<u8>("m"),
+            Err(QueryError::Invalid { value, .. }) if value == "x"
+        ));
+    }
+}
@@ -0,0 +1,288 @@
+// RFC 3986 5.2 (reference resolution) and 5.3 (recomposition)
+
+use crate::traits::New;
+
+use super::{Authority, Scheme, Uri, UriError, remove_dot_segments};
+
+impl Authority {
+    pub fn recompose(&self) -> String {
+        let mut s = String::new();
+        if let Some(ref creds) = self.creds {
+            s.push_str(&creds.username);
+            if let Some(ref password) = creds.password {
+                s.push(':');
+                s.push_str(password);
+            }
+            s.push('@');
+        }
+        s.push_str(&self.host.to_string());
+        if let Some(port) = self.port {
+            s.push_str(&format!(":{}", port));
+        }
+        s
+    }
+}
+
+impl Uri {
+    /// RFC 3986 5.3
+    pub fn recompose(&self) -> String {
+        let mut s = String::new();
+        if self.scheme != Scheme::Unknown {
+            s.push_str(self.scheme.as_str());
+            s.push(':');
+        }
+        if let Some(ref authority) = self.authority {
+            s.push_str("//");
+            s.push_str(&authority.recompose());
+        }
+        s.push_str(&self.recompose_tail());
+        s
+    }
+
+    // Path, query and fragment, which are always kept as sent
+    pub(super) fn recompose_tail(&self) -> String {
+        let mut s = self.path.clone();
+        if self.query.is_present() {
+            s.push('?');
+            s.push_str(self.query.raw());
+        }
+        if let Some(ref fragment) = self.fragment {
+            s.push('#');
+            s.push_str(fragment);
+        }
+        s
+    }
+
+    pub fn is_absolute(&self) -> bool {
+        self.scheme != Scheme::Unknown
+    }
+
+    /// Resolves `reference` against `self` (RFC 3986 5.2.2), `self` should be absolute
+    pub fn join(&self, reference: &Uri) -> Uri {
+        let mut target = Uri::new();
+        if reference.scheme != Scheme::Unknown {
+            target.scheme = reference.scheme.clone();
+            target.authority = reference.authority.clone();
+            target.path = remove_dot_segments(&reference.path);
+            target.query = reference.query.clone();
+        } else {
+            if reference.authority.is_some() {
+                target.authority = reference.authority.clone();
+                target.path = remove_dot_segments(&reference.path);
+                target.query = reference.query.clone();
+            } else {
+                if reference.path.is_empty() {
+                    target.path = self.path.clone();
+                    target.query = if reference.query.is_present() {
+                        reference.query.clone()
+                    } else {
+                        self.query.clone()
+                    };
+                } else {
+                    target.path = if reference.path.starts_with('/') {
+                        remove_dot_segments(&reference.path)
+                    } else {
+                        remove_dot_segments(&self.merge(&reference.path))
+                    };
+                    target.query = reference.query.clone();
+                }
+                target.authority = self.authority.clone();
+            }
+            target.scheme = self.scheme.clone();
+        }
+        target.fragment = reference.fragment.clone();
+        target
+    }
+
+    /// Parses `reference` and resolves it against `self`
+    pub fn join_str(&self, reference: &str) -> Result<Uri, UriError> {
+        Ok(self.join(&Uri::parse(reference)?))
+    }
+
+    // RFC 3986 5.2.3
+    fn merge(&self, reference: &str) -> String {
+        if self.authority.is_some() && self.path.is_empty() {
+            format!("/{}", reference)
+        } else {
+            match self.path.rfind('/') {
+                Some(i) => format!("{}{}", &self.path[..=i], reference),
+                None => reference.to_string(),
+            }
+        }
+    }
+
+    /// The shortest relative reference which `join`s back to `target`. `None`
+    /// if scheme or authority differ or no reference reaches `target`
+    pub fn make_relative(&self, target: &Uri) -> Option<Uri> {
+        if self.scheme != target.scheme
+            || self.authority.as_ref().map(Authority::recompose)
+                != target.authority.as_ref().map(Authority::recompose)
+        {
+            return None;
+        }
+        let mut relative = Uri::new();
+        relative.fragment = target.fragment.clone();
+        if self.path == target.path {
+            if self.query == target.query {
+                return Some(relative);
+            }
+            if target.query.is_present() {
+                relative.query = target.query.clone();
+                return Some(relative);
+            }
+        }
+        relative.query = target.query.clone();
+        if target.path.is_empty() {
+            // An empty path takes the path of the base, only `//host` keeps it empty
+            relative.authority = Some(target.authority.clone()?);
+            return Some(relative);
+        }
+        if !target.path.starts_with('/') || !self.path.starts_with('/') {
+            relative.path = match target.path.starts_with('/') {
+                true if target.path.starts_with("//") => format!("/.{}", target.path),
+                true => target.path.clone(),
+                // Merged with the directory of the base, which only a base without `/` lacks
+                false if self.path.contains('/') => return None,
+                false if target.path.split('/').next().unwrap().contains(':') => {
+                    format!("./{}", target.path)
+                }
+                false => target.path.clone(),
+            };
+            return Some(relative);
+        }
+
+        let base_dir: Vec<&str> = self.path[..=self.path.rfind('/').unwrap()]
+            .split('/')
+            .filter(|s| !s.is_empty())
+            .collect();
+        let (target_dir, target_last) = target.path.split_at(target.path.rfind('/').unwrap() + 1);
+        let target_dir: Vec<&str> = target_dir.split('/').filter(|s| !s.is_empty()).collect();
+        // Empty segments can not be expressed with `..`, use the absolute path then
+        if self.path.contains("//") || target.path.contains("//") {
+            relative.path = match target.path.starts_with("//") {
+                // `//` would start an authority
+                true => format!("/.{}", target.path),
+                false => target.path.clone(),
+            };
+            return Some(relative);
+        }
+        let common = base_dir
+            .iter()
+            .zip(target_dir.iter())
+            .take_while(|(a, b)| a == b)
+            .count();
+        let mut path = "../".repeat(base_dir.len() - common);
+        for segment in &target_dir[common..] {
+            path.push_str(segment);
+            path.push('/');
+        }
+        path.push_str(target_last);
+        if path.is_empty() {
+            path.push_str("./");
+        }
+        // A first segment with `:` would be read as a scheme
+        if path.split('/').next().unwrap().contains(':') {
+            path.insert_str(0, "./");
+        }
+        relative.path = path;
+        Some(relative)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const BASE: &str = "http://a/b/c/d;p?q";
+
+    fn join(reference: &str) -> String {
+        Uri::parse(BASE).unwrap().join_str(reference).unwrap().to_string()
+    }
+
+    // RFC 3986 5.4.1
+    #[test]
+    fn normal_examples() {
+        for (reference, target) in [
+            ("g:h", "g:h"),
+            ("g", "http://a/b/c/g"),
+            ("./g", "http://a/b/c/g"),
+            ("g/", "http://a/b/c/g/"),
+            ("/g", "http://a/g"),
+            ("//g", "http://g"),
+            ("?y", "http://a/b/c/d;p?y"),
+            ("g?y", "http://a/b/c/g?y"),
+            ("#s", "http://a/b/c/d;p?q#s"),
+            ("g#s", "http://a/b/c/g#s"),
+            ("g?y#s", "http://a/b/c/g?y#s"),
+            (";x", "http://a/b/c/;x"),
+            ("g;x", "http://a/b/c/g;x"),
+            ("g;x?y#s", "http://a/b/c/g;x?y#s"),
+            ("", "http://a/b/c/d;p?q"),
+            (".", "http://a/b/c/"),
+            ("./", "http://a/b/c/"),
+            ("..", "http://a/b/"),
+            ("../", "http://a/b/"),
+            ("../g", "http://a/b/g"),
+            ("../..", "http://a/"),
+            ("../../", "http://a/"),
+            ("../../g", "http://a/g"),
+        ] {
+            assert_eq!(join(reference), target, "{reference}");
+        }
+    }
+
+    // RFC 3986 5.4.2
+    #[test]
+    fn abnormal_examples() {
+        for (reference, target) in [
+            ("../../../g", "http://a/g"),
+            ("../../../../g", "http://a/g"),
+            ("/./g", "http://a/g"),
+            ("/../g", "http://a/g"),
+            ("g.", "http://a/b/c/g."),
+            (".g", "http://a/b/c/.g"),
+            ("g..", "http://a/b/c/g.."),
+            ("..g", "http://a/b/c/..g"),
+            ("./../g", "http://a/b/g"),
+            ("./g/.", "http://a/b/c/g/"),
+            ("g/./h", "http://a/b/c/g/h"),
+            ("g/../h", "http://a/b/c/h"),
+            ("g;x=1/./y", "http://a/b/c/g;x=1/y"),
+            ("g;x=1/../y", "http://a/b/c/y"),
+            ("g?y/./x", "http://a/b/c/g?y/./x"),
+            ("g?y/../x", "http://a/b/c/g?y/../x"),
+            ("g#s/./x", "http://a/b/c/g#s/./x"),
+            ("g#s/../x", "http://a/b/c/g#s/../x"),
+            ("http:g", "http:g"),
+        ] {
+            assert_eq!(join(reference), target, "{reference}");
+        }
+    }
+
+    #[test]
+    fn make_relative() {
+        let relative = |base: &str, target: &str| {
+            let base = Uri::parse(base).unwrap();
+            let target = Uri::parse(target).unwrap();
+            let relative = base.make_relative(&target)?;
+            assert_eq!(base.join(&relative), target, "{relative}");
+            Some(relative.to_string())
+        };
+        assert_eq!(relative("http://a/x", "http://a").as_deref(), Some("//a"));
+        assert_eq!(relative("http://a/x?q", "http://a?q").as_deref(), Some("//a?q"));
+        assert_eq!(relative("http://a", "http://a").as_deref(), Some(""));
+        assert_eq!(relative("http://a", "http://a/").as_deref(), Some("/"));
+        assert_eq!(relative(BASE, "http://a/b/c/g").as_deref(), Some("g"));
+        assert_eq!(relative(BASE, "http://a/b/g?y#s").as_deref(), Some("../g?y#s"));
+        assert_eq!(relative(BASE, "http://a/b/c/d;p?y").as_deref(), Some("?y"));
+        assert_eq!(relative(BASE, "http://a/b/c/").as_deref(), Some("./"));
+        assert_eq!(relative(BASE, "http://a/b/c/g:h").as_deref(), Some("./g:h"));
+        assert_eq!(relative(BASE, "http://a//x").as_deref(), Some("/.//x"));
+        assert_eq!(relative("foo:x", "foo:y:z").as_deref(), Some("./y:z"));
+        assert_eq!(relative("foo:x", "foo:/y").as_deref(), Some("/y"));
+        assert_eq!(relative("foo:x/y", "foo:z"), None);
+        assert_eq!(relative("foo:x", "foo:"), None);
+        assert_eq!(relative(BASE, "https://a/b"), None);
+        assert_eq!(relative(BASE, "http://b/b"), None);
+    }
+}
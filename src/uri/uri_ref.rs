@@ -0,0 +1,158 @@
+use std::fmt::Display;
+
+use super::{Authority, Host, Path, Port, Querys, Scheme, Spelling, Uri, UserInfo, default_port};
+
+/// A parsed URI which borrows every component from the parsed string.
+/// Components are raw, neither decoded nor lowercased, use `into_owned` for that
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct UriRef<'a> {
+    pub(super) src: &'a str,
+    pub(super) scheme: Option<&'a str>,
+    pub(super) authority: Option<AuthorityRef<'a>>,
+    pub(super) path: &'a str,
+    pub(super) query: Option<&'a str>,
+    pub(super) fragment: Option<&'a str>,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct AuthorityRef<'a> {
+    pub(super) src: &'a str,
+    pub(super) userinfo: Option<&'a str>,
+    pub(super) host: &'a str,
+    pub(super) port: Option<Port>,
+}
+
+impl<'a> AuthorityRef<'a> {
+    /// The whole parsed authority
+    pub fn as_str(&self) -> &'a str {
+        self.src
+    }
+    pub fn userinfo(&self) -> Option<&'a str> {
+        self.userinfo
+    }
+    /// IP-literals keep their brackets
+    pub fn host(&self) -> &'a str {
+        self.host
+    }
+    pub fn port(&self) -> Option<Port> {
+        self.port
+    }
+    pub fn into_owned(self) -> Authority {
+        let creds = self.userinfo.map(|userinfo| match userinfo.split_once(':') {
+            Some((username, password)) => UserInfo {
+                username: username.to_string(),
+                password: Some(password.to_string()),
+            },
+            None => UserInfo {
+                username: userinfo.to_string(),
+                password: None,
+            },
+        });
+        Authority {
+            creds,
+            // Already validated by `parse`
+            host: Host::parse(self.host).unwrap_or_default(),
+            port: self.port,
+        }
+    }
+}
+
+impl<'a> UriRef<'a> {
+    /// The whole parsed string
+    pub fn as_str(&self) -> &'a str {
+        self.src
+    }
+    pub fn scheme(&self) -> Option<&'a str> {
+        self.scheme
+    }
+    pub fn is_absolute(&self) -> bool {
+        self.scheme.is_some()
+    }
+    pub fn authority(&self) -> Option<&AuthorityRef<'a>> {
+        self.authority.as_ref()
+    }
+    pub fn host(&self) -> Option<&'a str> {
+        self.authority.map(|authority| authority.host)
+    }
+    /// The explicit port, otherwise the default of the scheme
+    pub fn port(&self) -> Option<Port> {
+        self.authority
+            .and_then(|authority| authority.port)
+            .or_else(|| self.scheme.and_then(default_port))
+    }
+    pub fn path(&self) -> &'a str {
+        self.path
+    }
+    /// Without `?`, `None` if there was no `?` at all
+    pub fn query(&self) -> Option<&'a str> {
+        self.query
+    }
+    /// Raw `(key, value)` pairs in order, `None` for flags
+    pub fn query_pairs(&self) -> impl Iterator<Item = (&'a str, Option<&'a str>)> {
+        self.query
+            .unwrap_or("")
+            .split('&')
+            .filter(|field| !field.is_empty())
+            .map(|field| match field.split_once('=') {
+                Some((key, value)) => (key, Some(value)),
+                None => (field, None),
+            })
+    }
+    pub fn fragment(&self) -> Option<&'a str> {
+        self.fragment
+    }
+    pub fn into_owned(self) -> Uri {
+        self.into_owned_with_path(self.path.to_string())
+    }
+    /// `into_owned` with `path` in place of the parsed path, a normalized
+    /// path is not copied twice
+    pub fn into_owned_with_path(self, path: Path) -> Uri {
+        let scheme = match self.scheme {
+            Some(scheme) => Scheme::from_str(scheme),
+            None => Scheme::Unknown,
+        };
+        let authority = self.authority.map(AuthorityRef::into_owned);
+        Uri {
+            spelling: self.spelling(&scheme, authority.as_ref()),
+            scheme,
+            authority,
+            path,
+            query: match self.query {
+                Some(query) => Querys::parse(query),
+                None => Querys::default(),
+            },
+            fragment: self.fragment.map(|fragment| fragment.to_string()),
+        }
+    }
+    // `None` if the scheme and authority were sent in their canonical spelling
+    fn spelling(&self, scheme: &Scheme, authority: Option<&Authority>) -> Option<Spelling> {
+        let canonical = self.scheme.is_none_or(|raw| raw == scheme.as_str())
+            && self
+                .authority
+                .zip(authority)
+                .is_none_or(|(raw, parsed)| raw.src == parsed.recompose());
+        if canonical {
+            return None;
+        }
+        let mut text = String::new();
+        if let Some(raw) = self.scheme {
+            text.push_str(raw);
+            text.push(':');
+        }
+        if let Some(raw) = self.authority {
+            text.push_str("//");
+            text.push_str(raw.src);
+        }
+        Some(Spelling {
+            scheme: scheme.clone(),
+            authority: authority.cloned(),
+            text: text.into(),
+        })
+    }
+}
+
+impl Display for UriRef<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.src)
+    }
+}
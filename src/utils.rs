@@ -28,7 +28,10 @@ enum Formattype {
     HTML,
     CSS,
     Image(Image),
-    Video
+    Video,
+    Wasm,
+    Woff2,
+    Binary,
 }
 
 #[derive(Clone)]
@@ -36,6 +39,10 @@ enum Image {
     Pdf,
     Jpeg,
     Png,
+    Gif,
+    Svg,
+    Webp,
+    Ico,
 }
 
 pub const HTML: ContType = ContType(Formattype::HTML);
@@ -47,6 +54,13 @@ pub const XML: ContType = ContType(Formattype::Xml);
 pub const PDF: ContType = ContType(Formattype::Image(Image::Pdf));
 pub const JPEG: ContType = ContType(Formattype::Image(Image::Jpeg));
 pub const PNG: ContType = ContType(Formattype::Image(Image::Png));
+pub const GIF: ContType = ContType(Formattype::Image(Image::Gif));
+pub const SVG: ContType = ContType(Formattype::Image(Image::Svg));
+pub const WEBP: ContType = ContType(Formattype::Image(Image::Webp));
+pub const ICO: ContType = ContType(Formattype::Image(Image::Ico));
+pub const WASM: ContType = ContType(Formattype::Wasm);
+pub const WOFF2: ContType = ContType(Formattype::Woff2);
+pub const BINARY: ContType = ContType(Formattype::Binary);
 
 impl ContType { 
     pub fn as_str(&self) -> &str {
@@ -60,7 +74,14 @@ impl ContType {
             Formattype::Image(Image::Pdf) => "application/pdf",
             Formattype::Image(Image::Jpeg) => "image/jpeg",
             Formattype::Image(Image::Png) => "image/png",
-            Formattype::Video => "video/mp4"
+            Formattype::Image(Image::Gif) => "image/gif",
+            Formattype::Image(Image::Svg) => "image/svg+xml",
+            Formattype::Image(Image::Webp) => "image/webp",
+            Formattype::Image(Image::Ico) => "image/x-icon",
+            Formattype::Video => "video/mp4",
+            Formattype::Wasm => "application/wasm",
+            Formattype::Woff2 => "font/woff2",
+            Formattype::Binary => "application/octet-stream",
         }
     }
 
@@ -79,10 +100,39 @@ impl ContType {
             _=>Formattype::Plain
         })
     }
+
+    /// For files, case-insensitive and `application/octet-stream` for anything unknown
+    pub fn from_extension(ext: &str)->ContType {
+        ContType::for_extension(ext).unwrap_or(BINARY)
+    }
+
+    /// Like `from_extension` but `None` for unknown extensions
+    pub fn for_extension(ext: &str)->Option<ContType> {
+        Some(ContType(match ext.to_ascii_lowercase().as_str() {
+            "html" | "htm"=>Formattype::HTML,
+            "css"=>Formattype::CSS,
+            "js" | "mjs"=>Formattype::JS,
+            "txt" | "md"=>Formattype::Plain,
+            "json" | "map"=>Formattype::JSON,
+            "xml"=>Formattype::Xml,
+            "pdf"=>Formattype::Image(Image::Pdf),
+            "jpeg" | "jpg"=>Formattype::Image(Image::Jpeg),
+            "png"=>Formattype::Image(Image::Png),
+            "gif"=>Formattype::Image(Image::Gif),
+            "svg"=>Formattype::Image(Image::Svg),
+            "webp"=>Formattype::Image(Image::Webp),
+            "ico"=>Formattype::Image(Image::Ico),
+            "mp4"=>Formattype::Video,
+            "wasm"=>Formattype::Wasm,
+            "woff2"=>Formattype::Woff2,
+            "bin"=>Formattype::Binary,
+            _=>return None
+        }))
+    }
 }
 
 
-#[derive(Clone, Copy)]
+#[derive(Clone, Copy, PartialEq, Eq)]
 pub struct Version(VV);
 
 
@@ -163,15 +213,25 @@ pub enum AllowedExtentions {
     SetCookie,
     Cookie,
     Lang,
+    ContentType,
+    Host,
+    Accept,
 }
 
 impl HTTPHeader {
+    /// Header names are case-insensitive, `host` is `AllowedExtentions::Host`
     pub fn key_parse(key: &str)->AllowedExtentions {
-        match key {
-            "Lang"=>AllowedExtentions::Lang,
-            "Set-Cookie"=>AllowedExtentions::SetCookie,
-            "Cookie"=>AllowedExtentions::Cookie,
-            _=>{
+        let known = [
+            ("Lang", AllowedExtentions::Lang),
+            ("Set-Cookie", AllowedExtentions::SetCookie),
+            ("Cookie", AllowedExtentions::Cookie),
+            ("Content-Type", AllowedExtentions::ContentType),
+            ("Host", AllowedExtentions::Host),
+            ("Accept", AllowedExtentions::Accept),
+        ];
+        match known.into_iter().find(|(name, _)| name.eq_ignore_ascii_case(key)) {
+            Some((_, known))=>known,
+            None=>{
                 #[cfg(feature = "log_missing_extention")]
                 log(ERROR, format!("Unknown Extention: \"{}\"", key));///////////////////////////////////////////////////////////////////////////////////////////////////////////////////////////////////////////////////////////////////////////////////////////////////
                 AllowedExtentions::Unknown(key.to_string())